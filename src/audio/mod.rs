@@ -6,10 +6,15 @@
 //! [sound hardware wiki]: https://gbdev.gg8.se/wiki/articles/Gameboy_sound_hardware
 
 use std::collections::VecDeque;
-use std::convert::TryInto;
+use std::fmt::{self, Display};
+use std::io;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
+use derivative::Derivative;
+use log::*;
+use thiserror::Error;
 
 use crate::bytes::ByteExt;
 use crate::cpu;
@@ -17,12 +22,23 @@ use crate::memory::Addressable;
 use crate::TCycles;
 
 mod output;
+mod resampler;
+mod sink;
+mod vgm;
 
 use output::Output;
+use sink::SinkOutput;
 
-/// Shared buffer containing PCM audio samples generated by the emulator to be sent to the audio
-/// device.
-type SampleBuffer = Arc<Mutex<VecDeque<f32>>>;
+pub use output::AudioStats;
+pub use sink::{AudioSink, BufferSink, WavSink};
+pub use vgm::{VgmWriter, WriteSeek};
+
+/// Shared buffer containing stereo PCM audio samples generated by the emulator.
+pub type SampleBuffer = Arc<Mutex<VecDeque<[f32; 2]>>>;
+
+/// The number of T-cycles between each time resampled audio is sent to the output, about one
+/// millisecond.
+const FRAME_LENGTH: u32 = cpu::FREQUENCY / 1024;
 
 /// Four available square wave duty cycles.
 const DUTY: [[u8; 8]; 4] = [
@@ -32,40 +48,96 @@ const DUTY: [[u8; 8]; 4] = [
     [0, 1, 1, 1, 1, 1, 1, 0],
 ];
 
+/// The value that each register is ORed with when read, starting at NR10 (0xFF10) and ending
+/// before the wave pattern RAM. Write-only and unused bits always read as 1.
+const READ_MASKS: [u8; 0x20] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // Unused
+];
+
 /// Generates clocks for the other modulation units.
-#[derive(Debug)]
+///
+/// The sequencer is clocked at 512 Hz by the falling edge of bit 4 of the DIV register.
+///
+/// Step | Length | Sweep | Envelope
+/// ---- | ------ | ----- | --------
+/// 0    | Clock  |       |
+/// 1    |        |       |
+/// 2    | Clock  | Clock |
+/// 3    |        |       |
+/// 4    | Clock  |       |
+/// 5    |        |       |
+/// 6    | Clock  | Clock |
+/// 7    |        |       | Clock
+#[derive(Debug, Default)]
 struct FrameSequencer {
-    timer: u32,
+    /// The next step to be executed.
+    step: u8,
 }
 
 impl FrameSequencer {
-    fn new() -> FrameSequencer {
-        FrameSequencer { timer: 0 }
+    /// Advances the sequencer, returning the step that should be executed.
+    fn clock(&mut self) -> u8 {
+        let step = self.step;
+        self.step = (self.step + 1) % 8;
+        step
     }
 
-    /// Ticks the frame sequencer a single cycle.
-    ///
-    /// If the step value of the sequencer has changed since the last call, returns the new value.
-    fn step(&mut self) -> Option<u8> {
-        const FREQUENCY: u32 = cpu::FREQUENCY / 512;
-
-        let step = if self.timer % FREQUENCY == 0 {
-            Some(
-                (self.timer / FREQUENCY)
-                    .try_into()
-                    .expect("step must be between 0 and 7"),
-            )
+    /// Whether the next step of the sequencer clocks the length counters.
+    fn next_step_clocks_length(&self) -> bool {
+        matches!(self.step, 0 | 2 | 4 | 6)
+    }
+}
+
+/// The length counter of a channel, which disables the channel when it expires.
+#[derive(Debug, Default)]
+struct LengthCounter {
+    /// The number of length clocks remaining until the channel is disabled.
+    counter: u16,
+
+    /// Whether the counter is clocked by the frame sequencer (bit 6 of NRx4).
+    enabled: bool,
+}
+
+impl LengthCounter {
+    /// Clocks the counter. Returns `true` if the counter expired, disabling the channel.
+    fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            self.counter == 0
         } else {
-            None
-        };
+            false
+        }
+    }
 
-        self.timer += 1;
+    /// Updates the counter for a write to NRx4. `max` is the full length of the counter, and
+    /// `extra_clock` is `true` if the next step of the frame sequencer does not clock the length.
+    ///
+    /// Returns `true` if the channel should be disabled.
+    fn write_control(&mut self, enabled: bool, trigger: bool, max: u16, extra_clock: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = enabled;
+
+        // Enabling the counter during the first half of a length period clocks it immediately.
+        let mut expired = false;
+        if extra_clock && !was_enabled && enabled && self.counter > 0 {
+            self.counter -= 1;
+            expired = self.counter == 0 && !trigger;
+        }
+
+        if trigger && self.counter == 0 {
+            self.counter = max;
 
-        if self.timer == FREQUENCY * 8 {
-            self.timer = 0;
+            if enabled && extra_clock {
+                self.counter -= 1;
+            }
         }
 
-        step
+        expired
     }
 }
 
@@ -108,10 +180,6 @@ pub struct Wave {
 
     /// Sound length (0-63).
     pub length_load: u8,
-
-    length: u8,
-
-    enabled: bool,
 }
 
 impl Wave {
@@ -127,21 +195,6 @@ impl Wave {
     pub fn write(&mut self, byte: u8) {
         self.pattern = byte >> 6;
         self.length_load = byte & 0x3F;
-        self.length = 64 - self.length_load;
-    }
-
-    /// Clocks the length counter. Returns `true` if the counter reaches zero.
-    fn step(&mut self) -> bool {
-        if self.length > 0 && self.enabled {
-            self.length -= 1;
-        }
-
-        if self.length == 0 {
-            self.enabled = false;
-            true
-        } else {
-            false
-        }
     }
 }
 
@@ -157,9 +210,10 @@ pub struct Envelope {
     /// Number of envelope sweep (0-7). The length of one step of the sweep is n * (1/64).
     pub number: u8,
 
-    /// Whether the unit should update the volume on the next step.
+    /// Whether the volume has reached its limit, stopping automatic updates.
     disabled: bool,
 
+    /// The number of frame sequencer clocks until the volume is next updated.
     counter: u8,
 
     volume: u8,
@@ -182,11 +236,51 @@ impl Envelope {
         self.number = byte & 0x7;
     }
 
+    /// Modifies the envelope state according to a byte written while the channel is on.
+    ///
+    /// The volume of a playing channel is changed in an unusual way, known as "zombie mode". Some
+    /// games rely on this to change the volume without retriggering the channel.
+    fn write_zombie(&mut self, byte: u8) {
+        if self.number == 0 && !self.disabled {
+            self.volume += 1;
+        } else if !self.direction_increase {
+            self.volume += 2;
+        }
+
+        if self.direction_increase != byte.has_bit_set(3) {
+            self.volume = 16u8.wrapping_sub(self.volume);
+        }
+
+        self.volume &= 0xF;
+
+        self.write(byte);
+    }
+
+    /// Whether the channel's DAC is powered. The DAC is only on if the upper 5 bits of the
+    /// register are nonzero.
+    fn dac_enabled(&self) -> bool {
+        self.initial_vol != 0 || self.direction_increase
+    }
+
+    fn trigger(&mut self) {
+        self.counter = self.number;
+        self.volume = self.initial_vol;
+        self.disabled = false;
+    }
+
     fn step(&mut self) {
-        if self.number != 0 {
-            self.counter += 1;
+        if self.number == 0 {
+            return;
+        }
 
-            if self.counter >= self.number {
+        if self.counter > 0 {
+            self.counter -= 1;
+        }
+
+        if self.counter == 0 {
+            self.counter = self.number;
+
+            if !self.disabled {
                 let new_volume = if self.direction_increase {
                     Some(self.volume + 1)
                 } else {
@@ -197,8 +291,6 @@ impl Envelope {
                     Some(new_volume) if new_volume <= 15 => self.volume = new_volume,
                     _ => self.disabled = true,
                 }
-
-                self.counter = 0;
             }
         }
     }
@@ -386,6 +478,8 @@ pub struct SquareChannel {
     /// The frequency data.
     pub frequency: Frequency,
 
+    length_counter: LengthCounter,
+
     timer: u16,
 
     cycle_position: usize,
@@ -414,26 +508,48 @@ impl SquareChannel {
         };
     }
 
-    /// Executes the [trigger event].
+    /// Executes the [trigger event]. The length counter is reloaded separately.
     ///
     /// [trigger event]: https://gbdev.gg8.se/wiki/articles/Gameboy_sound_hardware#Trigger_Event
     fn trigger(&mut self) {
-        self.is_on = true;
+        // The channel is only enabled if its DAC is on.
+        self.is_on = self.envelope.dac_enabled();
+
+        self.timer = (2048 - self.frequency.frequency) * 4;
+        self.envelope.trigger();
+    }
 
-        if self.wave.length == 0 {
-            self.wave.length = 64;
+    fn write_envelope(&mut self, byte: u8) {
+        if self.is_on {
+            self.envelope.write_zombie(byte);
+        } else {
+            self.envelope.write(byte);
         }
 
-        // TODO: reload frequency timer
+        if !self.envelope.dac_enabled() {
+            self.is_on = false;
+        }
+    }
 
-        self.envelope.counter = self.envelope.number;
-        self.envelope.volume = self.envelope.initial_vol;
+    fn write_control(&mut self, byte: u8, extra_clock: bool) {
+        let trigger = byte.has_bit_set(7);
 
-        // TODO: Handle disabled channel DAC
+        if self
+            .length_counter
+            .write_control(byte.has_bit_set(6), trigger, 64, extra_clock)
+        {
+            self.is_on = false;
+        }
+
+        self.frequency.write_hi(byte);
+
+        if trigger {
+            self.trigger();
+        }
     }
 
     fn clock_length(&mut self) {
-        if self.wave.step() {
+        if self.length_counter.clock() {
             self.is_on = false;
         }
     }
@@ -466,6 +582,36 @@ pub struct Sound3 {
 
     /// The wave pattern memory for storing arbitrary sound data. Holds 32 4-bit samples.
     pub wave_pattern: [u8; 16],
+
+    /// Whether the channel's DAC is powered.
+    pub dac_enabled: bool,
+
+    length_counter: LengthCounter,
+}
+
+impl Sound3 {
+    fn write_control(&mut self, byte: u8, extra_clock: bool) {
+        let trigger = byte.has_bit_set(7);
+
+        if self
+            .length_counter
+            .write_control(byte.has_bit_set(6), trigger, 256, extra_clock)
+        {
+            self.is_on = false;
+        }
+
+        self.frequency.write_hi(byte);
+
+        if trigger {
+            self.is_on = self.dac_enabled;
+        }
+    }
+
+    fn clock_length(&mut self) {
+        if self.length_counter.clock() {
+            self.is_on = false;
+        }
+    }
 }
 
 /// Sound channel 4.
@@ -491,16 +637,129 @@ pub struct Sound4 {
 
     /// The initial flag and counter/consecutive selection.
     pub initial_counter_consecutive: InitialCounterConsecutive,
+
+    length_counter: LengthCounter,
+}
+
+impl Sound4 {
+    fn write_envelope(&mut self, byte: u8) {
+        if self.is_on {
+            self.envelope.write_zombie(byte);
+        } else {
+            self.envelope.write(byte);
+        }
+
+        if !self.envelope.dac_enabled() {
+            self.is_on = false;
+        }
+    }
+
+    fn write_control(&mut self, byte: u8, extra_clock: bool) {
+        let trigger = byte.has_bit_set(7);
+
+        if self
+            .length_counter
+            .write_control(byte.has_bit_set(6), trigger, 64, extra_clock)
+        {
+            self.is_on = false;
+        }
+
+        self.initial_counter_consecutive.write(byte);
+
+        if trigger {
+            self.is_on = self.envelope.dac_enabled();
+            self.envelope.trigger();
+        }
+    }
+
+    fn clock_length(&mut self) {
+        if self.length_counter.clock() {
+            self.is_on = false;
+        }
+    }
+
+    fn clock_envelope(&mut self) {
+        self.envelope.step();
+    }
+}
+
+/// Identifies one of the four sound channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Square 1, with sweep.
+    Square1,
+
+    /// Square 2.
+    Square2,
+
+    /// Sound 3, the wave channel.
+    Wave,
+
+    /// Sound 4, the noise channel.
+    Noise,
+}
+
+impl Channel {
+    /// All channels, in register order.
+    pub const ALL: [Channel; 4] = [
+        Channel::Square1,
+        Channel::Square2,
+        Channel::Wave,
+        Channel::Noise,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            Channel::Square1 => 0,
+            Channel::Square2 => 1,
+            Channel::Wave => 2,
+            Channel::Noise => 3,
+        }
+    }
+}
+
+impl Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Channel::Square1 => "square1",
+            Channel::Square2 => "square2",
+            Channel::Wave => "wave",
+            Channel::Noise => "noise",
+        };
+
+        f.pad(name)
+    }
+}
+
+/// Error returned when parsing an unknown [`Channel`].
+#[derive(Debug, Error)]
+#[error("unknown channel `{0}`, expected 1-4, square1, square2, wave or noise")]
+pub struct ParseChannelError(String);
+
+impl FromStr for Channel {
+    type Err = ParseChannelError;
+
+    /// Parses a channel from its number (1-4) or its name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "1" | "square1" | "sq1" => Ok(Channel::Square1),
+            "2" | "square2" | "sq2" => Ok(Channel::Square2),
+            "3" | "wave" => Ok(Channel::Wave),
+            "4" | "noise" => Ok(Channel::Noise),
+            _ => Err(ParseChannelError(s.to_string())),
+        }
+    }
 }
 
 /// The controller for the four sound channels output by the Game Boy. Also known as the APU (Audio
 /// Processing Unit) or PAPU (Pseudo-Audio Processing Unit).
 ///
-/// Generates [PCM audio samples] at the frequency of the APU and stores them in a ring buffer.
-/// These samples are then downsampled to a rate acceptable by the computer hardware.
+/// Generates [PCM audio samples] at the frequency of the APU. These samples are then resampled to
+/// the rate of each [`AudioSink`], such as the computer's audio hardware.
 ///
 /// [PCM audio samples]: https://en.wikipedia.org/wiki/Pulse-code_modulation
-#[derive(Debug)]
+#[derive(Derivative)]
+#[derivative(Debug)]
 pub struct SoundController {
     /// Square 1: Rectangle waveform with sweep and envelope functions.
     pub square_1: SquareChannel,
@@ -531,11 +790,41 @@ pub struct SoundController {
     /// Whether to output Vin to SO2.
     pub vin_so2: bool,
 
-    /// Number of cycles since the last output sample.
-    cycle_count: u32,
+    /// Channels that are excluded from the mix, indexed by [`Channel`].
+    muted: [bool; 4],
+
+    /// Channels that are soloed. If any channel is soloed, all other channels are excluded from
+    /// the mix.
+    soloed: [bool; 4],
+
+    /// The sum of each channel's output since the levels were last taken.
+    level_sums: [u64; 4],
+
+    /// The number of cycles that were summed into `level_sums`.
+    level_cycles: u64,
+
+    /// Number of cycles since the last time samples were sent to the output.
+    frame_clock: u32,
+
+    /// The amplitude of the last mixed sample of each terminal.
+    amplitude: [f32; 2],
+
+    /// Audio playback. `None` if no playback is desired.
+    playback: Option<SinkOutput<Output>>,
+
+    /// Additional destinations for the generated audio.
+    #[derivative(Debug = "ignore")]
+    sinks: Vec<SinkOutput<Box<dyn AudioSink>>>,
 
-    /// Audio output. `None` if no output is desired.
-    out: Option<Output>,
+    /// Samples captured for [`SoundController::drain_captured`].
+    capture: Option<SampleBuffer>,
+
+    /// The number of T-cycles that the sound controller has been ticked.
+    cycles: u64,
+
+    /// Log of register writes. `None` if the writes are not being logged.
+    #[derivative(Debug = "ignore")]
+    vgm: Option<VgmWriter<Box<dyn WriteSeek>>>,
 }
 
 impl SoundController {
@@ -546,57 +835,309 @@ impl SoundController {
             square_2: SquareChannel::default(),
             sound_3: Sound3::default(),
             sound_4: Sound4::default(),
-            frame_sequencer: FrameSequencer::new(),
+            frame_sequencer: FrameSequencer::default(),
             sound_enabled: false,
             so1_vol: 0,
             so2_vol: 0,
             vin_so1: false,
             vin_so2: false,
-            cycle_count: 0,
-            out: None,
+            muted: [false; 4],
+            soloed: [false; 4],
+            level_sums: [0; 4],
+            level_cycles: 0,
+            frame_clock: 0,
+            amplitude: [0.0, 0.0],
+            playback: None,
+            sinks: vec![],
+            capture: None,
+            cycles: 0,
+            vgm: None,
         }
     }
 
     /// Creates an emulated sound controller that plays audio.
     pub fn new_with_playback() -> Result<SoundController> {
         Ok(SoundController {
-            out: Some(Output::new()?),
+            playback: Some(SinkOutput::new(Output::new()?)),
             ..Default::default()
         })
     }
 
+    /// Sends the generated audio to an additional sink.
+    pub fn add_sink(&mut self, sink: impl AudioSink + 'static) {
+        self.sinks.push(SinkOutput::new(Box::new(sink)));
+    }
+
+    /// Starts capturing the generated audio at the given sample rate. The captured samples may be
+    /// retrieved with [`SoundController::drain_captured`].
+    ///
+    /// Has no effect if audio is already being captured.
+    pub fn capture(&mut self, sample_rate: u32) {
+        if self.capture.is_none() {
+            let sink = BufferSink::new(sample_rate);
+            self.capture = Some(sink.buffer());
+            self.add_sink(sink);
+        }
+    }
+
+    /// Removes and returns all samples captured since the last call.
+    ///
+    /// Returns an empty vector if audio is not being captured.
+    pub fn drain_captured(&mut self) -> Vec<[f32; 2]> {
+        match &self.capture {
+            Some(buffer) => buffer.lock().unwrap().drain(..).collect(),
+            None => vec![],
+        }
+    }
+
+    /// Starts logging every write to the sound registers as a VGM file. Any previous log is
+    /// finalized.
+    pub fn log_vgm(&mut self, writer: impl WriteSeek + 'static) -> io::Result<()> {
+        let mut vgm = VgmWriter::new(Box::new(writer) as Box<dyn WriteSeek>)?;
+        vgm.set_time(self.cycles)?;
+        self.vgm = Some(vgm);
+        Ok(())
+    }
+
+    /// Stops logging register writes, and finalizes the VGM file.
+    pub fn stop_vgm_log(&mut self) {
+        self.vgm = None;
+    }
+
+    /// Runs a VGM logging operation, stopping the log if it fails.
+    fn with_vgm(&mut self, f: impl FnOnce(&mut VgmWriter<Box<dyn WriteSeek>>) -> io::Result<()>) {
+        if let Some(vgm) = &mut self.vgm {
+            if let Err(e) = f(vgm) {
+                error!("unable to write VGM log, stopping: {}", e);
+                self.vgm = None;
+            }
+        }
+    }
+
+    /// Returns statistics about the audio stream, if audio is being played.
+    pub fn stats(&self) -> Option<AudioStats> {
+        self.playback.as_ref().map(|playback| playback.sink.stats())
+    }
+
+    /// Returns the number of T-cycles that must be emulated to queue enough audio to reach the
+    /// target playback latency, if audio is being played.
+    pub fn cycles_until_buffered(&self) -> Option<TCycles> {
+        self.playback
+            .as_ref()
+            .map(|playback| playback.sink.cycles_until_target())
+    }
+
+    /// Mutes or unmutes a channel. Muted channels are excluded from the mix.
+    pub fn set_muted(&mut self, channel: Channel, muted: bool) {
+        self.muted[channel.index()] = muted;
+    }
+
+    /// Returns whether a channel is muted.
+    pub fn is_muted(&self, channel: Channel) -> bool {
+        self.muted[channel.index()]
+    }
+
+    /// Solos or unsolos a channel. While any channel is soloed, only soloed channels are mixed.
+    pub fn set_soloed(&mut self, channel: Channel, soloed: bool) {
+        self.soloed[channel.index()] = soloed;
+    }
+
+    /// Returns whether a channel is soloed.
+    pub fn is_soloed(&self, channel: Channel) -> bool {
+        self.soloed[channel.index()]
+    }
+
+    /// Returns whether a channel is included in the mix, taking both mutes and solos into
+    /// account.
+    pub fn is_audible(&self, channel: Channel) -> bool {
+        let index = channel.index();
+        let any_soloed = self.soloed.iter().any(|&soloed| soloed);
+
+        !self.muted[index] && (!any_soloed || self.soloed[index])
+    }
+
+    /// Returns the current output of each channel (0-15), before mixing, indexed by [`Channel`].
+    ///
+    /// Mutes and solos do not affect the result.
+    pub fn channel_outputs(&self) -> [u8; 4] {
+        // Sound 3 and sound 4 are not synthesized yet, so they are always silent.
+        [self.square_1.output as u8, self.square_2.output as u8, 0, 0]
+    }
+
+    /// Returns the average output of each channel since the last call, from 0.0 to 1.0, indexed
+    /// by [`Channel`]. Calling this once per frame provides the levels for a visualizer.
+    ///
+    /// Like [`SoundController::channel_outputs`], these are measured before mixing.
+    pub fn take_channel_levels(&mut self) -> [f32; 4] {
+        let mut levels = [0.0; 4];
+
+        if self.level_cycles > 0 {
+            for (level, &sum) in levels.iter_mut().zip(self.level_sums.iter()) {
+                *level = sum as f32 / self.level_cycles as f32 / 15.0;
+            }
+        }
+
+        self.level_sums = [0; 4];
+        self.level_cycles = 0;
+
+        levels
+    }
+
+    /// Mixes the output of each channel into the left (SO2) and right (SO1) terminals.
+    fn mix(&self, outputs: [u8; 4]) -> [f32; 2] {
+        let channels = [
+            (self.square_1.so2_enabled, self.square_1.so1_enabled),
+            (self.square_2.so2_enabled, self.square_2.so1_enabled),
+            (self.sound_3.so2_enabled, self.sound_3.so1_enabled),
+            (self.sound_4.so2_enabled, self.sound_4.so1_enabled),
+        ];
+
+        let mut left = 0_f32;
+        let mut right = 0_f32;
+
+        for ((&channel, &output), &(left_enabled, right_enabled)) in
+            Channel::ALL.iter().zip(outputs.iter()).zip(channels.iter())
+        {
+            if !self.is_audible(channel) {
+                continue;
+            }
+
+            let sample = f32::from(output) / 100_f32;
+
+            if left_enabled {
+                left += sample;
+            }
+
+            if right_enabled {
+                right += sample;
+            }
+        }
+
+        // The master volume scales each terminal by 1/8 to 8/8.
+        [
+            left * f32::from(self.so2_vol + 1) / 8_f32,
+            right * f32::from(self.so1_vol + 1) / 8_f32,
+        ]
+    }
+
+    /// Clocks the frame sequencer. Called on each falling edge of bit 4 of the DIV register.
+    pub fn clock_frame_sequencer(&mut self) {
+        if !self.sound_enabled {
+            return;
+        }
+
+        let step = self.frame_sequencer.clock();
+
+        if matches!(step, 0 | 2 | 4 | 6) {
+            self.square_1.clock_length();
+            self.square_2.clock_length();
+            self.sound_3.clock_length();
+            self.sound_4.clock_length();
+        }
+
+        // TODO: clock the sweep unit on steps 2 and 6.
+
+        if step == 7 {
+            self.square_1.clock_envelope();
+            self.square_2.clock_envelope();
+            self.sound_4.clock_envelope();
+        }
+    }
+
+    /// Powers on the sound controller. The frame sequencer and square wave positions are reset.
+    fn power_on(&mut self) {
+        self.sound_enabled = true;
+        self.frame_sequencer = FrameSequencer::default();
+        self.square_1.cycle_position = 0;
+        self.square_2.cycle_position = 0;
+    }
+
+    /// Powers off the sound controller, clearing every register.
+    ///
+    /// On the DMG, the length counters and the wave pattern RAM are unaffected.
+    fn power_off(&mut self) {
+        self.sound_enabled = false;
+
+        let square_1 = std::mem::take(&mut self.square_1);
+        self.square_1.length_counter.counter = square_1.length_counter.counter;
+
+        let square_2 = std::mem::take(&mut self.square_2);
+        self.square_2.length_counter.counter = square_2.length_counter.counter;
+
+        let sound_3 = std::mem::take(&mut self.sound_3);
+        self.sound_3.length_counter.counter = sound_3.length_counter.counter;
+        self.sound_3.wave_pattern = sound_3.wave_pattern;
+
+        let sound_4 = std::mem::take(&mut self.sound_4);
+        self.sound_4.length_counter.counter = sound_4.length_counter.counter;
+
+        self.so1_vol = 0;
+        self.so2_vol = 0;
+        self.vin_so1 = false;
+        self.vin_so2 = false;
+    }
+
+    /// Reloads the length counter of a channel from a write to its NRx1 register.
+    fn write_length(&mut self, address: u16, byte: u8) {
+        match address {
+            0xFF11 => self.square_1.length_counter.counter = 64 - u16::from(byte & 0x3F),
+            0xFF16 => self.square_2.length_counter.counter = 64 - u16::from(byte & 0x3F),
+            0xFF1B => self.sound_3.length_counter.counter = 256 - u16::from(byte),
+            0xFF20 => self.sound_4.length_counter.counter = 64 - u16::from(byte & 0x3F),
+            _ => unreachable!("not a length register: {:#06x}", address),
+        }
+    }
+
     /// Ticks the audio output by a number of T-cycles.
     pub fn step(&mut self, cycles: TCycles) {
         for _ in 0..cycles.0 {
-            if let Some(step) = self.frame_sequencer.step() {
-                if step % 2 == 0 {
-                    self.square_1.clock_length();
-                    self.square_2.clock_length();
-                }
+            self.square_1.step();
+            self.square_2.step();
 
-                if step == 7 {
-                    self.square_1.clock_envelope();
-                    self.square_2.clock_envelope();
-                }
+            let outputs = self.channel_outputs();
+            for (sum, &output) in self.level_sums.iter_mut().zip(outputs.iter()) {
+                *sum += u64::from(output);
             }
+            self.level_cycles += 1;
 
-            self.square_1.step();
-            self.square_2.step();
+            if self.playback.is_some() || !self.sinks.is_empty() {
+                let sample = self.mix(outputs);
+
+                // The resampler only needs to know when the signal changes.
+                if sample != self.amplitude {
+                    let delta = [sample[0] - self.amplitude[0], sample[1] - self.amplitude[1]];
 
-            if let Some(out) = &self.out {
-                let mut sample = 0_f32;
+                    if let Some(playback) = &mut self.playback {
+                        playback.add_delta(self.frame_clock, delta);
+                    }
 
-                sample += self.square_1.output as f32 / 100_f32;
-                sample += self.square_2.output as f32 / 100_f32;
+                    for sink in &mut self.sinks {
+                        sink.add_delta(self.frame_clock, delta);
+                    }
 
-                if self.cycle_count % out.decimation_factor == 0 {
-                    out.sample_buffer.lock().unwrap().push_back(sample);
-                    self.cycle_count = 0;
+                    self.amplitude = sample;
                 }
 
-                self.cycle_count += 1;
+                self.frame_clock += 1;
             }
         }
+
+        if self.frame_clock >= FRAME_LENGTH {
+            if let Some(playback) = &mut self.playback {
+                playback.end_frame(self.frame_clock);
+            }
+
+            for sink in &mut self.sinks {
+                sink.end_frame(self.frame_clock);
+            }
+
+            self.frame_clock = 0;
+        }
+
+        self.cycles += u64::from(cycles.0);
+
+        let cycles = self.cycles;
+        self.with_vgm(|vgm| vgm.set_time(cycles));
     }
 }
 
@@ -613,12 +1154,7 @@ impl Addressable for SoundController {
     ///
     /// Panics if reading memory that is not managed by the sound controller.
     fn read_byte(&self, address: u16) -> u8 {
-        // Access to sound registers, aside from 0xFF26, is disabled unless the sound is on.
-        if !self.sound_enabled && address != 0xFF26 {
-            return 0xFF;
-        }
-
-        match address {
+        let byte = match address {
             // NR10: Sound 1 sweep register
             // Bit 6-4 - Sweep time
             // Bit 3   - Sweep Increase/Decrease
@@ -673,13 +1209,7 @@ impl Addressable for SoundController {
 
             // NR30: Channel 3 sound on/off
             // Bit 7 - Sound channel 3 off (0=Stop, 1=Playback)
-            0xFF1A => {
-                if self.sound_3.is_on {
-                    1
-                } else {
-                    0
-                }
-            }
+            0xFF1A => u8::from(self.sound_3.dac_enabled) << 7,
 
             // NR31: Channel 3 sound length
             // Bit 7-0 - Sound length (0-255)
@@ -797,14 +1327,19 @@ impl Addressable for SoundController {
             // back upper 4 bits first.
             0xFF30..=0xFF3F => {
                 let index = address - 0xFF30;
-                self.sound_3.wave_pattern[index as usize]
+                return self.sound_3.wave_pattern[index as usize];
             }
 
+            // Unused
+            0xFF15 | 0xFF1F | 0xFF27..=0xFF2F => 0xFF,
+
             _ => panic!(
                 "read out-of-range address in the sound controller: {:#0x}",
                 address
             ),
-        }
+        };
+
+        byte | READ_MASKS[usize::from(address - 0xFF10)]
     }
 
     /// Writes a byte of audio memory.
@@ -813,11 +1348,24 @@ impl Addressable for SoundController {
     ///
     /// Panics if writing memory that is not managed by the sound controller.
     fn write_byte(&mut self, address: u16, byte: u8) {
-        // Access to sound registers, aside from 0xFF26, is disabled unless sound is on.
-        if !self.sound_enabled && address != 0xFF26 {
-            return;
+        let cycles = self.cycles;
+        self.with_vgm(|vgm| vgm.write_register(cycles, address, byte));
+
+        // Writes to the sound registers are ignored while the sound is off, except for NR52 and
+        // the wave pattern RAM. On the DMG, the length counters may also be written.
+        if !self.sound_enabled {
+            match address {
+                0xFF26 | 0xFF30..=0xFF3F => (),
+                0xFF11 | 0xFF16 | 0xFF1B | 0xFF20 => {
+                    self.write_length(address, byte);
+                    return;
+                }
+                _ => return,
+            }
         }
 
+        let extra_clock = !self.frame_sequencer.next_step_clocks_length();
+
         match address {
             // NR10: Sound 1 sweep register
             // Bit 6-4 - Sweep time
@@ -830,13 +1378,16 @@ impl Addressable for SoundController {
             // NR11: Sound 1 Sound length/Wave pattern duty
             // Bit 7-6 - Wave pattern duty
             // Bit 5-0 - Sound length data (Write only)
-            0xFF11 => self.square_1.wave.write(byte),
+            0xFF11 => {
+                self.square_1.wave.write(byte);
+                self.write_length(address, byte);
+            }
 
             // NR12: Channel 1 volume envelope
             // Bit 7-4 - Initial volume of the envelope (0-15) (0 = no sound)
             // Bit 3   - Envelope direction (0 = decrease, 1 = increase)
             // Bit 2-0 - Number of envelope sweep (n: 0-7) (If 0, stop the envelope operation)
-            0xFF12 => self.square_1.envelope.write(byte),
+            0xFF12 => self.square_1.write_envelope(byte),
 
             // NR13: Channel 1 Frequency low
             // Lower 8 bits of the 11-bit frequency
@@ -847,25 +1398,21 @@ impl Addressable for SoundController {
             // Bit 6   - Counter/consecutive selection (1 = stop output when length in NR11
             //           expires)
             // Bit 2-0 - Frequency's higher 3 bits (write only)
-            0xFF14 => {
-                if byte.has_bit_set(7) {
-                    self.square_1.trigger();
-                }
-
-                self.square_1.wave.enabled = byte.has_bit_set(6);
-                self.square_1.frequency.write_hi(byte);
-            }
+            0xFF14 => self.square_1.write_control(byte, extra_clock),
 
             // NR21: Sound 2 Sound length/Wave pattern duty
             // Bit 7-6 - Wave pattern duty
             // Bit 5-0 - Sound length data (Write only)
-            0xFF16 => self.square_2.wave.write(byte),
+            0xFF16 => {
+                self.square_2.wave.write(byte);
+                self.write_length(address, byte);
+            }
 
             // NR22: Channel 2 volume envelope
             // Bit 7-4 - Initial volume of the envelope (0-15) (0 = no sound)
             // Bit 3   - Envelope direction (0 = decrease, 1 = increase)
             // Bit 2-0 - Number of envelope sweep (n: 0-7) (If 0, stop the envelope operation)
-            0xFF17 => self.square_2.envelope.write(byte),
+            0xFF17 => self.square_2.write_envelope(byte),
 
             // NR23: Channel 2 frequency low
             // Lower 8 bits of the 11-bit frequency
@@ -876,22 +1423,24 @@ impl Addressable for SoundController {
             // Bit 6   - Counter/consecutive selection (1 = stop output when length in NR21
             //           expires)
             // Bit 2-0 - Frequency's higher 3 bits (write only)
-            0xFF19 => {
-                if byte.has_bit_set(7) {
-                    self.square_2.trigger();
-                }
-                self.square_2.wave.enabled = byte.has_bit_set(6);
-
-                self.square_2.frequency.write_hi(byte);
-            }
+            0xFF19 => self.square_2.write_control(byte, extra_clock),
 
             // NR30: Channel 3 sound on/off
             // Bit 7 - Sound channel 3 off (0=Stop, 1=Playback)
-            0xFF1A => self.sound_3.is_on = byte.has_bit_set(7),
+            0xFF1A => {
+                self.sound_3.dac_enabled = byte.has_bit_set(7);
+
+                if !self.sound_3.dac_enabled {
+                    self.sound_3.is_on = false;
+                }
+            }
 
             // NR31: Channel 3 sound length
             // Bit 7-0 - Sound length (0-255)
-            0xFF1B => self.sound_3.length.write(byte),
+            0xFF1B => {
+                self.sound_3.length.write(byte);
+                self.write_length(address, byte);
+            }
 
             // NR32: Channel 3 select output level
             // Bit 6-5 - Select output level:
@@ -910,17 +1459,20 @@ impl Addressable for SoundController {
             // Bit 6   - Counter/consecutive selection (1 = stop output when length in NR11
             //           expires)
             // Bit 2-0 - Frequency's higher 3 bits (write only)
-            0xFF1E => self.sound_3.frequency.write_hi(byte),
+            0xFF1E => self.sound_3.write_control(byte, extra_clock),
 
             // NR41: Channel 4 sound length
             // Bit 5-0 - Sound length data (0-63)
-            0xFF20 => self.sound_4.length.write(byte),
+            0xFF20 => {
+                self.sound_4.length.write(byte);
+                self.write_length(address, byte);
+            }
 
             // NR42: Channel 4 volume envelope
             // Bit 7-4 - Initial volume of envelope (0=No sound)
             // Bit 3   - Envelope direction (0=Decrease, 1=Increase)
             // Bit 2-0 - Number of envelope sweep (If zero, stop envelope operation)
-            0xFF21 => self.sound_4.envelope.write(byte),
+            0xFF21 => self.sound_4.write_envelope(byte),
 
             // NR43: Channel 4 polynomial counter
             // Bit 7-4 - Shift clock frequency
@@ -931,7 +1483,7 @@ impl Addressable for SoundController {
             // NR44: Channel 4 counter/consecutive; initial
             // Bit 7 - Initial (1=restart sound) (write only)
             // Bit 6 - Counter/consecutive selection (1=Stop output when length in NR41 expires)
-            0xFF23 => self.sound_4.initial_counter_consecutive.write(byte),
+            0xFF23 => self.sound_4.write_control(byte, extra_clock),
 
             // NR50: Channel control / ON-OFF / Volume
             // Specifies the master volume for Left/Right sound output.
@@ -981,16 +1533,11 @@ impl Addressable for SoundController {
             // Bit 2 - Sound 3 ON flag (Read Only)
             // Bit 1 - Sound 2 ON flag (Read Only)
             // Bit 0 - Sound 1 ON flag (Read Only)
-            0xFF26 => {
-                self.sound_enabled = byte.has_bit_set(7);
-
-                if !self.sound_enabled {
-                    self.square_1 = SquareChannel::default();
-                    self.square_2 = SquareChannel::default();
-                    self.sound_3 = Sound3::default();
-                    self.sound_4 = Sound4::default();
-                }
-            }
+            0xFF26 => match (self.sound_enabled, byte.has_bit_set(7)) {
+                (false, true) => self.power_on(),
+                (true, false) => self.power_off(),
+                _ => (),
+            },
 
             // Channel 3 Wave pattern memory
             // Waveform storage for arbitrary sound data. Holds 32 4-bit samples, which are played
@@ -1000,6 +1547,9 @@ impl Addressable for SoundController {
                 self.sound_3.wave_pattern[index as usize] = byte;
             }
 
+            // Unused
+            0xFF15 | 0xFF1F | 0xFF27..=0xFF2F => (),
+
             _ => panic!(
                 "write out-of-range address in the sound controller: {:#0x}",
                 address
@@ -1015,9 +1565,11 @@ mod tests {
     use proptest::proptest;
 
     use crate::bytes::ByteExt;
+    use crate::cpu::{self, TCycles};
     use crate::memory::Addressable;
+    use crate::test_util::SharedBuffer;
 
-    use super::{Frequency, SoundController, Sweep};
+    use super::{Channel, Frequency, SoundController, Sweep};
 
     #[test]
     fn sweep_read() {
@@ -1147,10 +1699,6 @@ mod tests {
                         expected.set_bit(6, so2_vol.has_bit_set(2));
                         expected.set_bit(7, vin_so2);
 
-                        sc.sound_enabled = false;
-                        assert_eq!(sc.read_byte(0xFF24), 0xFF);
-
-                        sc.sound_enabled = true;
                         assert_eq!(sc.read_byte(0xFF24), expected);
                     }
                 }
@@ -1221,10 +1769,6 @@ mod tests {
             sc.sound_4.so1_enabled = i.has_bit_set(3);
             sc.sound_4.so2_enabled = i.has_bit_set(7);
 
-            sc.sound_enabled = false;
-            assert_eq!(sc.read_byte(0xFF25), 0xFF);
-
-            sc.sound_enabled = true;
             assert_eq!(sc.read_byte(0xFF25), i);
         }
     }
@@ -1301,7 +1845,6 @@ mod tests {
 
     #[test]
     fn ff26_write() {
-        // TODO: need to test that sound registers are cleared
         let mut sc = SoundController::new();
 
         for i_large in 0usize..256 {
@@ -1313,6 +1856,225 @@ mod tests {
         }
     }
 
+    #[test]
+    fn power_off() {
+        let mut sc = SoundController::new();
+
+        sc.write_byte(0xFF26, 0x80);
+        sc.write_byte(0xFF24, 0x77);
+        sc.write_byte(0xFF25, 0xFF);
+        sc.write_byte(0xFF11, 0x80);
+        sc.write_byte(0xFF12, 0xF3);
+        sc.write_byte(0xFF30, 0x12);
+
+        sc.write_byte(0xFF26, 0x00);
+
+        // Registers are cleared, except for the wave pattern RAM.
+        assert_eq!(sc.read_byte(0xFF24), 0x00);
+        assert_eq!(sc.read_byte(0xFF25), 0x00);
+        assert_eq!(sc.read_byte(0xFF11), 0x3F);
+        assert_eq!(sc.read_byte(0xFF12), 0x00);
+        assert_eq!(sc.read_byte(0xFF26), 0x70);
+        assert_eq!(sc.read_byte(0xFF30), 0x12);
+
+        // Writes are ignored, except for the length counters and wave pattern RAM.
+        sc.write_byte(0xFF24, 0x77);
+        sc.write_byte(0xFF11, 0xBF);
+        sc.write_byte(0xFF31, 0x34);
+        assert_eq!(sc.read_byte(0xFF24), 0x00);
+        assert_eq!(sc.read_byte(0xFF11), 0x3F);
+        assert_eq!(sc.square_1.length_counter.counter, 1);
+        assert_eq!(sc.read_byte(0xFF31), 0x34);
+
+        // Unused registers always read as 1s.
+        assert_eq!(sc.read_byte(0xFF15), 0xFF);
+        assert_eq!(sc.read_byte(0xFF2F), 0xFF);
+    }
+
+    #[test]
+    fn dac() {
+        let mut sc = SoundController::new();
+        sc.write_byte(0xFF26, 0x80);
+
+        // Triggering a channel with its DAC off leaves it disabled.
+        sc.write_byte(0xFF14, 0x80);
+        sc.write_byte(0xFF1E, 0x80);
+        assert_eq!(sc.read_byte(0xFF26) & 0x0F, 0x00);
+
+        sc.write_byte(0xFF12, 0x08);
+        sc.write_byte(0xFF14, 0x80);
+        sc.write_byte(0xFF1A, 0x80);
+        sc.write_byte(0xFF1E, 0x80);
+        assert_eq!(sc.read_byte(0xFF26) & 0x0F, 0x05);
+
+        // Turning off the DAC disables the channel.
+        sc.write_byte(0xFF12, 0x00);
+        sc.write_byte(0xFF1A, 0x00);
+        assert_eq!(sc.read_byte(0xFF26) & 0x0F, 0x00);
+        assert_eq!(sc.read_byte(0xFF1A), 0x7F);
+    }
+
+    #[test]
+    fn length_counter() {
+        let mut sc = SoundController::new();
+        sc.write_byte(0xFF26, 0x80);
+        sc.write_byte(0xFF17, 0xF0);
+
+        // A length of 2, enabled on trigger.
+        sc.write_byte(0xFF16, 0x3E);
+        sc.write_byte(0xFF19, 0xC0);
+
+        sc.clock_frame_sequencer();
+        sc.clock_frame_sequencer();
+        assert!(sc.square_2.is_on);
+
+        sc.clock_frame_sequencer();
+        assert!(!sc.square_2.is_on);
+
+        // Triggering with an expired counter reloads it with the full length. The next step does
+        // not clock the length, so the counter is clocked immediately.
+        sc.write_byte(0xFF19, 0xC0);
+        assert!(sc.square_2.is_on);
+        assert_eq!(sc.square_2.length_counter.counter, 63);
+    }
+
+    #[test]
+    fn length_enable_extra_clock() {
+        let mut sc = SoundController::new();
+        sc.write_byte(0xFF26, 0x80);
+        sc.write_byte(0xFF21, 0xF0);
+
+        sc.write_byte(0xFF20, 0x3F);
+        sc.write_byte(0xFF23, 0x80);
+        assert!(sc.sound_4.is_on);
+
+        // While the next step clocks the length, enabling the counter has no immediate effect.
+        sc.write_byte(0xFF23, 0x40);
+        assert_eq!(sc.sound_4.length_counter.counter, 1);
+        sc.write_byte(0xFF23, 0x00);
+
+        // Otherwise, the counter is clocked, which may disable the channel.
+        sc.clock_frame_sequencer();
+        sc.write_byte(0xFF23, 0x40);
+        assert_eq!(sc.sound_4.length_counter.counter, 0);
+        assert!(!sc.sound_4.is_on);
+    }
+
+    #[test]
+    fn zombie_mode() {
+        let mut sc = SoundController::new();
+        sc.write_byte(0xFF26, 0x80);
+
+        sc.write_byte(0xFF12, 0x80);
+        sc.write_byte(0xFF14, 0x80);
+        assert_eq!(sc.square_1.envelope.volume, 8);
+
+        // The period was 0, so the volume is incremented.
+        sc.write_byte(0xFF12, 0x80);
+        assert_eq!(sc.square_1.envelope.volume, 9);
+
+        // Changing the direction inverts the volume.
+        sc.write_byte(0xFF12, 0x88);
+        assert_eq!(sc.square_1.envelope.volume, 6);
+    }
+
+    #[test]
+    fn capture() {
+        let mut sc = SoundController::new();
+        sc.capture(48000);
+
+        sc.write_byte(0xFF26, 0x80);
+        sc.write_byte(0xFF24, 0x77);
+        sc.write_byte(0xFF25, 0x10);
+        sc.write_byte(0xFF12, 0xF0);
+        sc.write_byte(0xFF11, 0x80);
+        sc.write_byte(0xFF13, 0x00);
+        sc.write_byte(0xFF14, 0x87);
+
+        for _ in 0..cpu::FREQUENCY / 4 {
+            sc.step(TCycles(4));
+        }
+
+        let samples = sc.drain_captured();
+        assert!((47990..48010).contains(&samples.len()), "{}", samples.len());
+
+        // Square 1 is only routed to the left output.
+        assert!(samples.iter().any(|[left, _]| left.abs() > 0.1));
+        assert!(samples.iter().all(|[_, right]| *right == 0.0));
+
+        assert!(sc.drain_captured().is_empty());
+    }
+
+    #[test]
+    fn vgm_log() {
+        let log = SharedBuffer::default();
+
+        let mut sc = SoundController::new();
+        sc.log_vgm(log.clone()).unwrap();
+
+        sc.write_byte(0xFF26, 0x80);
+        sc.step(TCycles(cpu::FREQUENCY / 441));
+        sc.write_byte(0xFF12, 0xF0);
+        sc.stop_vgm_log();
+
+        assert_eq!(
+            &log.contents()[0x100..],
+            &[0xB3, 0x16, 0x80, 0x61, 0x63, 0x00, 0xB3, 0x02, 0xF0, 0x66][..]
+        );
+    }
+
+    #[test]
+    fn mute_and_solo() {
+        let mut sc = SoundController::new();
+
+        assert!(Channel::ALL.iter().all(|&channel| sc.is_audible(channel)));
+
+        sc.set_muted(Channel::Square1, true);
+        assert!(!sc.is_audible(Channel::Square1));
+        assert!(sc.is_audible(Channel::Square2));
+
+        sc.set_soloed(Channel::Wave, true);
+        sc.set_soloed(Channel::Square1, true);
+        assert!(!sc.is_audible(Channel::Square1));
+        assert!(!sc.is_audible(Channel::Square2));
+        assert!(sc.is_audible(Channel::Wave));
+        assert!(!sc.is_audible(Channel::Noise));
+
+        sc.set_soloed(Channel::Wave, false);
+        sc.set_soloed(Channel::Square1, false);
+        assert!(sc.is_audible(Channel::Square2));
+    }
+
+    #[test]
+    fn channel_taps() {
+        let mut sc = SoundController::new();
+        sc.capture(48000);
+        sc.set_muted(Channel::Square1, true);
+
+        sc.write_byte(0xFF26, 0x80);
+        sc.write_byte(0xFF24, 0x77);
+        sc.write_byte(0xFF25, 0x11);
+        sc.write_byte(0xFF12, 0xF0);
+        sc.write_byte(0xFF11, 0x80);
+        sc.write_byte(0xFF14, 0x87);
+
+        for _ in 0..cpu::FREQUENCY / 64 {
+            sc.step(TCycles(4));
+        }
+
+        // The 50% duty cycle wave at full volume averages to half of the maximum level.
+        let levels = sc.take_channel_levels();
+        assert!((levels[0] - 0.5).abs() < 0.01, "{}", levels[0]);
+        assert_eq!(&levels[1..], &[0.0, 0.0, 0.0]);
+        assert_eq!(sc.take_channel_levels(), [0.0; 4]);
+
+        // The tap is taken before the mix, so the muted channel is silent in the output.
+        assert!(sc
+            .drain_captured()
+            .iter()
+            .all(|&sample| sample == [0.0, 0.0]));
+    }
+
     proptest! {
         #[test]
         fn nr11(duty in 0u8..4, length_load in 0u8..64) {
@@ -1324,9 +2086,10 @@ mod tests {
             apu.write_byte(0xFF11, byte);
             assert_eq!(apu.square_1.wave.pattern, duty);
             assert_eq!(apu.square_1.wave.length_load, length_load);
-            assert_eq!(apu.square_1.wave.length, 64 - length_load);
+            assert_eq!(apu.square_1.length_counter.counter, u16::from(64 - length_load));
 
-            assert_eq!(apu.read_byte(0xFF11), byte);
+            // The sound length is write-only.
+            assert_eq!(apu.read_byte(0xFF11), duty << 6 | 0x3F);
         }
 
         #[test]
@@ -1354,9 +2117,10 @@ mod tests {
             apu.write_byte(0xFF16, byte);
             assert_eq!(apu.square_2.wave.pattern, duty);
             assert_eq!(apu.square_2.wave.length_load, length_load);
-            assert_eq!(apu.square_2.wave.length, 64 - length_load);
+            assert_eq!(apu.square_2.length_counter.counter, u16::from(64 - length_load));
 
-            assert_eq!(apu.read_byte(0xFF16), byte);
+            // The sound length is write-only.
+            assert_eq!(apu.read_byte(0xFF16), duty << 6 | 0x3F);
         }
 
         #[test]
@@ -2,7 +2,9 @@
 //!
 //! Plays the audio based on the state of the sound hardware.
 
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{anyhow, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
@@ -10,13 +12,38 @@ use cpal::{OutputCallbackInfo, SampleFormat, SampleRate, Stream};
 use derivative::Derivative;
 use log::*;
 
-use crate::cpu;
+use crate::cpu::{self, TCycles};
 
-use super::SampleBuffer;
+use super::{AudioSink, SampleBuffer};
 
 /// Audio sample rate. 44.1K Hz is CD-quality audio.
 const SAMPLE_RATE: SampleRate = SampleRate(44100);
 
+/// The amount of audio that we try to keep queued for the audio device.
+///
+/// Less than this risks running out of samples if the emulator is late, and more adds latency.
+const TARGET_LATENCY: Duration = Duration::from_millis(60);
+
+/// The maximum amount that the resampling ratio may be adjusted to keep the queue at the target
+/// latency. Pitch changes this small are inaudible.
+const MAX_RATE_DELTA: f64 = 0.005;
+
+/// Statistics about the health of the audio stream.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AudioStats {
+    /// The number of times the audio device requested samples that had not been generated yet.
+    pub underruns: u64,
+
+    /// The number of samples that were discarded because too many were queued.
+    pub overruns: u64,
+
+    /// The number of samples currently queued for the audio device.
+    pub buffered: usize,
+
+    /// The current adjustment to the resampling ratio. Values above 1.0 produce samples faster.
+    pub rate_adjustment: f64,
+}
+
 /// Outputs PCM audio generated by the sound controller.
 #[derive(Derivative)]
 #[derivative(Debug)]
@@ -25,17 +52,23 @@ pub struct Output {
     #[derivative(Debug = "ignore")]
     stream: Stream,
 
-    /// The CPU produces audio samples at the CPU clock rate. This is a much higher rate than PC
-    /// hardware typically supports. Therefore, we must downsample the raw signal to be playable
-    /// by audio hardware.
-    ///
-    /// The simplest way to accomplish this is "decimation": keeping only every nth sample. This
-    /// factor is computed by dividing the Game Boy CPU frequency by the audio hardware's sampling
-    /// rate.
-    pub decimation_factor: u32,
-
-    /// Queued raw emulated PCM audio samples.
+    /// Queued emulated PCM audio samples.
     pub sample_buffer: SampleBuffer,
+
+    /// The sample rate of the audio device.
+    sample_rate: u32,
+
+    /// The number of samples that should be queued to reach the target latency.
+    target_buffered: usize,
+
+    /// Incremented by the audio device callback whenever it runs out of samples.
+    underruns: Arc<AtomicU64>,
+
+    /// The number of samples that were discarded because too many were queued.
+    overruns: u64,
+
+    /// The current adjustment to the resampling ratio.
+    rate_adjustment: f64,
 }
 
 impl Output {
@@ -45,26 +78,46 @@ impl Output {
             .ok_or_else(|| anyhow!("no audio output devices found"))?;
 
         let sample_buffer = SampleBuffer::default();
+        let underruns = Arc::new(AtomicU64::new(0));
 
+        // Prefer stereo output, but fall back to mixing both channels together.
         let config = device
             .supported_output_configs()?
-            .find(|config| config.channels() == 1 && config.sample_format() == SampleFormat::F32)
+            .filter(|config| config.sample_format() == SampleFormat::F32)
+            .filter(|config| config.channels() == 1 || config.channels() == 2)
+            .max_by_key(|config| config.channels())
             .map(|config| config.with_sample_rate(SAMPLE_RATE))
             .ok_or_else(|| anyhow!("no supported audio output configuration found"))?
             .config();
 
         info!("initializing audio playback with {:?}", config);
 
-        let decimation_factor = cpu::FREQUENCY / config.sample_rate.0;
+        let sample_rate = config.sample_rate.0;
+        let channels = usize::from(config.channels);
+        let target_buffered = (TARGET_LATENCY.as_secs_f64() * f64::from(sample_rate)) as usize;
 
         let stream_buffer = Arc::clone(&sample_buffer);
+        let stream_underruns = Arc::clone(&underruns);
         let stream = device.build_output_stream(
             &config,
             move |dst: &mut [f32], _: &OutputCallbackInfo| {
                 let mut src = stream_buffer.lock().unwrap();
 
-                for sample in dst.iter_mut() {
-                    *sample = src.pop_front().unwrap_or(0.0);
+                if src.len() < dst.len() / channels {
+                    stream_underruns.fetch_add(1, Ordering::Relaxed);
+                }
+
+                for frame in dst.chunks_exact_mut(channels) {
+                    let [left, right] = src.pop_front().unwrap_or([0.0, 0.0]);
+
+                    match frame {
+                        [mono] => *mono = (left + right) / 2.0,
+                        [l, r] => {
+                            *l = left;
+                            *r = right;
+                        }
+                        _ => unreachable!(),
+                    }
                 }
             },
             |err| panic!("{}", err),
@@ -76,7 +129,65 @@ impl Output {
         Ok(Output {
             stream,
             sample_buffer,
-            decimation_factor,
+            sample_rate,
+            target_buffered,
+            underruns,
+            overruns: 0,
+            rate_adjustment: 1.0,
         })
     }
+
+    /// Returns the number of T-cycles that must be emulated to fill the queue to the target
+    /// latency.
+    pub fn cycles_until_target(&self) -> TCycles {
+        let buffered = self.sample_buffer.lock().unwrap().len();
+        let missing = self.target_buffered.saturating_sub(buffered) as u64;
+
+        TCycles((missing * u64::from(cpu::FREQUENCY) / u64::from(self.sample_rate)) as u32)
+    }
+
+    /// Returns the current statistics of the audio stream.
+    pub fn stats(&self) -> AudioStats {
+        AudioStats {
+            underruns: self.underruns.load(Ordering::Relaxed),
+            overruns: self.overruns,
+            buffered: self.sample_buffer.lock().unwrap().len(),
+            rate_adjustment: self.rate_adjustment,
+        }
+    }
+}
+
+impl AudioSink for Output {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Queues samples for playback.
+    ///
+    /// The resampling ratio is nudged up or down depending on whether the queue is below or above
+    /// the target latency. This keeps the queue from slowly draining or growing when the emulator
+    /// and the audio device disagree slightly about the passage of time.
+    fn write_samples(&mut self, samples: &[[f32; 2]]) {
+        let buffered = {
+            let mut buffer = self.sample_buffer.lock().unwrap();
+            buffer.extend(samples);
+
+            let max_buffered = self.target_buffered * 4;
+            if buffer.len() > max_buffered {
+                let excess = buffer.len() - max_buffered;
+                buffer.drain(..excess);
+                self.overruns += excess as u64;
+            }
+
+            buffer.len()
+        };
+
+        let target = self.target_buffered as f64;
+        let deviation = ((target - buffered as f64) / target).clamp(-1.0, 1.0);
+        self.rate_adjustment = 1.0 + MAX_RATE_DELTA * deviation;
+    }
+
+    fn rate_adjustment(&self) -> f64 {
+        self.rate_adjustment
+    }
 }
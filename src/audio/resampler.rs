@@ -0,0 +1,366 @@
+//! Conversion of the raw APU signal to a rate playable by audio hardware.
+//!
+//! The APU produces a new sample every T-cycle, which is far higher than the sample rate of any
+//! audio device. Simply keeping every nth sample aliases the harmonics of the square waves back
+//! into the audible range, which is heard as a harsh buzzing. Instead, we use band-limited step
+//! synthesis: the channel outputs are piecewise constant, so the signal can be described entirely
+//! by the times and sizes of its steps. Each step is added to the output as a windowed-sinc step,
+//! which contains no energy above the output's Nyquist frequency.
+//!
+//! This is the same technique used by [blip_buf], and it only costs work when the signal changes.
+//!
+//! [blip_buf]: https://code.google.com/archive/p/blip-buf/
+
+use crate::cpu;
+
+/// The number of fractional positions that the band-limited step kernel is computed for.
+const PHASES: usize = 32;
+
+/// The width of the band-limited step kernel, in output samples.
+const TAPS: usize = 16;
+
+/// The fraction of the output Nyquist frequency that is allowed to pass.
+const CUTOFF: f64 = 0.9;
+
+/// Resamples a piecewise constant signal by band-limited step synthesis.
+#[derive(Debug)]
+pub struct Resampler {
+    /// The number of output samples per input clock.
+    ratio: f64,
+
+    /// The nominal number of output samples per input clock, before any rate adjustment.
+    base_ratio: f64,
+
+    /// The position of the start of the current frame, measured in output samples.
+    offset: f64,
+
+    /// Differences between adjacent output samples. Integrating this buffer produces the output.
+    buffer: Vec<f32>,
+
+    /// The running sum of the differences that have been read.
+    integrator: f32,
+
+    /// Impulse responses of the step kernel at each fractional position.
+    kernel: Vec<[f32; TAPS]>,
+}
+
+impl Resampler {
+    /// Creates a resampler that converts a signal clocked at `clock_rate` to `sample_rate`.
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Resampler {
+        let ratio = f64::from(sample_rate) / f64::from(clock_rate);
+
+        Resampler {
+            ratio,
+            base_ratio: ratio,
+            offset: 0.0,
+            buffer: vec![0.0; TAPS],
+            integrator: 0.0,
+            kernel: (0..PHASES).map(kernel_phase).collect(),
+        }
+    }
+
+    /// Scales the output sample rate by a small factor, without changing the pitch noticeably.
+    ///
+    /// An adjustment of `1.0` produces samples at the nominal rate.
+    pub fn set_rate_adjustment(&mut self, adjustment: f64) {
+        self.ratio = self.base_ratio * adjustment;
+    }
+
+    /// Adds a change in amplitude of `delta` at `clock` cycles after the start of the frame.
+    pub fn add_delta(&mut self, clock: u32, delta: f32) {
+        let position = self.offset + f64::from(clock) * self.ratio;
+        let index = position as usize;
+        let phase = ((position - index as f64) * PHASES as f64) as usize;
+
+        if self.buffer.len() < index + TAPS {
+            self.buffer.resize(index + TAPS, 0.0);
+        }
+
+        let buffer = &mut self.buffer[index..index + TAPS];
+        for (out, tap) in buffer.iter_mut().zip(self.kernel[phase].iter()) {
+            *out += delta * tap;
+        }
+    }
+
+    /// Ends the current frame after `clocks` cycles. Samples up to the end of the frame become
+    /// available to read, and the next frame begins.
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.offset += f64::from(clocks) * self.ratio;
+
+        let len = self.offset as usize + TAPS;
+        if self.buffer.len() < len {
+            self.buffer.resize(len, 0.0);
+        }
+    }
+
+    /// The number of samples that may be read.
+    pub fn samples_available(&self) -> usize {
+        self.offset as usize
+    }
+
+    /// Reads all available samples into `out`.
+    pub fn read_samples(&mut self, out: &mut Vec<f32>) {
+        let available = self.samples_available();
+
+        out.extend(self.buffer.drain(..available).map(|difference| {
+            self.integrator += difference;
+            self.integrator
+        }));
+
+        if self.buffer.len() < TAPS {
+            self.buffer.resize(TAPS, 0.0);
+        }
+
+        self.offset -= available as f64;
+    }
+}
+
+/// Resamples both channels of the stereo APU output, and filters them like the DMG output
+/// circuitry.
+#[derive(Debug)]
+pub struct StereoResampler {
+    channels: [Resampler; 2],
+    high_pass: [HighPassFilter; 2],
+    scratch: [Vec<f32>; 2],
+}
+
+impl StereoResampler {
+    /// Creates a resampler that converts the APU output to the given sample rate.
+    pub fn new(sample_rate: u32) -> StereoResampler {
+        StereoResampler {
+            channels: [
+                Resampler::new(cpu::FREQUENCY, sample_rate),
+                Resampler::new(cpu::FREQUENCY, sample_rate),
+            ],
+            high_pass: [
+                HighPassFilter::new(sample_rate),
+                HighPassFilter::new(sample_rate),
+            ],
+            scratch: [vec![], vec![]],
+        }
+    }
+
+    /// Adds a change in amplitude of each channel at `clock` cycles after the start of the frame.
+    pub fn add_delta(&mut self, clock: u32, delta: [f32; 2]) {
+        for (channel, delta) in self.channels.iter_mut().zip(delta.iter()) {
+            if *delta != 0.0 {
+                channel.add_delta(clock, *delta);
+            }
+        }
+    }
+
+    /// Scales the output sample rate of both channels. See [`Resampler::set_rate_adjustment`].
+    pub fn set_rate_adjustment(&mut self, adjustment: f64) {
+        for channel in &mut self.channels {
+            channel.set_rate_adjustment(adjustment);
+        }
+    }
+
+    /// Ends the current frame after `clocks` cycles, and appends the filtered stereo samples to
+    /// `out`.
+    pub fn end_frame(&mut self, clocks: u32, out: &mut Vec<[f32; 2]>) {
+        for (channel, scratch) in self.channels.iter_mut().zip(self.scratch.iter_mut()) {
+            channel.end_frame(clocks);
+            channel.read_samples(scratch);
+        }
+
+        let [left_filter, right_filter] = &mut self.high_pass;
+        let [left, right] = &mut self.scratch;
+
+        out.extend(
+            left.drain(..)
+                .zip(right.drain(..))
+                .map(|(left, right)| [left_filter.filter(left), right_filter.filter(right)]),
+        );
+    }
+}
+
+/// Computes the impulse response of the band-limited step kernel for a fractional sample position.
+///
+/// Each phase is a Blackman-windowed sinc, normalized so that the step it produces has exactly the
+/// height of the delta.
+fn kernel_phase(phase: usize) -> [f32; TAPS] {
+    use std::f64::consts::PI;
+
+    let fraction = phase as f64 / PHASES as f64;
+    let center = (TAPS / 2) as f64;
+
+    let mut taps = [0.0; TAPS];
+    for (k, tap) in taps.iter_mut().enumerate() {
+        let x = k as f64 - center - fraction;
+
+        let sinc = if x == 0.0 {
+            1.0
+        } else {
+            (PI * CUTOFF * x).sin() / (PI * CUTOFF * x)
+        };
+
+        let window_position = (x + center) / TAPS as f64;
+        let window = 0.42 - 0.5 * (2.0 * PI * window_position).cos()
+            + 0.08 * (4.0 * PI * window_position).cos();
+
+        *tap = sinc * window;
+    }
+
+    let sum = taps.iter().sum::<f64>();
+    let mut kernel = [0.0; TAPS];
+    for (out, tap) in kernel.iter_mut().zip(taps.iter()) {
+        *out = (tap / sum) as f32;
+    }
+    kernel
+}
+
+/// The high-pass filter present on the output of the DMG.
+///
+/// The Game Boy's audio output is connected through a capacitor, which removes any DC offset from
+/// the signal. Without it, the unipolar channel outputs would produce pops whenever a channel is
+/// started or stopped.
+#[derive(Debug)]
+pub struct HighPassFilter {
+    /// The voltage of the capacitor.
+    capacitor: f32,
+
+    /// The fraction of the capacitor's charge that remains after a single output sample.
+    charge_factor: f32,
+}
+
+impl HighPassFilter {
+    /// Creates a filter for a signal at the given sample rate.
+    pub fn new(sample_rate: u32) -> HighPassFilter {
+        // The DMG capacitor retains this fraction of its charge every T-cycle.
+        const DMG_CHARGE_FACTOR: f64 = 0.999958;
+
+        let cycles_per_sample = f64::from(cpu::FREQUENCY) / f64::from(sample_rate);
+
+        HighPassFilter {
+            capacitor: 0.0,
+            charge_factor: DMG_CHARGE_FACTOR.powf(cycles_per_sample) as f32,
+        }
+    }
+
+    /// Filters a single sample.
+    pub fn filter(&mut self, sample: f32) -> f32 {
+        let out = sample - self.capacitor;
+        self.capacitor = sample - out * self.charge_factor;
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu;
+
+    use super::{HighPassFilter, Resampler, StereoResampler, TAPS};
+
+    #[test]
+    fn sample_count() {
+        let mut resampler = Resampler::new(cpu::FREQUENCY, 44100);
+
+        let mut samples = vec![];
+        for _ in 0..128 {
+            resampler.end_frame(cpu::FREQUENCY / 128);
+            resampler.read_samples(&mut samples);
+        }
+
+        assert_eq!(samples.len(), 44100);
+    }
+
+    #[test]
+    fn rate_adjustment() {
+        let mut resampler = Resampler::new(cpu::FREQUENCY, 44100);
+        resampler.set_rate_adjustment(1.005);
+
+        let mut samples = vec![];
+        for _ in 0..128 {
+            resampler.end_frame(cpu::FREQUENCY / 128);
+            resampler.read_samples(&mut samples);
+        }
+
+        assert_eq!(samples.len(), 44320);
+    }
+
+    #[test]
+    fn step_height() {
+        let mut resampler = Resampler::new(cpu::FREQUENCY, 44100);
+
+        resampler.add_delta(1000, 0.5);
+        resampler.end_frame(cpu::FREQUENCY / 100);
+
+        let mut samples = vec![];
+        resampler.read_samples(&mut samples);
+
+        // The step is smeared over the width of the kernel, but settles at the size of the delta.
+        assert_eq!(samples[0], 0.0);
+        for sample in &samples[TAPS + 11..] {
+            assert!((sample - 0.5).abs() < 1e-4, "{} != 0.5", sample);
+        }
+    }
+
+    #[test]
+    fn square_wave_does_not_alias() {
+        // A 15 KHz square wave. Its third harmonic at 45 KHz would alias to 900 Hz if the signal
+        // were decimated.
+        const PERIOD: u32 = cpu::FREQUENCY / 15000;
+
+        let mut resampler = Resampler::new(cpu::FREQUENCY, 44100);
+        let mut amplitude = 0.0;
+        for clock in 0..cpu::FREQUENCY / 10 {
+            let sample = if clock % PERIOD < PERIOD / 2 {
+                1.0
+            } else {
+                0.0
+            };
+            if sample != amplitude {
+                resampler.add_delta(clock, sample - amplitude);
+                amplitude = sample;
+            }
+        }
+        resampler.end_frame(cpu::FREQUENCY / 10);
+
+        let mut samples = vec![];
+        resampler.read_samples(&mut samples);
+
+        // Averaging over whole cycles of the fundamental should leave only the DC component. Any
+        // aliased low-frequency energy would show up as drift between the windows.
+        let windows = samples[100..]
+            .chunks_exact(441)
+            .map(|window| window.iter().sum::<f32>() / window.len() as f32)
+            .collect::<Vec<_>>();
+
+        for window in windows {
+            assert!((window - 0.5).abs() < 0.02, "{} != 0.5", window);
+        }
+    }
+
+    #[test]
+    fn stereo() {
+        let mut resampler = StereoResampler::new(44100);
+
+        resampler.add_delta(0, [0.5, 0.0]);
+        resampler.add_delta(100, [0.0, 0.25]);
+
+        let mut samples = vec![];
+        resampler.end_frame(cpu::FREQUENCY / 100, &mut samples);
+
+        assert_eq!(samples.len(), 440);
+
+        // The high-pass filter slowly pulls each channel back towards zero.
+        let [left, right] = samples[TAPS + 11];
+        assert!(left > 0.4 && left < 0.5, "{}", left);
+        assert!(right > 0.2 && right < 0.25, "{}", right);
+    }
+
+    #[test]
+    fn high_pass_removes_dc() {
+        let mut filter = HighPassFilter::new(44100);
+
+        assert_eq!(filter.filter(1.0), 1.0);
+
+        let mut out = 1.0;
+        for _ in 0..44100 {
+            out = filter.filter(1.0);
+        }
+
+        assert!(out.abs() < 1e-3);
+    }
+}
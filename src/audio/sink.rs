@@ -0,0 +1,266 @@
+//! Destinations for the audio generated by the sound controller.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use log::*;
+
+use super::resampler::StereoResampler;
+use super::SampleBuffer;
+
+/// Receives mixed stereo audio from the sound controller.
+///
+/// Samples are `[left, right]` pairs in the range `-1.0..=1.0`, already resampled to the sink's
+/// sample rate and filtered like the output of the DMG.
+pub trait AudioSink {
+    /// The sample rate that the sink expects samples at.
+    fn sample_rate(&self) -> u32;
+
+    /// Receives a batch of samples.
+    fn write_samples(&mut self, samples: &[[f32; 2]]);
+
+    /// A small adjustment to the rate that samples are produced at. Real-time sinks may use this
+    /// to keep their queue at a steady level. Values above 1.0 produce samples faster.
+    fn rate_adjustment(&self) -> f64 {
+        1.0
+    }
+}
+
+impl<S: AudioSink + ?Sized> AudioSink for Box<S> {
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+
+    fn write_samples(&mut self, samples: &[[f32; 2]]) {
+        (**self).write_samples(samples)
+    }
+
+    fn rate_adjustment(&self) -> f64 {
+        (**self).rate_adjustment()
+    }
+}
+
+/// An audio sink, along with the state required to resample the APU output for it.
+#[derive(Debug)]
+pub(super) struct SinkOutput<S> {
+    pub sink: S,
+    resampler: StereoResampler,
+    samples: Vec<[f32; 2]>,
+}
+
+impl<S: AudioSink> SinkOutput<S> {
+    pub fn new(sink: S) -> SinkOutput<S> {
+        SinkOutput {
+            resampler: StereoResampler::new(sink.sample_rate()),
+            sink,
+            samples: vec![],
+        }
+    }
+
+    /// Adds a change in amplitude of each channel at `clock` cycles after the start of the frame.
+    pub fn add_delta(&mut self, clock: u32, delta: [f32; 2]) {
+        self.resampler.add_delta(clock, delta);
+    }
+
+    /// Ends the current frame after `clocks` cycles, and sends the resampled audio to the sink.
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.resampler.end_frame(clocks, &mut self.samples);
+        self.sink.write_samples(&self.samples);
+        self.samples.clear();
+
+        self.resampler
+            .set_rate_adjustment(self.sink.rate_adjustment());
+    }
+}
+
+/// A sink that queues samples in memory until they are drained.
+///
+/// See [`crate::Emulator::drain_audio`].
+#[derive(Debug)]
+pub struct BufferSink {
+    sample_rate: u32,
+    buffer: SampleBuffer,
+}
+
+impl BufferSink {
+    /// Creates a sink that accepts samples at the given rate.
+    pub fn new(sample_rate: u32) -> BufferSink {
+        BufferSink {
+            sample_rate,
+            buffer: SampleBuffer::default(),
+        }
+    }
+
+    /// Returns a handle to the queued samples.
+    pub fn buffer(&self) -> SampleBuffer {
+        Arc::clone(&self.buffer)
+    }
+}
+
+impl AudioSink for BufferSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write_samples(&mut self, samples: &[[f32; 2]]) {
+        self.buffer.lock().unwrap().extend(samples);
+    }
+}
+
+/// A sink that writes samples to a 16-bit stereo PCM WAV file.
+///
+/// The WAV header contains the length of the audio, so it is updated periodically and when the
+/// sink is dropped. A file that is not closed cleanly will be missing at most a second of audio.
+pub struct WavSink<W: Write + Seek> {
+    writer: W,
+    sample_rate: u32,
+
+    /// The number of samples written to the file.
+    len: u32,
+
+    /// The number of samples written since the header was last updated.
+    unfinalized: u32,
+}
+
+impl WavSink<BufWriter<File>> {
+    /// Creates a WAV file at the given path.
+    pub fn create(path: impl AsRef<Path>, sample_rate: u32) -> io::Result<Self> {
+        WavSink::new(BufWriter::new(File::create(path)?), sample_rate)
+    }
+}
+
+impl<W: Write + Seek> WavSink<W> {
+    /// The number of bytes used by each stereo sample.
+    const BLOCK_ALIGN: u16 = 4;
+
+    /// Creates a sink that writes a WAV file to the given writer.
+    pub fn new(mut writer: W, sample_rate: u32) -> io::Result<Self> {
+        writer.write_all(b"RIFF")?;
+        writer.write_u32::<LittleEndian>(36)?;
+        writer.write_all(b"WAVE")?;
+
+        writer.write_all(b"fmt ")?;
+        writer.write_u32::<LittleEndian>(16)?;
+        writer.write_u16::<LittleEndian>(1)?; // PCM
+        writer.write_u16::<LittleEndian>(2)?; // Channels
+        writer.write_u32::<LittleEndian>(sample_rate)?;
+        writer.write_u32::<LittleEndian>(sample_rate * u32::from(Self::BLOCK_ALIGN))?;
+        writer.write_u16::<LittleEndian>(Self::BLOCK_ALIGN)?;
+        writer.write_u16::<LittleEndian>(16)?; // Bits per sample
+
+        writer.write_all(b"data")?;
+        writer.write_u32::<LittleEndian>(0)?;
+
+        Ok(WavSink {
+            writer,
+            sample_rate,
+            len: 0,
+            unfinalized: 0,
+        })
+    }
+
+    /// Updates the header with the current length of the audio, and flushes the writer.
+    pub fn finalize(&mut self) -> io::Result<()> {
+        let data_len = self.len * u32::from(Self::BLOCK_ALIGN);
+
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer.write_u32::<LittleEndian>(36 + data_len)?;
+        self.writer.seek(SeekFrom::Start(40))?;
+        self.writer.write_u32::<LittleEndian>(data_len)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        self.unfinalized = 0;
+
+        Ok(())
+    }
+
+    fn write_sample(&mut self, sample: f32) -> io::Result<()> {
+        let sample = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+        self.writer.write_i16::<LittleEndian>(sample)
+    }
+}
+
+impl<W: Write + Seek> AudioSink for WavSink<W> {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn write_samples(&mut self, samples: &[[f32; 2]]) {
+        let result = samples.iter().try_for_each(|&[left, right]| {
+            self.write_sample(left)?;
+            self.write_sample(right)
+        });
+
+        if let Err(e) = result {
+            error!("unable to write WAV samples: {}", e);
+            return;
+        }
+
+        self.len += samples.len() as u32;
+        self.unfinalized += samples.len() as u32;
+
+        if self.unfinalized >= self.sample_rate {
+            if let Err(e) = self.finalize() {
+                error!("unable to update WAV header: {}", e);
+            }
+        }
+    }
+}
+
+impl<W: Write + Seek> Drop for WavSink<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finalize() {
+            error!("unable to finalize WAV file: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use super::{AudioSink, BufferSink, WavSink};
+
+    #[test]
+    fn buffer_sink() {
+        let mut sink = BufferSink::new(48000);
+        let buffer = sink.buffer();
+
+        sink.write_samples(&[[0.0, 1.0], [0.5, -0.5]]);
+
+        assert_eq!(sink.sample_rate(), 48000);
+        assert_eq!(
+            buffer.lock().unwrap().drain(..).collect::<Vec<_>>(),
+            vec![[0.0, 1.0], [0.5, -0.5]]
+        );
+    }
+
+    #[test]
+    fn wav_sink() {
+        let mut wav = Cursor::new(vec![]);
+
+        let mut sink = WavSink::new(&mut wav, 44100).unwrap();
+        sink.write_samples(&[[0.0, 1.0], [-1.0, 2.0]]);
+        drop(sink);
+
+        let wav = wav.into_inner();
+
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(LittleEndian::read_u32(&wav[4..8]), 36 + 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(LittleEndian::read_u16(&wav[22..24]), 2);
+        assert_eq!(LittleEndian::read_u32(&wav[24..28]), 44100);
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(LittleEndian::read_u32(&wav[40..44]), 8);
+
+        let mut samples = [0; 4];
+        LittleEndian::read_i16_into(&wav[44..], &mut samples);
+        assert_eq!(samples, [0, i16::MAX, -i16::MAX, i16::MAX]);
+    }
+}
@@ -0,0 +1,200 @@
+//! Logging of sound register writes in the VGM format.
+//!
+//! A VGM file is a stream of timed register writes to a sound chip, which can be played back by
+//! external tools. See the [VGM specification] for more details.
+//!
+//! [VGM specification]: https://vgmrips.net/wiki/VGM_Specification
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use log::*;
+
+use crate::cpu;
+
+/// The rate that time is measured at in a VGM file.
+const SAMPLE_RATE: u64 = 44100;
+
+/// The version of the VGM specification that the file follows, in BCD. 1.61 introduced the Game
+/// Boy DMG.
+const VERSION: u32 = 0x161;
+
+/// The offset of the command data from the start of the file.
+const DATA_OFFSET: u32 = 0x100;
+
+/// A writer or file that may be seeked.
+pub trait WriteSeek: Write + Seek {}
+
+impl<W: Write + Seek> WriteSeek for W {}
+
+/// Writes a VGM file containing writes to the Game Boy DMG sound registers.
+///
+/// The header contains the length of the file, so it is updated periodically and when the writer
+/// is dropped. A file that is not closed cleanly will be missing at most a second of audio.
+pub struct VgmWriter<W: Write + Seek> {
+    writer: W,
+
+    /// The current time, in T-cycles.
+    cycle: u64,
+
+    /// The time that has been written to the file, in samples.
+    samples: u64,
+
+    /// The time that had been written to the file when the header was last updated, in samples.
+    finalized_samples: u64,
+}
+
+impl<W: Write + Seek> VgmWriter<W> {
+    /// Creates a VGM writer that writes to the given writer.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        // The length fields are filled in when the file is finalized.
+        let mut header = [0; DATA_OFFSET as usize];
+        header[0x00..0x04].copy_from_slice(b"Vgm ");
+        LittleEndian::write_u32(&mut header[0x08..], VERSION);
+        LittleEndian::write_u32(&mut header[0x34..], DATA_OFFSET - 0x34);
+        LittleEndian::write_u32(&mut header[0x80..], cpu::FREQUENCY);
+
+        writer.write_all(&header)?;
+
+        let mut vgm = VgmWriter {
+            writer,
+            cycle: 0,
+            samples: 0,
+            finalized_samples: 0,
+        };
+        vgm.finalize()?;
+
+        Ok(vgm)
+    }
+
+    /// Records a write to a sound register at a time in T-cycles.
+    pub fn write_register(&mut self, cycle: u64, address: u16, value: u8) -> io::Result<()> {
+        debug_assert!((0xFF10..=0xFF3F).contains(&address));
+
+        self.set_time(cycle)?;
+        self.write_waits()?;
+
+        self.writer
+            .write_all(&[0xB3, (address - 0xFF10) as u8, value])
+    }
+
+    /// Advances the current time, in T-cycles, without writing any registers.
+    pub fn set_time(&mut self, cycle: u64) -> io::Result<()> {
+        self.cycle = cycle;
+
+        if self.target_samples() - self.finalized_samples >= SAMPLE_RATE {
+            self.finalize()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the end of the file, updates the header with the current length, and flushes the
+    /// writer.
+    ///
+    /// Logging may continue after the file is finalized.
+    pub fn finalize(&mut self) -> io::Result<()> {
+        self.write_waits()?;
+        self.writer.write_u8(0x66)?;
+
+        let end = self.writer.stream_position()?;
+
+        self.writer.seek(SeekFrom::Start(0x04))?;
+        self.writer.write_u32::<LittleEndian>((end - 0x04) as u32)?;
+        self.writer.seek(SeekFrom::Start(0x18))?;
+        self.writer.write_u32::<LittleEndian>(self.samples as u32)?;
+
+        // Any further commands overwrite the end of the file.
+        self.writer.seek(SeekFrom::Start(end - 1))?;
+        self.writer.flush()?;
+
+        self.finalized_samples = self.samples;
+
+        Ok(())
+    }
+
+    /// The current time, in samples.
+    fn target_samples(&self) -> u64 {
+        self.cycle * SAMPLE_RATE / u64::from(cpu::FREQUENCY)
+    }
+
+    /// Writes wait commands until the file reaches the current time.
+    fn write_waits(&mut self) -> io::Result<()> {
+        let target = self.target_samples();
+
+        while self.samples < target {
+            let wait = (target - self.samples).min(0xFFFF);
+
+            match wait {
+                1..=16 => self.writer.write_u8(0x70 + (wait - 1) as u8)?,
+                735 => self.writer.write_u8(0x62)?,
+                882 => self.writer.write_u8(0x63)?,
+                _ => {
+                    self.writer.write_u8(0x61)?;
+                    self.writer.write_u16::<LittleEndian>(wait as u16)?;
+                }
+            }
+
+            self.samples += wait;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Drop for VgmWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finalize() {
+            error!("unable to finalize VGM file: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::{ByteOrder, LittleEndian};
+
+    use crate::cpu;
+
+    use super::VgmWriter;
+
+    #[test]
+    fn vgm_writer() {
+        let mut vgm = Cursor::new(vec![]);
+
+        let mut writer = VgmWriter::new(&mut vgm).unwrap();
+        writer.write_register(0, 0xFF26, 0x80).unwrap();
+        writer.write_register(95, 0xFF24, 0x77).unwrap();
+        writer.write_register(1000, 0xFF30, 0x12).unwrap();
+        writer
+            .write_register(u64::from(cpu::FREQUENCY) * 2, 0xFF25, 0xFF)
+            .unwrap();
+        drop(writer);
+
+        let vgm = vgm.into_inner();
+
+        assert_eq!(&vgm[0x00..0x04], b"Vgm ");
+        assert_eq!(LittleEndian::read_u32(&vgm[0x04..]) as usize, vgm.len() - 4);
+        assert_eq!(LittleEndian::read_u32(&vgm[0x08..]), 0x161);
+        assert_eq!(LittleEndian::read_u32(&vgm[0x18..]), 88200);
+        assert_eq!(LittleEndian::read_u32(&vgm[0x34..]), 0xCC);
+        assert_eq!(LittleEndian::read_u32(&vgm[0x80..]), cpu::FREQUENCY);
+
+        #[rustfmt::skip]
+        assert_eq!(
+            &vgm[0x100..],
+            &[
+                0xB3, 0x16, 0x80,
+                0xB3, 0x14, 0x77,
+                0x79,
+                0xB3, 0x20, 0x12,
+                0x61, 0xFF, 0xFF,
+                0x61, 0x7F, 0x58,
+                0xB3, 0x15, 0xFF,
+                0x66,
+            ][..]
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Code/data logging.
+//!
+//! The log records how each byte of the cartridge ROM has been accessed by the CPU. It is stored
+//! as a file with one byte of [`CdlFlags`] for each byte of the ROM, so it can be accumulated
+//! across sessions and used to find the code and data regions of a ROM.
+//!
+//! Like watchpoints, only accesses that tick the components are logged.
+
+use std::io::{self, Write};
+
+use bitflags::bitflags;
+use thiserror::Error;
+
+use crate::memory::ROM_BANK_SIZE;
+
+bitflags! {
+    /// The ways that a byte of the ROM has been accessed.
+    pub struct CdlFlags: u8 {
+        /// Fetched as the first byte of an instruction.
+        const OPCODE = 0x01;
+
+        /// Fetched as an operand of an instruction.
+        const OPERAND = 0x02;
+
+        /// Read by an instruction.
+        const DATA = 0x04;
+
+        /// Fetched as part of an instruction.
+        const CODE = Self::OPCODE.bits | Self::OPERAND.bits;
+    }
+}
+
+/// An error returned when loading a code/data log fails.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("the log covers {actual} bytes, but the ROM is {expected} bytes")]
+pub struct CdlSizeError {
+    expected: usize,
+    actual: usize,
+}
+
+/// The number of bytes of a region of the ROM that have been accessed in each way.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Coverage {
+    /// The number of bytes that have been fetched as part of an instruction.
+    pub code: usize,
+
+    /// The number of bytes that have been read as data.
+    pub data: usize,
+
+    /// The number of bytes that have not been accessed.
+    pub unused: usize,
+
+    /// The number of bytes in the region.
+    pub total: usize,
+}
+
+impl Coverage {
+    fn of(flags: &[u8]) -> Coverage {
+        let mut coverage = Coverage {
+            total: flags.len(),
+            ..Coverage::default()
+        };
+
+        for &flags in flags {
+            let flags = CdlFlags::from_bits_truncate(flags);
+
+            if flags.intersects(CdlFlags::CODE) {
+                coverage.code += 1;
+            }
+
+            if flags.contains(CdlFlags::DATA) {
+                coverage.data += 1;
+            }
+
+            if flags.is_empty() {
+                coverage.unused += 1;
+            }
+        }
+
+        coverage
+    }
+
+    /// The percentage of the bytes that have been accessed.
+    pub fn percent_used(&self) -> f64 {
+        (self.total - self.unused) as f64 * 100.0 / self.total.max(1) as f64
+    }
+}
+
+/// Records how each byte of the cartridge ROM has been accessed.
+#[derive(Debug, Clone)]
+pub struct CodeDataLog {
+    flags: Vec<u8>,
+}
+
+impl CodeDataLog {
+    /// Creates an empty log for a ROM of `rom_len` bytes.
+    pub fn new(rom_len: usize) -> CodeDataLog {
+        CodeDataLog {
+            flags: vec![0; rom_len],
+        }
+    }
+
+    /// Loads a log that was previously saved for a ROM of `rom_len` bytes.
+    pub fn from_bytes(bytes: &[u8], rom_len: usize) -> Result<CodeDataLog, CdlSizeError> {
+        if bytes.len() != rom_len {
+            return Err(CdlSizeError {
+                expected: rom_len,
+                actual: bytes.len(),
+            });
+        }
+
+        Ok(CodeDataLog {
+            flags: bytes.to_vec(),
+        })
+    }
+
+    /// The contents of the log file, one byte of flags for each byte of the ROM.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.flags
+    }
+
+    /// Records an access to the byte at `offset` in the ROM.
+    pub fn record(&mut self, offset: usize, flags: CdlFlags) {
+        if let Some(logged) = self.flags.get_mut(offset) {
+            *logged |= flags.bits();
+        }
+    }
+
+    /// The accesses that have been recorded for the byte at `offset` in the ROM.
+    pub fn flags(&self, offset: usize) -> CdlFlags {
+        self.flags.get(offset).map_or(CdlFlags::empty(), |&flags| {
+            CdlFlags::from_bits_truncate(flags)
+        })
+    }
+
+    /// The coverage of the entire ROM.
+    pub fn coverage(&self) -> Coverage {
+        Coverage::of(&self.flags)
+    }
+
+    /// The coverage of each ROM bank, in order.
+    pub fn bank_coverage(&self) -> Vec<Coverage> {
+        self.flags.chunks(ROM_BANK_SIZE).map(Coverage::of).collect()
+    }
+
+    /// Writes a report of the coverage of each bank and of the entire ROM.
+    pub fn write_report(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "{:>6} {:>8} {:>8} {:>8} {:>8}",
+            "bank", "code", "data", "unused", "used"
+        )?;
+
+        let banks = self
+            .bank_coverage()
+            .into_iter()
+            .enumerate()
+            .map(|(bank, coverage)| (format!("{:#04x}", bank), coverage));
+
+        for (bank, coverage) in banks.chain(Some((String::from("total"), self.coverage()))) {
+            writeln!(
+                out,
+                "{:>6} {:>8} {:>8} {:>8} {:>7.2}%",
+                bank,
+                coverage.code,
+                coverage.data,
+                coverage.unused,
+                coverage.percent_used()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::cpu::Cpu;
+
+    use super::{CdlFlags, CdlSizeError, CodeDataLog, Coverage};
+
+    #[test]
+    fn log_accesses() {
+        let mut rom = vec![0; 0x8000];
+        rom[0x14D] = 0xE7; // Header checksum
+        rom[0x100..0x103].copy_from_slice(&[0xFA, 0x50, 0x01]); // LD A,($0150)
+
+        let mut bus = Bus::default();
+        bus.mmu.load_rom(&rom).unwrap();
+        bus.cdl = Some(CodeDataLog::new(rom.len()));
+
+        let mut cpu = Cpu::new();
+        cpu.reg.pc = 0x100;
+        bus.timer.reset_diff();
+        cpu.step(&mut bus);
+
+        let cdl = bus.cdl.as_ref().unwrap();
+        assert_eq!(cdl.flags(0x100), CdlFlags::OPCODE);
+        assert_eq!(cdl.flags(0x101), CdlFlags::OPERAND);
+        assert_eq!(cdl.flags(0x102), CdlFlags::OPERAND);
+        assert_eq!(cdl.flags(0x150), CdlFlags::DATA);
+        assert_eq!(cdl.flags(0x103), CdlFlags::empty());
+
+        assert_eq!(
+            cdl.bank_coverage(),
+            [
+                Coverage {
+                    code: 3,
+                    data: 1,
+                    unused: 0x4000 - 4,
+                    total: 0x4000,
+                },
+                Coverage {
+                    code: 0,
+                    data: 0,
+                    unused: 0x4000,
+                    total: 0x4000,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn accumulate() {
+        let mut cdl = CodeDataLog::new(4);
+        cdl.record(1, CdlFlags::OPCODE);
+
+        let mut cdl = CodeDataLog::from_bytes(cdl.as_bytes(), 4).unwrap();
+        cdl.record(1, CdlFlags::DATA);
+        cdl.record(4, CdlFlags::DATA);
+        assert_eq!(cdl.as_bytes(), [0x00, 0x05, 0x00, 0x00]);
+
+        assert_eq!(
+            CodeDataLog::from_bytes(cdl.as_bytes(), 8).unwrap_err(),
+            CdlSizeError {
+                expected: 8,
+                actual: 4
+            }
+        );
+    }
+}
@@ -1,6 +1,8 @@
 //! Inter-component communication.
 
+mod cdl;
 mod timer;
+mod watchpoint;
 
 use std::fmt::{self, Display};
 use std::io::Write;
@@ -20,6 +22,9 @@ use crate::memory::{Addressable, Mmu};
 
 use self::timer::Timer;
 
+pub use self::cdl::{CdlFlags, CdlSizeError, CodeDataLog, Coverage};
+pub use self::watchpoint::{ParseWatchKindError, WatchHit, WatchKind, Watchpoint, Watchpoints};
+
 /// The "wires" of the emulator.
 ///
 /// The `Bus` contains each individual component. All memory accesses are proxied through the
@@ -34,6 +39,11 @@ pub struct Bus {
     pub timer: Timer,
     pub button_state: ButtonState,
     pub serial_transfer_data: u8,
+    pub watchpoints: Watchpoints,
+
+    /// Records how the cartridge ROM is accessed. `None` if accesses are not being logged.
+    #[derivative(Debug = "ignore")]
+    pub cdl: Option<CodeDataLog>,
     #[derivative(Debug = "ignore")]
     pub serial_out: Option<Box<dyn Write>>,
 }
@@ -57,15 +67,51 @@ impl Bus {
     }
 
     /// Reads a single byte from memory. Ticks each component a cycle.
+    ///
+    /// The read is checked against the watchpoints, and logged as data.
     pub fn read_byte(&mut self, address: u16) -> u8 {
+        self.read_byte_as(address, CdlFlags::DATA)
+    }
+
+    /// Reads a single byte of an instruction from memory. Ticks each component a cycle.
+    ///
+    /// The read is checked against the watchpoints, and logged as the given part of an
+    /// instruction.
+    pub fn fetch_byte(&mut self, address: u16, flags: CdlFlags) -> u8 {
+        self.read_byte_as(address, flags)
+    }
+
+    fn read_byte_as(&mut self, address: u16, flags: CdlFlags) -> u8 {
         let byte = self.read_byte_no_tick(address);
+
+        if !self.watchpoints.is_empty() {
+            self.watchpoints.check_read(address, byte);
+        }
+
+        if let Some(cdl) = &mut self.cdl {
+            if let Some(offset) = self.mmu.cartridge_offset(address) {
+                cdl.record(offset, flags);
+            }
+        }
+
         self.tick(MCycles(1));
         byte
     }
 
     /// Writes a single byte to memory. Ticks each component a cycle.
+    ///
+    /// The write is checked against the watchpoints.
     pub fn write_byte(&mut self, address: u16, byte: u8) {
-        self.write_byte_no_tick(address, byte);
+        if self.watchpoints.is_empty() {
+            self.write_byte_no_tick(address, byte);
+        } else {
+            let old = self.read_byte_no_tick(address);
+            self.write_byte_no_tick(address, byte);
+            let current = self.read_byte_no_tick(address);
+
+            self.watchpoints.check_write(address, old, byte, current);
+        }
+
         self.tick(MCycles(1));
     }
 
@@ -97,8 +143,15 @@ impl Bus {
             self.ppu.step(&mut self.interrupts);
         }
 
-        self.timer
+        let apu_clocks = self
+            .timer
             .tick(cycles, &mut self.interrupts.timer.requested);
+
+        for _ in 0..apu_clocks {
+            self.audio.clock_frame_sequencer();
+        }
+
+        self.audio.step(t_cycles);
     }
 
     /// Create an iterator over the entire memory space.
@@ -353,7 +406,11 @@ impl Bus {
             }
 
             // DIV - Divider Register
-            0xFF04 => self.timer.reset_divider(),
+            0xFF04 => {
+                if self.timer.reset_divider() {
+                    self.audio.clock_frame_sequencer();
+                }
+            }
 
             // TIMA - Timer Counter
             0xFF05 => self.timer.reg.counter = byte,
@@ -466,7 +523,7 @@ impl Display for Bus {
 
 #[cfg(test)]
 mod tests {
-    use super::Bus;
+    use super::{Bus, WatchKind, Watchpoint};
 
     use std::{u16, u8};
 
@@ -620,6 +677,42 @@ mod tests {
         assert_eq!(bus.read_byte(0xFF00) & 0x3F, 0x3C);
     }
 
+    #[test]
+    fn watchpoints() {
+        let mut bus = Bus::default();
+        bus.watchpoints
+            .add(Watchpoint::new(0xC000..=0xC0FF, WatchKind::Write));
+        bus.watchpoints
+            .add(Watchpoint::new(0xD000..=0xD000, WatchKind::Change));
+        bus.watchpoints
+            .add(Watchpoint::new(0xFF80..=0xFF80, WatchKind::Read));
+
+        bus.read_byte(0xC000);
+        bus.write_byte(0xC100, 0x12);
+        bus.write_byte(0xD000, 0x00);
+        bus.write_byte_no_tick(0xC000, 0x34);
+        bus.read_byte_no_tick(0xFF80);
+        assert_eq!(bus.watchpoints.take_hit(), None);
+
+        bus.write_byte(0xC0FF, 0x56);
+        bus.write_byte(0xC000, 0x78);
+        let hit = bus.watchpoints.take_hit().unwrap();
+        assert_eq!(hit.address, 0xC0FF);
+        assert_eq!((hit.old, hit.new), (0x00, 0x56));
+        assert_eq!(bus.watchpoints.take_hit(), None);
+
+        bus.write_byte(0xD000, 0x9A);
+        let hit = bus.watchpoints.take_hit().unwrap();
+        assert_eq!(hit.watchpoint.kind, WatchKind::Change);
+        assert_eq!((hit.old, hit.new), (0x00, 0x9A));
+
+        bus.write_byte_no_tick(0xFF80, 0xBC);
+        assert_eq!(bus.read_byte(0xFF80), 0xBC);
+        let hit = bus.watchpoints.take_hit().unwrap();
+        assert_eq!(hit.watchpoint.kind, WatchKind::Read);
+        assert_eq!(hit.new, 0xBC);
+    }
+
     #[test]
     fn lcd_control() {
         let mut bus = Bus::default();
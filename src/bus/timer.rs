@@ -31,15 +31,19 @@ impl Timer {
 
     /// Increment all timer-related registers, based on the M-time of the last instruction.
     ///
-    /// Requests the timer interrupt if necessary.
-    pub(super) fn tick(&mut self, mtime: MCycles, interrupt_requested: &mut bool) {
+    /// Requests the timer interrupt if necessary. Returns the number of times that bit 4 of DIV
+    /// fell from 1 to 0, which clocks the APU frame sequencer.
+    pub(super) fn tick(&mut self, mtime: MCycles, interrupt_requested: &mut bool) -> u32 {
         self.diff += mtime.0;
 
-        // The divider is always counting, regardless of whether the timer is enabled.
-        self.div_counter += TCycles::from(mtime);
+        // The divider is always counting, regardless of whether the timer is enabled. The internal
+        // counter is 16 bits wide, so bit 4 of DIV falls each time bit 13 is carried into.
+        let div_counter = self.div_counter.0 + TCycles::from(mtime).0;
+        let apu_clocks = (div_counter >> 13) - (self.div_counter.0 >> 13);
+        self.div_counter = TCycles(div_counter & 0xFFFF);
 
         if !self.is_enabled() {
-            return;
+            return apu_clocks;
         }
 
         self.timer_counter += mtime;
@@ -78,6 +82,8 @@ impl Timer {
                 *interrupt_requested = true;
             }
         }
+
+        apu_clocks
     }
 
     /// Returns the number of M-cycles that have passed since the last call of this method.
@@ -89,9 +95,15 @@ impl Timer {
         self.diff = 0;
     }
 
-    pub fn reset_divider(&mut self) {
+    /// Resets DIV to 0. Returns `true` if bit 4 of DIV was set, in which case the reset clocks the
+    /// APU frame sequencer.
+    pub fn reset_divider(&mut self) -> bool {
+        let apu_clock = self.div_counter.0 & 0x1000 != 0;
+
         self.div_counter = TCycles(0);
         self.timer_counter = MCycles(0);
+
+        apu_clock
     }
 
     pub fn is_enabled(&self) -> bool {
@@ -145,6 +157,34 @@ mod tests {
         assert_eq!(timer.divider(), 1);
     }
 
+    #[test]
+    fn apu_clock() {
+        let mut interrupt_requested = false;
+        let mut timer = Timer::default();
+
+        // Bit 4 of DIV falls every 8192 T-cycles.
+        let mut clocks = 0;
+        for _ in 0..4096 {
+            clocks += timer.tick(MCycles(1), &mut interrupt_requested);
+        }
+        assert_eq!(clocks, 2);
+
+        // DIV wraps around after 65536 T-cycles, and the clock continues.
+        for _ in 0..12288 {
+            clocks += timer.tick(MCycles(1), &mut interrupt_requested);
+        }
+        assert_eq!(timer.divider(), 0);
+        assert_eq!(clocks, 8);
+
+        assert_eq!(timer.tick(MCycles(2048), &mut interrupt_requested), 1);
+        assert_eq!(timer.divider(), 32);
+
+        // Resetting DIV while bit 4 is set clocks the sequencer.
+        timer.tick(MCycles(1024), &mut interrupt_requested);
+        assert!(timer.reset_divider());
+        assert!(!timer.reset_divider());
+    }
+
     #[test]
     fn tima() {
         let mut interrupt_requested = false;
@@ -0,0 +1,188 @@
+//! Memory watchpoints.
+//!
+//! Watchpoints are checked on every memory access made by the CPU through the bus. Accesses made
+//! without ticking the components, such as those made by the debugger itself, are never watched.
+
+use std::fmt::{self, Display};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// The kind of memory access that triggers a watchpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    /// Any read from the watched memory.
+    Read,
+
+    /// Any write to the watched memory.
+    Write,
+
+    /// A write that changes the value of the watched memory.
+    Change,
+}
+
+impl Display for WatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Change => "change",
+        };
+
+        write!(f, "{}", kind)
+    }
+}
+
+/// An error returned when parsing a [`WatchKind`] fails.
+#[derive(Debug, Error)]
+#[error("unknown watchpoint kind '{0}': expected 'r', 'w' or 'c'")]
+pub struct ParseWatchKindError(String);
+
+impl FromStr for WatchKind {
+    type Err = ParseWatchKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "r" | "read" => Ok(WatchKind::Read),
+            "w" | "write" => Ok(WatchKind::Write),
+            "c" | "change" => Ok(WatchKind::Change),
+            _ => Err(ParseWatchKindError(s.to_owned())),
+        }
+    }
+}
+
+/// Pauses execution when a range of memory is accessed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// The watched addresses.
+    pub range: RangeInclusive<u16>,
+
+    /// The kind of access that triggers the watchpoint.
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    /// Creates a watchpoint on a range of addresses.
+    pub fn new(range: RangeInclusive<u16>, kind: WatchKind) -> Watchpoint {
+        Watchpoint { range, kind }
+    }
+}
+
+impl Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.range.start() == self.range.end() {
+            write!(f, "{:#06x} ({})", self.range.start(), self.kind)
+        } else {
+            write!(
+                f,
+                "{:#06x}-{:#06x} ({})",
+                self.range.start(),
+                self.range.end(),
+                self.kind
+            )
+        }
+    }
+}
+
+/// A memory access that triggered a watchpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchHit {
+    /// The watchpoint that was triggered.
+    pub watchpoint: Watchpoint,
+
+    /// The address that was accessed.
+    pub address: u16,
+
+    /// The value of the memory before the access.
+    pub old: u8,
+
+    /// The value that was read or written.
+    pub new: u8,
+}
+
+impl Display for WatchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.watchpoint.kind {
+            WatchKind::Read => write!(f, "read {:#04x} from {:#06x}", self.new, self.address),
+            WatchKind::Write | WatchKind::Change => write!(
+                f,
+                "wrote {:#04x} to {:#06x} (was {:#04x})",
+                self.new, self.address, self.old
+            ),
+        }
+    }
+}
+
+/// The set of active watchpoints, and the first access that triggered one of them.
+#[derive(Debug, Default)]
+pub struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    hit: Option<WatchHit>,
+}
+
+impl Watchpoints {
+    /// Adds a watchpoint.
+    pub fn add(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    /// Removes the watchpoint at the given index, as listed by [`Watchpoints::iter`].
+    pub fn remove(&mut self, index: usize) -> Option<Watchpoint> {
+        if index < self.watchpoints.len() {
+            Some(self.watchpoints.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over the active watchpoints.
+    pub fn iter(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.watchpoints.iter()
+    }
+
+    /// Whether there are no active watchpoints.
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    /// Returns the first access that triggered a watchpoint since the last call, if any.
+    pub fn take_hit(&mut self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+
+    /// Checks a read of `value` from `address` against the watchpoints.
+    pub(super) fn check_read(&mut self, address: u16, value: u8) {
+        self.check(address, value, value, |kind| kind == WatchKind::Read);
+    }
+
+    /// Checks a write of `value` to `address` against the watchpoints. `old` and `current` are
+    /// the values that could be read from the address before and after the write.
+    pub(super) fn check_write(&mut self, address: u16, old: u8, value: u8, current: u8) {
+        self.check(address, old, value, |kind| match kind {
+            WatchKind::Read => false,
+            WatchKind::Write => true,
+            WatchKind::Change => old != current,
+        });
+    }
+
+    fn check(&mut self, address: u16, old: u8, new: u8, triggers: impl Fn(WatchKind) -> bool) {
+        if self.hit.is_some() {
+            return;
+        }
+
+        let watchpoint = self
+            .watchpoints
+            .iter()
+            .find(|watchpoint| watchpoint.range.contains(&address) && triggers(watchpoint.kind));
+
+        if let Some(watchpoint) = watchpoint {
+            self.hit = Some(WatchHit {
+                watchpoint: watchpoint.clone(),
+                address,
+                old,
+                new,
+            });
+        }
+    }
+}
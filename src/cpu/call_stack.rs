@@ -0,0 +1,202 @@
+//! A shadow of the call stack, for backtraces.
+//!
+//! The return addresses on the stack cannot be told apart from other pushed values, so the CPU
+//! records every call, restart and interrupt dispatch as it happens, and drops the record when
+//! the return address is popped.
+//!
+//! Programs may manipulate the stack directly, such as by popping a return address to read inline
+//! data, or by resetting the stack pointer. Each frame is identified by where its return address
+//! was pushed, so frames that are below the stack pointer after a return, or at or below the
+//! return address of a new call, are discarded as stale.
+
+use std::fmt::{self, Display};
+
+/// The way that a frame was entered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// A `CALL` instruction.
+    Call,
+
+    /// A `RST` instruction.
+    Restart,
+
+    /// An interrupt dispatch.
+    Interrupt,
+}
+
+impl Display for FrameKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            FrameKind::Call => "call",
+            FrameKind::Restart => "rst",
+            FrameKind::Interrupt => "interrupt",
+        };
+
+        write!(f, "{}", kind)
+    }
+}
+
+/// A function that has been entered, but has not returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// How the function was entered.
+    pub kind: FrameKind,
+
+    /// The address of the function.
+    pub target: u16,
+
+    /// The bank that was mapped at the address of the function when it was entered.
+    pub target_bank: Option<u16>,
+
+    /// The address that the function returns to.
+    pub return_address: u16,
+
+    /// The bank that was mapped at the return address when the function was entered.
+    pub return_bank: Option<u16>,
+
+    /// The address of the return address on the stack.
+    pub sp: u16,
+}
+
+/// The functions that have been entered but have not returned, as seen by the CPU.
+#[derive(Debug, Default, Clone)]
+pub struct CallStack {
+    /// The frames, outermost first. The stack pointer of each frame is lower than the last.
+    frames: Vec<Frame>,
+}
+
+impl CallStack {
+    /// Records that a function was entered.
+    pub fn push(&mut self, frame: Frame) {
+        while let Some(top) = self.frames.last() {
+            if top.sp > frame.sp {
+                break;
+            }
+
+            self.frames.pop();
+        }
+
+        self.frames.push(frame);
+    }
+
+    /// Records that a return set the stack pointer to `sp`.
+    pub fn pop(&mut self, sp: u16) {
+        while let Some(top) = self.frames.last() {
+            if top.sp >= sp {
+                break;
+            }
+
+            self.frames.pop();
+        }
+    }
+
+    /// Returns an iterator over the frames, innermost first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Frame> {
+        self.frames.iter().rev()
+    }
+
+    /// Whether no functions have been entered.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Forgets all frames.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::cpu::{Cpu, Flags};
+
+    use super::{CallStack, Frame, FrameKind};
+
+    fn frame(return_address: u16, sp: u16) -> Frame {
+        Frame {
+            kind: FrameKind::Call,
+            target: 0,
+            target_bank: None,
+            return_address,
+            return_bank: None,
+            sp,
+        }
+    }
+
+    fn step(cpu: &mut Cpu, bus: &mut Bus) {
+        bus.timer.reset_diff();
+        cpu.step(bus);
+    }
+
+    fn return_addresses(stack: &CallStack) -> Vec<u16> {
+        stack.iter().map(|frame| frame.return_address).collect()
+    }
+
+    #[test]
+    fn stale_frames() {
+        let mut stack = CallStack::default();
+        stack.push(frame(0x0150, 0xDFFC));
+        stack.push(frame(0x0200, 0xDFFA));
+        stack.push(frame(0x0300, 0xDFF6));
+        assert_eq!(return_addresses(&stack), [0x0300, 0x0200, 0x0150]);
+
+        // The innermost function discarded its return address and jumped back to its caller, which
+        // made another call.
+        stack.push(frame(0x0210, 0xDFF6));
+        assert_eq!(return_addresses(&stack), [0x0210, 0x0200, 0x0150]);
+
+        // A return skipped over a frame that was discarded.
+        stack.pop(0xDFFC);
+        assert_eq!(return_addresses(&stack), [0x0150]);
+
+        stack.pop(0xDFFE);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn track_calls() {
+        let mut cpu = Cpu::new();
+        let mut bus = Bus::default();
+        cpu.reg.pc = 0xC000;
+        cpu.reg.sp = 0xDFFE;
+
+        let program: &[(u16, &[u8])] = &[
+            (0xC000, &[0xCD, 0x10, 0xC0]), // CALL $C010
+            (0xC010, &[0xC4, 0x20, 0xC0]), // CALL NZ,$C020
+            (0xC013, &[0xEF]),             // RST $28
+            (0xC020, &[0xC8]),             // RET Z
+            (0xC021, &[0xD9]),             // RETI
+        ];
+
+        for (address, bytes) in program {
+            for (offset, byte) in bytes.iter().enumerate() {
+                bus.write_byte_no_tick(address + offset as u16, *byte);
+            }
+        }
+
+        cpu.reg.f.insert(Flags::ZERO);
+
+        step(&mut cpu, &mut bus);
+        step(&mut cpu, &mut bus);
+        step(&mut cpu, &mut bus);
+
+        let frames = cpu.call_stack.iter().copied().collect::<Vec<_>>();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].kind, FrameKind::Restart);
+        assert_eq!((frames[0].target, frames[0].return_address), (0x28, 0xC014));
+        assert_eq!(
+            (frames[1].target, frames[1].return_address),
+            (0xC010, 0xC003)
+        );
+
+        // The restart vector is in ROM, so continue from a copy of it in RAM instead.
+        cpu.reg.pc = 0xC020;
+        cpu.reg.f.remove(Flags::ZERO);
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.call_stack.iter().count(), 2);
+
+        step(&mut cpu, &mut bus);
+        assert_eq!(return_addresses(&cpu.call_stack), [0xC003]);
+    }
+}
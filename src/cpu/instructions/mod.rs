@@ -15,7 +15,7 @@ use log::*;
 use regex::{NoExpand, Regex};
 use smallvec::SmallVec;
 
-use crate::bus::Bus;
+use crate::bus::{Bus, CdlFlags};
 use crate::bytes::WordExt;
 use crate::cpu::{arithmetic, Flags, MCycles, State, TCycles};
 
@@ -71,6 +71,19 @@ pub struct Instruction {
 }
 
 impl Instruction {
+    /// Decodes the instruction at a memory address, using `read_byte` to read each byte.
+    pub fn decode(address: u16, mut read_byte: impl FnMut(u16) -> u8) -> Instruction {
+        let byte = read_byte(address);
+
+        let def = &INSTRUCTIONS[byte as usize];
+
+        let operands = (0..def.num_operands)
+            .map(|i| read_byte(address.wrapping_add(1 + u16::from(i))))
+            .collect();
+
+        Instruction { def, operands }
+    }
+
     /// The number of clock cycles it takes to execute this instruction.
     pub fn cycles(&self) -> TCycles {
         if self.def.byte == 0xCB {
@@ -79,6 +92,81 @@ impl Instruction {
             self.def.cycles
         }
     }
+
+    /// The number of bytes that the instruction occupies in memory.
+    pub fn size(&self) -> u16 {
+        1 + self.operands.len() as u16
+    }
+
+    /// The encoding of the instruction in memory, including the opcode.
+    pub fn bytes(&self) -> SmallVec<[u8; 3]> {
+        let mut bytes = SmallVec::new();
+        bytes.push(self.def.byte);
+        bytes.extend_from_slice(&self.operands);
+        bytes
+    }
+
+    /// Whether the instruction is a `CALL` or `RST`, which pushes a return address.
+    pub fn is_call(&self) -> bool {
+        matches!(
+            self.def.byte,
+            0xC4 | 0xCC
+                | 0xCD
+                | 0xD4
+                | 0xDC
+                | 0xC7
+                | 0xCF
+                | 0xD7
+                | 0xDF
+                | 0xE7
+                | 0xEF
+                | 0xF7
+                | 0xFF
+        )
+    }
+
+    /// Whether the instruction is a `RST`.
+    pub fn is_restart(&self) -> bool {
+        matches!(
+            self.def.byte,
+            0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF
+        )
+    }
+
+    /// Whether the instruction is a `RET` or `RETI`, which pops a return address.
+    pub fn is_return(&self) -> bool {
+        matches!(self.def.byte, 0xC0 | 0xC8 | 0xC9 | 0xD0 | 0xD8 | 0xD9)
+    }
+
+    /// The address that the instruction may transfer control to, if it is located at `address`.
+    ///
+    /// Returns `None` for instructions that are not jumps, calls or restarts, and for jumps whose
+    /// target depends on registers, such as `JP (HL)`.
+    pub fn jump_target(&self, address: u16) -> Option<u16> {
+        match self.def.byte {
+            // JR
+            0x18 | 0x20 | 0x28 | 0x30 | 0x38 => {
+                let offset = self.operands[0] as i8;
+                Some(
+                    address
+                        .wrapping_add(self.size())
+                        .wrapping_add(offset as u16),
+                )
+            }
+
+            // JP, CALL
+            0xC2 | 0xC3 | 0xC4 | 0xCA | 0xCC | 0xCD | 0xD2 | 0xD4 | 0xDA | 0xDC => {
+                Some(LittleEndian::read_u16(&self.operands))
+            }
+
+            // RST
+            0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => {
+                Some(u16::from(self.def.byte & 0x38))
+            }
+
+            _ => None,
+        }
+    }
 }
 
 impl Default for Instruction {
@@ -119,28 +207,22 @@ impl Display for Instruction {
 impl super::Cpu {
     /// Retrieves the current instruction. Does not consume any cycles.
     pub fn current_instruction(&self, bus: &Bus) -> Instruction {
-        let byte = bus.read_byte_no_tick(self.reg.pc);
-
-        let def = &INSTRUCTIONS[byte as usize];
-
-        let operands = (0..def.num_operands)
-            .map(|i| bus.read_byte_no_tick(self.reg.pc + 1 + u16::from(i)))
-            .collect();
-
-        Instruction { def, operands }
+        Instruction::decode(self.reg.pc, |address| bus.read_byte_no_tick(address))
     }
 
     /// Decodes the next instruction.
     pub fn fetch(&self, bus: &mut Bus) -> Instruction {
-        let byte = bus.read_byte(self.reg.pc);
-
-        let def = &INSTRUCTIONS[byte as usize];
+        let pc = self.reg.pc;
 
-        let operands = (0..def.num_operands)
-            .map(|i| bus.read_byte(self.reg.pc + 1 + u16::from(i)))
-            .collect();
+        Instruction::decode(pc, |address| {
+            let flags = if address == pc {
+                CdlFlags::OPCODE
+            } else {
+                CdlFlags::OPERAND
+            };
 
-        Instruction { def, operands }
+            bus.fetch_byte(address, flags)
+        })
     }
 
     /// Executes an instruction.
@@ -1789,6 +1871,31 @@ mod tests {
         assert_eq!(&rl_c.to_string(), "RL C");
     }
 
+    #[test]
+    fn decode() {
+        let program = [0x18, 0xFE, 0xCD, 0x50, 0x01, 0xCB, 0x7C, 0xFF];
+        let read = |address: u16| program[usize::from(address - 0x4000)];
+
+        let jr = Instruction::decode(0x4000, read);
+        assert_eq!(jr.size(), 2);
+        assert_eq!(jr.jump_target(0x4000), Some(0x4000));
+
+        let call = Instruction::decode(0x4002, read);
+        assert_eq!(call.bytes().as_slice(), &[0xCD, 0x50, 0x01]);
+        assert_eq!(call.jump_target(0x4002), Some(0x0150));
+
+        let bit = Instruction::decode(0x4005, read);
+        assert_eq!(bit.to_string(), "BIT 7,H");
+        assert_eq!(bit.size(), 2);
+        assert_eq!(bit.jump_target(0x4005), None);
+
+        let rst = Instruction::decode(0x4007, read);
+        assert_eq!(rst.jump_target(0x4007), Some(0x0038));
+
+        assert!(call.is_call() && rst.is_call() && !jr.is_call());
+        assert!(!call.is_return());
+    }
+
     #[test]
     fn fetch() {
         let mut bus = Bus::default();
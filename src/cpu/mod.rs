@@ -3,6 +3,7 @@
 //! Contains an implementation of the registers and instruction set.
 
 pub mod arithmetic;
+mod call_stack;
 mod instructions;
 mod registers;
 
@@ -13,6 +14,7 @@ use crate::bus::Bus;
 use derive_more::{Add, AddAssign, Sub, SubAssign};
 use log::*;
 
+pub use self::call_stack::{CallStack, Frame, FrameKind};
 pub use self::instructions::Instruction;
 pub use self::registers::{Flags, Registers};
 
@@ -147,6 +149,9 @@ pub struct Cpu {
     /// The state of execution.
     pub state: State,
 
+    /// The functions that have been entered and have not returned.
+    pub call_stack: CallStack,
+
     halt_bug: bool,
 }
 
@@ -160,7 +165,20 @@ impl Cpu {
         match self.state {
             State::Running => {
                 let instruction = self.fetch(bus);
+                let sp = self.reg.sp;
                 self.execute(&instruction, bus);
+
+                // Conditional calls and returns only move the stack pointer if they are taken.
+                if instruction.is_call() && self.reg.sp == sp.wrapping_sub(2) {
+                    let kind = if instruction.is_restart() {
+                        FrameKind::Restart
+                    } else {
+                        FrameKind::Call
+                    };
+                    self.enter_frame(kind, bus);
+                } else if instruction.is_return() && self.reg.sp == sp.wrapping_add(2) {
+                    self.call_stack.pop(self.reg.sp);
+                }
             }
             State::Halted => {
                 // Tick the duration of a NOP.
@@ -190,6 +208,7 @@ impl Cpu {
                         $bus.tick(MCycles(3));
 
                         self.rst($vector, $bus);
+                        self.enter_frame(FrameKind::Interrupt, $bus);
 
                         return;
                     }
@@ -272,6 +291,26 @@ impl Cpu {
         }
 
         self.state = State::Running;
+        self.call_stack.clear();
+    }
+
+    /// Records a new frame on the call stack, after the return address has been pushed and the
+    /// program counter has been set to the start of the function.
+    fn enter_frame(&mut self, kind: FrameKind, bus: &Bus) {
+        let sp = self.reg.sp;
+        let return_address = u16::from_le_bytes([
+            bus.read_byte_no_tick(sp),
+            bus.read_byte_no_tick(sp.wrapping_add(1)),
+        ]);
+
+        self.call_stack.push(Frame {
+            kind,
+            target: self.reg.pc,
+            target_bank: bus.mmu.bank(self.reg.pc),
+            return_address,
+            return_bank: bus.mmu.bank(return_address),
+            sp,
+        });
     }
 }
 
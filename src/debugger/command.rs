@@ -0,0 +1,888 @@
+//! Commands that drive the debugger.
+//!
+//! Frontends, such as the prompt in [`crate::tui`], translate their input into a
+//! [`DebugCommand`] and present the [`DebugResponse`] that executing it returns. Nothing is
+//! printed by the debugger itself, so any frontend can drive it.
+
+use std::fmt::{self, Display};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::audio::Channel;
+use crate::bus::{CodeDataLog, WatchHit, Watchpoint};
+use crate::cpu::{FrameKind, Instruction};
+use crate::Emulator;
+
+use super::{
+    BankedAddress, Breakpoint, Candidate, Disassembly, Expr, HexDump, MemoryRange, Profiler,
+    SearchFilter,
+};
+
+/// The number of bytes that are shown at a time by [`DebugCommand::Dump`].
+const DUMP_PAGE_SIZE: usize = 0x100;
+
+/// The most search candidates that are listed after starting or filtering a search.
+const SEARCH_LIST_LIMIT: usize = 20;
+
+/// A request to inspect or control the emulator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugCommand {
+    /// Execute a number of instructions.
+    Step(u32),
+
+    /// Execute the current instruction. If it is a call, resume until the call returns.
+    StepOver,
+
+    /// Resume until the current function returns.
+    StepOut,
+
+    /// Resume until the program counter reaches a location.
+    RunTo(BankedAddress),
+
+    /// Resume execution.
+    Continue,
+
+    /// Pause execution.
+    Pause,
+
+    /// Insert a breakpoint, replacing any at the same location.
+    AddBreakpoint(Breakpoint),
+
+    /// Skip the next hits of the breakpoint at a location.
+    SetIgnoreCount { location: BankedAddress, count: u32 },
+
+    /// Replace the commands that are executed when the breakpoint at a location pauses
+    /// execution. A command that resumes execution, such as [`DebugCommand::Continue`], should
+    /// be last, since any following commands are executed once execution pauses again.
+    SetBreakpointCommands {
+        location: BankedAddress,
+        commands: Vec<DebugCommand>,
+    },
+
+    /// Insert a watchpoint.
+    AddWatchpoint(Watchpoint),
+
+    /// Remove a watchpoint by its index in the list of watchpoints.
+    RemoveWatchpoint(usize),
+
+    /// List the breakpoints and watchpoints.
+    ListBreakpoints,
+
+    /// Show the instruction at the program counter.
+    CurrentInstruction,
+
+    /// Show the call stack.
+    Backtrace,
+
+    /// Disassemble memory from a location, or from the program counter. Locations with a bank
+    /// are disassembled from that ROM bank, even if it is not mapped.
+    Disassemble {
+        location: Option<BankedAddress>,
+        count: usize,
+    },
+
+    /// Set a register, flag or I/O register to the value of an expression.
+    SetRegister { name: String, value: Expr },
+
+    /// Write the value of an expression to memory, as a byte or a word. Writes to ROM are
+    /// patched.
+    Poke {
+        location: BankedAddress,
+        value: Expr,
+        word: bool,
+    },
+
+    /// Write the value of an expression to every byte from a location to an address, inclusive.
+    Fill {
+        start: BankedAddress,
+        end: u16,
+        value: Expr,
+    },
+
+    /// Remove all patches to the ROM.
+    Unpatch,
+
+    /// Show a page of memory from a range, which may be in a bank that is not mapped. Without a
+    /// range, continues from the end of the last page, or from the start of memory.
+    Dump(Option<MemoryRange>),
+
+    /// Write the contents of a range of memory to a file.
+    SaveMemory { range: MemoryRange, path: PathBuf },
+
+    /// Write the contents of a file to memory, from the start of a range. The file must fit in
+    /// the range. Writes to ROM are patched.
+    LoadMemory { range: MemoryRange, path: PathBuf },
+
+    /// Show the state of the CPU and the mapped banks.
+    CpuState,
+
+    /// Show the values of expressions, such as registers or memory.
+    Print(Vec<Expr>),
+
+    /// Start a search of RAM, with every byte as a candidate.
+    StartSearch,
+
+    /// Keep the search candidates whose values match a filter.
+    FilterSearch(SearchFilter),
+
+    /// Show the current values of the first search candidates.
+    ListSearch { count: usize },
+
+    /// Show the addresses where the most time is spent.
+    Profile { count: usize },
+
+    /// Forget the execution profile.
+    ResetProfile,
+
+    /// Write the full execution profile to a file.
+    WriteProfile(PathBuf),
+
+    /// Write the execution profile to a file as folded stacks.
+    WriteFoldedProfile(PathBuf),
+
+    /// Show how much of each ROM bank has been executed or read.
+    CodeDataReport,
+
+    /// Save the code/data log.
+    SaveCodeDataLog,
+
+    /// Toggle whether a sound channel is muted.
+    Mute(Channel),
+
+    /// Toggle whether a sound channel is soloed.
+    Solo(Channel),
+
+    /// Show the output and state of each sound channel.
+    Channels,
+
+    /// Execute a list of commands, such as a script, before any further commands from the
+    /// frontend.
+    Exec(Vec<DebugCommand>),
+
+    /// Stop the emulator.
+    Quit,
+}
+
+/// A frame of a backtrace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BacktraceFrame {
+    /// Where execution is, or where it returns to, described with its symbol.
+    pub location: String,
+
+    /// How the function that returns to `location` was entered, and its description. `None` for
+    /// the innermost frame.
+    pub entry: Option<(FrameKind, String)>,
+}
+
+/// The state of a sound channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelStatus {
+    pub channel: Channel,
+
+    /// The current output level.
+    pub output: u8,
+
+    pub muted: bool,
+    pub soloed: bool,
+
+    /// Whether the channel is heard, after muting and soloing.
+    pub audible: bool,
+}
+
+/// The result of a [`DebugCommand`], or a notification that execution paused.
+///
+/// Responses are displayed as the debugger prompt shows them.
+#[derive(Debug, Clone)]
+pub enum DebugResponse {
+    /// The command completed, and has nothing to show.
+    Done,
+
+    /// A report to show as-is.
+    Text(String),
+
+    /// The breakpoints, with their symbols, and the watchpoints.
+    Breakpoints {
+        breakpoints: Vec<(Breakpoint, Option<String>)>,
+        watchpoints: Vec<Watchpoint>,
+    },
+
+    /// An instruction and its location, described with its symbol.
+    Instruction {
+        location: String,
+        instruction: Instruction,
+    },
+
+    /// The call stack, innermost first.
+    Backtrace(Vec<BacktraceFrame>),
+
+    /// Disassembled instructions.
+    Disassembly(Vec<Disassembly>),
+
+    /// A page of memory, and the number of bytes that are left in the range that was dumped.
+    Memory { dump: HexDump, remaining: usize },
+
+    /// The number of search candidates that are left, and some of the candidates, with their
+    /// symbols.
+    Search {
+        total: usize,
+        candidates: Vec<(Candidate, Option<String>)>,
+    },
+
+    /// The number of ROM bytes that were restored by removing patches.
+    Unpatched(usize),
+
+    /// The state of each sound channel.
+    Channels(Vec<ChannelStatus>),
+
+    /// Execution paused because of a watchpoint, after the instruction at `location`.
+    WatchHit {
+        hit: WatchHit,
+        location: String,
+        instruction: Instruction,
+    },
+}
+
+impl Display for DebugResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebugResponse::Done => Ok(()),
+            DebugResponse::Text(text) => write!(f, "{}", text.trim_end()),
+            DebugResponse::Breakpoints {
+                breakpoints,
+                watchpoints,
+            } => {
+                if breakpoints.is_empty() {
+                    write!(f, "no breakpoints")?;
+                } else {
+                    write!(f, "breakpoints:")?;
+                    for (breakpoint, symbol) in breakpoints {
+                        match symbol {
+                            Some(name) => write!(f, "\n{} <{}>", breakpoint, name)?,
+                            None => write!(f, "\n{}", breakpoint)?,
+                        }
+                    }
+                }
+
+                if !watchpoints.is_empty() {
+                    write!(f, "\nwatchpoints:")?;
+                    for (index, watchpoint) in watchpoints.iter().enumerate() {
+                        write!(f, "\n{}: {}", index, watchpoint)?;
+                    }
+                }
+
+                Ok(())
+            }
+            DebugResponse::Instruction {
+                location,
+                instruction,
+            } => write!(f, "{}: {}", location, instruction),
+            DebugResponse::Backtrace(frames) => {
+                for (depth, frame) in frames.iter().enumerate() {
+                    if depth > 0 {
+                        writeln!(f)?;
+                    }
+
+                    match &frame.entry {
+                        Some((kind, target)) => write!(
+                            f,
+                            "#{:<2} {}  ({} to {})",
+                            depth, frame.location, kind, target
+                        )?,
+                        None => write!(f, "#{:<2} {}", depth, frame.location)?,
+                    }
+                }
+
+                Ok(())
+            }
+            DebugResponse::Disassembly(lines) => {
+                for (i, line) in lines.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+
+                    write!(f, "{}", line)?;
+                }
+
+                Ok(())
+            }
+            DebugResponse::Memory { dump, remaining } => {
+                write!(f, "{}", dump)?;
+
+                if *remaining > 0 {
+                    write!(f, "\n({} more bytes, enter `d` to continue)", remaining)?;
+                }
+
+                Ok(())
+            }
+            DebugResponse::Search { total, candidates } => {
+                match total {
+                    1 => write!(f, "1 candidate")?,
+                    total => write!(f, "{} candidates", total)?,
+                }
+
+                for (candidate, symbol) in candidates {
+                    write!(f, "\n{}", candidate.location)?;
+                    if let Some(name) = symbol {
+                        write!(f, " <{}>", name)?;
+                    }
+                    write!(f, ": {:#04x} ({})", candidate.value, candidate.value)?;
+                }
+
+                Ok(())
+            }
+            DebugResponse::Unpatched(patches) => {
+                write!(f, "restored {} patched ROM bytes", patches)
+            }
+            DebugResponse::Channels(channels) => {
+                for (i, status) in channels.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+
+                    // A muted channel is silent even if it is soloed.
+                    let state = if status.muted {
+                        "muted"
+                    } else if status.soloed {
+                        "soloed"
+                    } else if !status.audible {
+                        "silent"
+                    } else {
+                        ""
+                    };
+
+                    write!(f, "{:<8} {:>2} {}", status.channel, status.output, state)?;
+                }
+
+                Ok(())
+            }
+            DebugResponse::WatchHit {
+                hit,
+                location,
+                instruction,
+            } => write!(
+                f,
+                "watchpoint {}: {}\n  at {}: {}",
+                hit.watchpoint, hit, location, instruction
+            ),
+        }
+    }
+}
+
+/// A user interface that drives the debugger.
+///
+/// The frontend is polled by [`Emulator::update`]: repeatedly while execution is paused, and once
+/// per update while it is running. Polling must not block, so that the window stays responsive.
+/// Frontends that read input from a blocking source should do so on another thread.
+pub trait DebugFrontend {
+    /// Returns the next command to execute, if one is ready.
+    ///
+    /// The state of the emulator may be inspected, such as to display it or to resolve symbols in
+    /// the command.
+    fn poll_command(&mut self, emulator: &Emulator) -> Option<DebugCommand>;
+
+    /// Presents the result of the last command, or the reason that execution paused.
+    fn respond(&mut self, response: Result<DebugResponse>);
+}
+
+/// Executes a command against the emulator.
+pub(crate) fn execute(emulator: &mut Emulator, command: DebugCommand) -> Result<DebugResponse> {
+    match command {
+        DebugCommand::Step(count) => {
+            for _ in 0..count {
+                emulator.step();
+            }
+        }
+        DebugCommand::StepOver => emulator.step_over(),
+        DebugCommand::StepOut => emulator.step_out(),
+        DebugCommand::RunTo(location) => emulator.run_to(location),
+        DebugCommand::Continue => emulator.resume(),
+        DebugCommand::Pause => emulator.pause(),
+        DebugCommand::AddBreakpoint(breakpoint) => emulator.add_breakpoint(breakpoint),
+        DebugCommand::SetIgnoreCount { location, count } => {
+            if !emulator.set_ignore_count(location, count) {
+                bail!("no breakpoint at {}", location);
+            }
+        }
+        DebugCommand::SetBreakpointCommands { location, commands } => {
+            if !emulator.set_breakpoint_commands(location, commands) {
+                bail!("no breakpoint at {}", location);
+            }
+        }
+        DebugCommand::AddWatchpoint(watchpoint) => emulator.add_watchpoint(watchpoint),
+        DebugCommand::RemoveWatchpoint(index) => {
+            if emulator.remove_watchpoint(index).is_none() {
+                bail!("no watchpoint {}", index);
+            }
+        }
+        DebugCommand::ListBreakpoints => {
+            let breakpoints = emulator
+                .breakpoints()
+                .into_iter()
+                .map(|breakpoint| {
+                    let symbol = emulator.symbols().symbolize(breakpoint.location());
+                    (breakpoint, symbol)
+                })
+                .collect();
+
+            return Ok(DebugResponse::Breakpoints {
+                breakpoints,
+                watchpoints: emulator.watchpoints(),
+            });
+        }
+        DebugCommand::CurrentInstruction => {
+            let (address, instruction) = emulator.current_instruction();
+            let address = BankedAddress::resolve(address, &emulator.bus.mmu);
+
+            return Ok(DebugResponse::Instruction {
+                location: emulator.symbols().describe(address),
+                instruction,
+            });
+        }
+        DebugCommand::Backtrace => return Ok(DebugResponse::Backtrace(backtrace(emulator))),
+        DebugCommand::Disassemble { location, count } => {
+            let location = location.unwrap_or_else(|| BankedAddress::new(emulator.cpu.reg.pc));
+
+            let lines = match location.bank {
+                Some(bank) => emulator.disassemble_rom(bank, location.address, count)?,
+                None => emulator.disassemble(location.address, count),
+            };
+
+            return Ok(DebugResponse::Disassembly(lines));
+        }
+        DebugCommand::SetRegister { name, value } => {
+            let value = value.evaluate(&emulator.cpu, &emulator.bus);
+            emulator.set_register(&name, value)?;
+        }
+        DebugCommand::Poke {
+            location,
+            value,
+            word,
+        } => {
+            let value = value.evaluate(&emulator.cpu, &emulator.bus);
+
+            if word {
+                emulator.poke_word(location, value as u16)?;
+            } else {
+                emulator.poke(location, value as u8)?;
+            }
+        }
+        DebugCommand::Fill { start, end, value } => {
+            if start.address > end {
+                bail!("range start must not be after its end");
+            }
+
+            let value = value.evaluate(&emulator.cpu, &emulator.bus);
+            emulator.fill(start, end, value as u8)?;
+        }
+        DebugCommand::Unpatch => {
+            let patches = emulator.bus.mmu.clear_rom_patches();
+            return Ok(DebugResponse::Unpatched(patches));
+        }
+        DebugCommand::Dump(range) => {
+            let range = range
+                .or_else(|| emulator.debug.as_mut()?.dump_next.take())
+                .unwrap_or(MemoryRange::ALL);
+
+            let page = range.start.address as usize + DUMP_PAGE_SIZE - 1;
+            let page = MemoryRange::new(range.start, page.min(range.end.into()) as u16);
+            let bytes = emulator.read_memory(page)?;
+
+            let rest = (page.end < range.end).then(|| {
+                let start = BankedAddress {
+                    address: page.end + 1,
+                    ..range.start
+                };
+                MemoryRange::new(start, range.end)
+            });
+            if let Some(debugger) = &mut emulator.debug {
+                debugger.dump_next = rest;
+            }
+
+            return Ok(DebugResponse::Memory {
+                dump: HexDump {
+                    start: range.start,
+                    bytes,
+                },
+                remaining: rest.map_or(0, |rest| rest.size()),
+            });
+        }
+        DebugCommand::SaveMemory { range, path } => {
+            let bytes = emulator.read_memory(range)?;
+            fs::write(path, bytes).context("could not write memory")?;
+        }
+        DebugCommand::LoadMemory { range, path } => {
+            let bytes = fs::read(path).context("could not read file")?;
+            if range.start.address > range.end {
+                bail!("range start must not be after its end");
+            } else if bytes.len() > range.size() {
+                bail!(
+                    "the file is {} bytes, but {} only has {} bytes",
+                    bytes.len(),
+                    range,
+                    range.size()
+                );
+            }
+
+            emulator.write_memory(range.start, &bytes)?;
+        }
+        DebugCommand::CpuState => {
+            let mmu = &emulator.bus.mmu;
+            let banks = match mmu.ram_bank() {
+                Some(ram_bank) => format!(
+                    "rom bank: {:#04x}  ram bank: {:#04x}",
+                    mmu.rom_bank(),
+                    ram_bank
+                ),
+                None => format!("rom bank: {:#04x}", mmu.rom_bank()),
+            };
+
+            return Ok(DebugResponse::Text(format!("{}\n{}", emulator.cpu, banks)));
+        }
+        DebugCommand::Print(expressions) => {
+            let values = expressions
+                .iter()
+                .map(|expression| {
+                    let value = expression.evaluate(&emulator.cpu, &emulator.bus);
+                    format!("{} = {:#04x} ({})", expression, value, value)
+                })
+                .collect::<Vec<_>>();
+
+            return Ok(DebugResponse::Text(values.join("\n")));
+        }
+        DebugCommand::Profile { count } => {
+            let mut report = vec![];
+            profiler(emulator)?.write_report(&mut report, emulator.symbols(), count)?;
+            return Ok(DebugResponse::Text(String::from_utf8(report)?));
+        }
+        DebugCommand::ResetProfile => {
+            profiler(emulator)?;
+            emulator.reset_profiler();
+        }
+        DebugCommand::WriteProfile(path) => {
+            let file = File::create(path).context("could not create report")?;
+            profiler(emulator)?.write_report(
+                BufWriter::new(file),
+                emulator.symbols(),
+                usize::MAX,
+            )?;
+        }
+        DebugCommand::WriteFoldedProfile(path) => {
+            let file = File::create(path).context("could not create folded stacks")?;
+            profiler(emulator)?.write_folded(BufWriter::new(file), emulator.symbols())?;
+        }
+        DebugCommand::CodeDataReport => {
+            let mut report = vec![];
+            code_data_log(emulator)?.write_report(&mut report)?;
+            return Ok(DebugResponse::Text(String::from_utf8(report)?));
+        }
+        DebugCommand::SaveCodeDataLog => {
+            code_data_log(emulator)?;
+            emulator.save_code_data_log()?;
+        }
+        DebugCommand::Mute(channel) => {
+            let audio = &mut emulator.bus.audio;
+            audio.set_muted(channel, !audio.is_muted(channel));
+        }
+        DebugCommand::Solo(channel) => {
+            let audio = &mut emulator.bus.audio;
+            audio.set_soloed(channel, !audio.is_soloed(channel));
+        }
+        DebugCommand::Channels => {
+            let audio = &emulator.bus.audio;
+            let channels = Channel::ALL
+                .iter()
+                .zip(audio.channel_outputs().iter())
+                .map(|(&channel, &output)| ChannelStatus {
+                    channel,
+                    output,
+                    muted: audio.is_muted(channel),
+                    soloed: audio.is_soloed(channel),
+                    audible: audio.is_audible(channel),
+                })
+                .collect();
+
+            return Ok(DebugResponse::Channels(channels));
+        }
+        DebugCommand::StartSearch => {
+            let total = emulator.start_search();
+            return Ok(search_results(emulator, total, SEARCH_LIST_LIMIT));
+        }
+        DebugCommand::FilterSearch(filter) => {
+            let total = emulator.filter_search(filter)?;
+            return Ok(search_results(emulator, total, SEARCH_LIST_LIMIT));
+        }
+        DebugCommand::ListSearch { count } => {
+            let search = emulator
+                .search()
+                .ok_or_else(|| anyhow!("no search is in progress"))?;
+
+            let candidates = search
+                .candidates()
+                .iter()
+                .take(count)
+                .map(|candidate| {
+                    let location = candidate.location;
+                    let current = Candidate {
+                        location,
+                        value: emulator.peek(location).unwrap_or(candidate.value),
+                    };
+                    (current, emulator.symbols().symbolize(location))
+                })
+                .collect();
+
+            return Ok(DebugResponse::Search {
+                total: search.candidates().len(),
+                candidates,
+            });
+        }
+        DebugCommand::Exec(commands) => {
+            if let Some(debugger) = &mut emulator.debug {
+                for command in commands.into_iter().rev() {
+                    debugger.queued.push_front(command);
+                }
+            }
+        }
+        DebugCommand::Quit => {
+            if let Some(debugger) = &mut emulator.debug {
+                debugger.quit = true;
+            }
+        }
+    }
+
+    Ok(DebugResponse::Done)
+}
+
+fn backtrace(emulator: &Emulator) -> Vec<BacktraceFrame> {
+    let symbols = emulator.symbols();
+    let (pc, _) = emulator.current_instruction();
+    let location = BankedAddress::resolve(pc, &emulator.bus.mmu);
+
+    let innermost = BacktraceFrame {
+        location: symbols.describe(location),
+        entry: None,
+    };
+
+    let callers = emulator.cpu.call_stack.iter().map(|frame| {
+        let return_location = BankedAddress {
+            bank: frame.return_bank,
+            address: frame.return_address,
+        };
+        let target = BankedAddress {
+            bank: frame.target_bank,
+            address: frame.target,
+        };
+
+        BacktraceFrame {
+            location: symbols.describe(return_location),
+            entry: Some((frame.kind, symbols.describe(target))),
+        }
+    });
+
+    std::iter::once(innermost).chain(callers).collect()
+}
+
+/// The number of search candidates, and the candidates if there are at most `limit`.
+fn search_results(emulator: &Emulator, total: usize, limit: usize) -> DebugResponse {
+    let candidates = match emulator.search() {
+        Some(search) if total <= limit => search
+            .candidates()
+            .iter()
+            .map(|&candidate| (candidate, emulator.symbols().symbolize(candidate.location)))
+            .collect(),
+        _ => vec![],
+    };
+
+    DebugResponse::Search { total, candidates }
+}
+
+fn profiler(emulator: &Emulator) -> Result<&Profiler> {
+    emulator
+        .profiler()
+        .ok_or_else(|| anyhow!("profiling is not enabled"))
+}
+
+fn code_data_log(emulator: &Emulator) -> Result<&CodeDataLog> {
+    emulator
+        .code_data_log()
+        .ok_or_else(|| anyhow!("code/data logging is not enabled"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use anyhow::Result;
+
+    use crate::audio::Channel;
+    use crate::debugger::{BankedAddress, Breakpoint, MemoryRange};
+    use crate::Emulator;
+
+    use super::{DebugCommand, DebugFrontend, DebugResponse};
+
+    /// A frontend that executes a fixed list of commands and records the responses.
+    struct Script {
+        commands: VecDeque<DebugCommand>,
+        responses: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl DebugFrontend for Script {
+        fn poll_command(&mut self, _: &Emulator) -> Option<DebugCommand> {
+            self.commands.pop_front()
+        }
+
+        fn respond(&mut self, response: Result<DebugResponse>) {
+            let response = match response {
+                Ok(response) => response.to_string(),
+                Err(e) => format!("error: {}", e),
+            };
+
+            self.responses.borrow_mut().push(response);
+        }
+    }
+
+    #[test]
+    fn drive_frontend() {
+        let responses = Rc::default();
+        let script = Script {
+            commands: VecDeque::from(vec![
+                DebugCommand::AddBreakpoint(Breakpoint::new(0xC002)),
+                DebugCommand::RemoveWatchpoint(0),
+                DebugCommand::ListBreakpoints,
+                DebugCommand::Continue,
+            ]),
+            responses: Rc::clone(&responses),
+        };
+
+        let mut emulator = Emulator::builder().with_debug_frontend(script).build();
+        emulator.cpu.reg.pc = 0xC000;
+        for address in 0xC000..0xC004 {
+            emulator.bus.write_byte_no_tick(address, 0x00); // NOP
+        }
+
+        // Returns once the script runs out, instead of waiting for more commands.
+        emulator.update(Duration::from_millis(1)).unwrap();
+        assert!(emulator.is_paused());
+        assert_eq!(emulator.cpu.reg.pc, 0xC002);
+
+        assert_eq!(
+            *responses.borrow(),
+            [
+                "",
+                "error: no watchpoint 0",
+                "breakpoints:\n0xc002 (hits: 0)",
+                ""
+            ]
+        );
+
+        match emulator.execute(DebugCommand::CurrentInstruction).unwrap() {
+            DebugResponse::Instruction { location, .. } => {
+                assert_eq!(location, BankedAddress::new(0xC002).to_string())
+            }
+            response => panic!("unexpected response: {:?}", response),
+        }
+
+        assert!(!emulator.quit_requested());
+        emulator.execute(DebugCommand::Quit).unwrap();
+        assert!(emulator.quit_requested());
+    }
+
+    #[test]
+    fn dump_pages() {
+        let mut emulator = Emulator::builder().with_debug().build();
+        let range = MemoryRange::new(BankedAddress::new(0xC000), 0xC17F);
+
+        let pages = [Some(range), None, None]
+            .into_iter()
+            .map(
+                |range| match emulator.execute(DebugCommand::Dump(range)).unwrap() {
+                    DebugResponse::Memory { dump, remaining } => {
+                        (dump.start.address, dump.bytes.len(), remaining)
+                    }
+                    response => panic!("unexpected response: {:?}", response),
+                },
+            )
+            .collect::<Vec<_>>();
+
+        // Once the range has been dumped, the next dump starts from the start of memory.
+        assert_eq!(
+            pages,
+            [
+                (0xC000, 0x100, 0x80),
+                (0xC100, 0x80, 0),
+                (0x0000, 0x100, 0xFF00)
+            ]
+        );
+    }
+
+    #[test]
+    fn breakpoint_commands() {
+        let responses = Rc::default();
+        let script = Script {
+            commands: VecDeque::from(vec![DebugCommand::Exec(vec![
+                DebugCommand::AddBreakpoint(Breakpoint::new(0xC002)),
+                DebugCommand::SetBreakpointCommands {
+                    location: BankedAddress::new(0xC002),
+                    commands: vec![
+                        DebugCommand::Print(vec!["pc".parse().unwrap(), "a".parse().unwrap()]),
+                        DebugCommand::Continue,
+                    ],
+                },
+                DebugCommand::AddBreakpoint(Breakpoint::new(0xC004)),
+                DebugCommand::Continue,
+            ])]),
+            responses: Rc::clone(&responses),
+        };
+
+        let mut emulator = Emulator::builder().with_debug_frontend(script).build();
+        emulator.cpu.reg.pc = 0xC000;
+        emulator.cpu.reg.a = 0x3C;
+        for address in 0xC000..0xC008 {
+            emulator.bus.write_byte_no_tick(address, 0x00); // NOP
+        }
+
+        emulator.update(Duration::from_millis(1)).unwrap();
+        assert!(emulator.is_paused());
+        assert_eq!(emulator.cpu.reg.pc, 0xC004);
+
+        assert_eq!(
+            responses.borrow()[5..],
+            ["pc = 0xc002 (49154)\na = 0x3c (60)", ""]
+        );
+        assert_eq!(
+            emulator.breakpoints()[0].to_string(),
+            "0xc002 (hits: 1, 2 commands)"
+        );
+
+        assert!(emulator
+            .execute(DebugCommand::SetBreakpointCommands {
+                location: BankedAddress::new(0xC003),
+                commands: vec![],
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn channels() {
+        let mut emulator = Emulator::builder().with_debug().build();
+
+        for command in [
+            DebugCommand::Mute(Channel::Square1),
+            DebugCommand::Solo(Channel::Square1),
+            DebugCommand::Solo(Channel::Wave),
+        ] {
+            emulator.execute(command).unwrap();
+        }
+
+        let channels = emulator.execute(DebugCommand::Channels).unwrap();
+        assert_eq!(
+            channels.to_string(),
+            "square1   0 muted\nsquare2   0 silent\nwave      0 soloed\nnoise     0 silent"
+        );
+    }
+}
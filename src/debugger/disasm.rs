@@ -0,0 +1,137 @@
+//! Disassembly of memory and ROM.
+
+use std::fmt::{self, Display};
+
+use itertools::Itertools;
+
+use crate::cpu::Instruction;
+
+use super::{BankedAddress, SymbolTable};
+
+/// A decoded instruction and its location.
+#[derive(Debug, Clone)]
+pub struct Disassembly {
+    /// The location of the first byte of the instruction.
+    pub location: BankedAddress,
+
+    /// The decoded instruction.
+    pub instruction: Instruction,
+
+    /// The label at the location of the instruction, if any.
+    pub label: Option<String>,
+
+    /// The symbolic name of the jump target, if any.
+    pub target_symbol: Option<String>,
+}
+
+impl Disassembly {
+    /// The address that the instruction may transfer control to, if any.
+    pub fn jump_target(&self) -> Option<u16> {
+        self.instruction.jump_target(self.location.address)
+    }
+}
+
+impl Display for Disassembly {
+    /// Formats the instruction as a line of a listing: its location, its encoding, the assembly,
+    /// and its jump target, if any. Labels are printed on their own line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(label) = &self.label {
+            writeln!(f, "{}:", label)?;
+        }
+
+        let bytes = self
+            .instruction
+            .bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .join(" ");
+
+        let line = format!(
+            "{:<13} {:<8}  {}",
+            self.location.to_string(),
+            bytes,
+            self.instruction
+        );
+
+        match (self.jump_target(), &self.target_symbol) {
+            (Some(target), Some(symbol)) => {
+                write!(f, "{:<40} ; -> {:#06x} <{}>", line, target, symbol)
+            }
+            (Some(target), None) => write!(f, "{:<40} ; -> {:#06x}", line, target),
+            (None, _) => write!(f, "{}", line.trim_end()),
+        }
+    }
+}
+
+/// Decodes `count` consecutive instructions starting at `start`.
+///
+/// Bytes are read with `read_byte`, and each address is qualified with the bank returned by
+/// `bank`. Locations and jump targets are named using `symbols`.
+pub fn disassemble(
+    start: u16,
+    count: usize,
+    mut read_byte: impl FnMut(u16) -> u8,
+    bank: impl Fn(u16) -> Option<u16>,
+    symbols: &SymbolTable,
+) -> Vec<Disassembly> {
+    let mut address = start;
+
+    (0..count)
+        .map(|_| {
+            let instruction = Instruction::decode(address, &mut read_byte);
+            let location = BankedAddress {
+                bank: bank(address),
+                address,
+            };
+
+            let target_symbol = instruction.jump_target(address).and_then(|target| {
+                symbols.symbolize(BankedAddress {
+                    bank: bank(target),
+                    address: target,
+                })
+            });
+
+            address = address.wrapping_add(instruction.size());
+
+            Disassembly {
+                location,
+                label: symbols.label(location).map(str::to_owned),
+                target_symbol,
+                instruction,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debugger::SymbolTable;
+
+    use super::disassemble;
+
+    #[test]
+    fn listing() {
+        let program = [0x00, 0x3E, 0x3C, 0xC3, 0x50, 0x01, 0x20, 0xFA];
+        let lines = disassemble(
+            0x4000,
+            4,
+            |address| program[usize::from(address - 0x4000)],
+            |_| Some(2),
+            &SymbolTable::parse("02:4000 Main\n02:4002 Main.loop"),
+        );
+
+        let lines = lines
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "Main:\n0x02:0x4000   00        NOP",
+                "0x02:0x4001   3e 3c     LD A,$0x3c",
+                "0x02:0x4003   c3 50 01  JP $0x0150       ; -> 0x0150",
+                "0x02:0x4006   20 fa     JR NZ,$0xfa      ; -> 0x4002 <Main.loop>",
+            ]
+        );
+    }
+}
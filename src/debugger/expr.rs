@@ -0,0 +1,634 @@
+//! Expressions that are evaluated against the state of the emulator.
+//!
+//! The syntax is similar to C. Operands may be numbers (`144`, `0x3c` or `$3c`), CPU registers and
+//! register pairs (`a`, `hl`, `sp`), flags (`zf`, `nf`, `hf`, `cf`), I/O registers by name (`ly`,
+//! `lcdc`) or memory, by surrounding an address with brackets (`[hl]`, `[0xc000]`).
+//!
+//! Comparisons and logical operators evaluate to 1 if they are true and 0 otherwise. An expression
+//! is considered true if it is not 0.
+
+use std::fmt::{self, Display};
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+
+use thiserror::Error;
+
+use crate::bus::Bus;
+use crate::cpu::{Cpu, Flags};
+
+/// I/O registers that may be referred to by name.
+const IO_REGISTERS: &[(&str, u16)] = &[
+    ("p1", 0xFF00),
+    ("joyp", 0xFF00),
+    ("sb", 0xFF01),
+    ("sc", 0xFF02),
+    ("div", 0xFF04),
+    ("tima", 0xFF05),
+    ("tma", 0xFF06),
+    ("tac", 0xFF07),
+    ("if", 0xFF0F),
+    ("nr50", 0xFF24),
+    ("nr51", 0xFF25),
+    ("nr52", 0xFF26),
+    ("lcdc", 0xFF40),
+    ("stat", 0xFF41),
+    ("scy", 0xFF42),
+    ("scx", 0xFF43),
+    ("ly", 0xFF44),
+    ("lyc", 0xFF45),
+    ("dma", 0xFF46),
+    ("bgp", 0xFF47),
+    ("obp0", 0xFF48),
+    ("obp1", 0xFF49),
+    ("wy", 0xFF4A),
+    ("wx", 0xFF4B),
+    ("ie", 0xFFFF),
+];
+
+/// An error encountered while parsing an expression.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseExprError {
+    #[error("unexpected character '{0}'")]
+    UnexpectedCharacter(char),
+
+    #[error("invalid number '{0}'")]
+    InvalidNumber(String),
+
+    #[error("unknown variable '{0}'")]
+    UnknownVariable(String),
+
+    #[error("unexpected '{0}'")]
+    UnexpectedToken(String),
+
+    #[error("unexpected end of expression")]
+    UnexpectedEnd,
+}
+
+/// A parsed expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expr {
+    /// The text that the expression was parsed from.
+    source: String,
+
+    node: Node,
+}
+
+impl Expr {
+    /// Evaluates the expression. Memory is read without ticking any components.
+    pub fn evaluate(&self, cpu: &Cpu, bus: &Bus) -> i64 {
+        self.node.evaluate(cpu, bus)
+    }
+
+    /// Evaluates the expression as a condition.
+    pub fn is_true(&self, cpu: &Cpu, bus: &Bus) -> bool {
+        self.evaluate(cpu, bus) != 0
+    }
+}
+
+/// Sets a CPU register, register pair, flag or I/O register by name, using the same names as
+/// expressions.
+///
+/// The value is truncated to the width of the register. Flags are set if the value is not 0.
+pub fn assign(name: &str, value: i64, cpu: &mut Cpu, bus: &mut Bus) -> Result<(), ParseExprError> {
+    let reg = &mut cpu.reg;
+
+    match name.parse()? {
+        Variable::Register(register) => match register {
+            Register::A => reg.a = value as u8,
+            Register::F => reg.f = Flags::from_bits_truncate(value as u8),
+            Register::B => reg.b = value as u8,
+            Register::C => reg.c = value as u8,
+            Register::D => reg.d = value as u8,
+            Register::E => reg.e = value as u8,
+            Register::H => reg.h = value as u8,
+            Register::L => reg.l = value as u8,
+            Register::Af => {
+                reg.a = (value >> 8) as u8;
+                reg.f = Flags::from_bits_truncate(value as u8);
+            }
+            Register::Bc => reg.bc_mut().write(value as u16),
+            Register::De => reg.de_mut().write(value as u16),
+            Register::Hl => reg.hl_mut().write(value as u16),
+            Register::Sp => reg.sp = value as u16,
+            Register::Pc => reg.pc = value as u16,
+        },
+        Variable::Flag(flag) => reg.f.set(flag, value != 0),
+        Variable::Io(address) => bus.write_byte_no_tick(address, value as u8),
+    }
+
+    Ok(())
+}
+
+impl FromStr for Expr {
+    type Err = ParseExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser {
+            tokens: tokens.into_iter().peekable(),
+        };
+
+        let node = parser.expression(0)?;
+
+        if let Some(token) = parser.tokens.next() {
+            return Err(ParseExprError::UnexpectedToken(token.to_string()));
+        }
+
+        Ok(Expr {
+            source: s.trim().to_owned(),
+            node,
+        })
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    Af,
+    Bc,
+    De,
+    Hl,
+    Sp,
+    Pc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variable {
+    Register(Register),
+    Flag(Flags),
+    Io(u16),
+}
+
+impl FromStr for Variable {
+    type Err = ParseExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_ascii_lowercase();
+
+        let register = match name.as_str() {
+            "a" => Register::A,
+            "f" => Register::F,
+            "b" => Register::B,
+            "c" => Register::C,
+            "d" => Register::D,
+            "e" => Register::E,
+            "h" => Register::H,
+            "l" => Register::L,
+            "af" => Register::Af,
+            "bc" => Register::Bc,
+            "de" => Register::De,
+            "hl" => Register::Hl,
+            "sp" => Register::Sp,
+            "pc" => Register::Pc,
+            "zf" => return Ok(Variable::Flag(Flags::ZERO)),
+            "nf" => return Ok(Variable::Flag(Flags::SUBTRACT)),
+            "hf" => return Ok(Variable::Flag(Flags::HALF_CARRY)),
+            "cf" => return Ok(Variable::Flag(Flags::CARRY)),
+            _ => {
+                return IO_REGISTERS
+                    .iter()
+                    .find(|(io_name, _)| *io_name == name)
+                    .map(|&(_, address)| Variable::Io(address))
+                    .ok_or_else(|| ParseExprError::UnknownVariable(s.to_owned()))
+            }
+        };
+
+        Ok(Variable::Register(register))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnaryOp {
+    Negate,
+    Not,
+    Complement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Subtract,
+    Multiply,
+}
+
+impl BinaryOp {
+    fn from_token(token: &Token) -> Option<BinaryOp> {
+        let op = match token {
+            Token::Operator(op) => op,
+            _ => return None,
+        };
+
+        let op = match *op {
+            "||" => BinaryOp::Or,
+            "&&" => BinaryOp::And,
+            "|" => BinaryOp::BitOr,
+            "^" => BinaryOp::BitXor,
+            "&" => BinaryOp::BitAnd,
+            "==" => BinaryOp::Equal,
+            "!=" => BinaryOp::NotEqual,
+            "<" => BinaryOp::Less,
+            "<=" => BinaryOp::LessEqual,
+            ">" => BinaryOp::Greater,
+            ">=" => BinaryOp::GreaterEqual,
+            "<<" => BinaryOp::ShiftLeft,
+            ">>" => BinaryOp::ShiftRight,
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Subtract,
+            "*" => BinaryOp::Multiply,
+            _ => return None,
+        };
+
+        Some(op)
+    }
+
+    /// How tightly the operator binds to its operands. Operators with higher precedence are
+    /// evaluated first.
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::BitOr => 3,
+            BinaryOp::BitXor => 4,
+            BinaryOp::BitAnd => 5,
+            BinaryOp::Equal | BinaryOp::NotEqual => 6,
+            BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => 7,
+            BinaryOp::ShiftLeft | BinaryOp::ShiftRight => 8,
+            BinaryOp::Add | BinaryOp::Subtract => 9,
+            BinaryOp::Multiply => 10,
+        }
+    }
+
+    fn apply(self, lhs: i64, rhs: i64) -> i64 {
+        match self {
+            BinaryOp::Or => i64::from(lhs != 0 || rhs != 0),
+            BinaryOp::And => i64::from(lhs != 0 && rhs != 0),
+            BinaryOp::BitOr => lhs | rhs,
+            BinaryOp::BitXor => lhs ^ rhs,
+            BinaryOp::BitAnd => lhs & rhs,
+            BinaryOp::Equal => i64::from(lhs == rhs),
+            BinaryOp::NotEqual => i64::from(lhs != rhs),
+            BinaryOp::Less => i64::from(lhs < rhs),
+            BinaryOp::LessEqual => i64::from(lhs <= rhs),
+            BinaryOp::Greater => i64::from(lhs > rhs),
+            BinaryOp::GreaterEqual => i64::from(lhs >= rhs),
+            BinaryOp::ShiftLeft => lhs.wrapping_shl(rhs as u32),
+            BinaryOp::ShiftRight => lhs.wrapping_shr(rhs as u32),
+            BinaryOp::Add => lhs.wrapping_add(rhs),
+            BinaryOp::Subtract => lhs.wrapping_sub(rhs),
+            BinaryOp::Multiply => lhs.wrapping_mul(rhs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Number(i64),
+    Variable(Variable),
+    Memory(Box<Node>),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, cpu: &Cpu, bus: &Bus) -> i64 {
+        match self {
+            Node::Number(n) => *n,
+            Node::Variable(Variable::Register(register)) => {
+                let reg = &cpu.reg;
+
+                let value = match register {
+                    Register::A => u16::from(reg.a),
+                    Register::F => u16::from(reg.f.bits()),
+                    Register::B => u16::from(reg.b),
+                    Register::C => u16::from(reg.c),
+                    Register::D => u16::from(reg.d),
+                    Register::E => u16::from(reg.e),
+                    Register::H => u16::from(reg.h),
+                    Register::L => u16::from(reg.l),
+                    Register::Af => reg.af(),
+                    Register::Bc => reg.bc(),
+                    Register::De => reg.de(),
+                    Register::Hl => reg.hl(),
+                    Register::Sp => reg.sp,
+                    Register::Pc => reg.pc,
+                };
+
+                i64::from(value)
+            }
+            Node::Variable(Variable::Flag(flag)) => i64::from(cpu.reg.f.contains(*flag)),
+            Node::Variable(Variable::Io(address)) => i64::from(bus.read_byte_no_tick(*address)),
+            Node::Memory(address) => {
+                i64::from(bus.read_byte_no_tick(address.evaluate(cpu, bus) as u16))
+            }
+            Node::Unary(op, operand) => {
+                let operand = operand.evaluate(cpu, bus);
+
+                match op {
+                    UnaryOp::Negate => operand.wrapping_neg(),
+                    UnaryOp::Not => i64::from(operand == 0),
+                    UnaryOp::Complement => !operand,
+                }
+            }
+            Node::Binary(op, lhs, rhs) => op.apply(lhs.evaluate(cpu, bus), rhs.evaluate(cpu, bus)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Identifier(String),
+    Operator(&'static str),
+    OpenBracket,
+    CloseBracket,
+    OpenParen,
+    CloseParen,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Identifier(name) => write!(f, "{}", name),
+            Token::Operator(op) => write!(f, "{}", op),
+            Token::OpenBracket => write!(f, "["),
+            Token::CloseBracket => write!(f, "]"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+        }
+    }
+}
+
+/// Operators, ordered so that the longest match is tried first.
+const OPERATORS: &[&str] = &[
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "|", "^", "&", "<", ">", "+", "-", "*", "!",
+    "~",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, ParseExprError> {
+    let mut tokens = vec![];
+    let mut chars = s.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '[' => Token::OpenBracket,
+            ']' => Token::CloseBracket,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '$' => {
+                chars.next();
+                let digits = take_word(s, &mut chars, start + 1);
+                let n = i64::from_str_radix(digits, 16)
+                    .map_err(|_| ParseExprError::InvalidNumber(format!("${}", digits)))?;
+                tokens.push(Token::Number(n));
+                continue;
+            }
+            c if c.is_ascii_digit() => {
+                let word = take_word(s, &mut chars, start);
+                tokens.push(Token::Number(parse_number(word)?));
+                continue;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let word = take_word(s, &mut chars, start);
+                tokens.push(Token::Identifier(word.to_owned()));
+                continue;
+            }
+            _ => {
+                let op = OPERATORS
+                    .iter()
+                    .find(|op| s[start..].starts_with(*op))
+                    .ok_or(ParseExprError::UnexpectedCharacter(c))?;
+
+                for _ in 0..op.len() {
+                    chars.next();
+                }
+
+                tokens.push(Token::Operator(op));
+                continue;
+            }
+        };
+
+        chars.next();
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// Consumes a run of alphanumeric characters beginning at `start`.
+fn take_word<'a>(s: &'a str, chars: &mut Peekable<CharIndices<'_>>, start: usize) -> &'a str {
+    let mut end = start;
+
+    while let Some(&(i, c)) = chars.peek() {
+        if !(c.is_ascii_alphanumeric() || c == '_') {
+            break;
+        }
+
+        end = i + c.len_utf8();
+        chars.next();
+    }
+
+    &s[start..end]
+}
+
+fn parse_number(word: &str) -> Result<i64, ParseExprError> {
+    let lower = word.to_ascii_lowercase();
+
+    let result = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        lower.parse()
+    };
+
+    result.map_err(|_| ParseExprError::InvalidNumber(word.to_owned()))
+}
+
+struct Parser {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+}
+
+impl Parser {
+    /// Parses an expression containing only binary operators that bind tighter than
+    /// `min_precedence`.
+    fn expression(&mut self, min_precedence: u8) -> Result<Node, ParseExprError> {
+        let mut lhs = self.unary()?;
+
+        while let Some(op) = self.tokens.peek().and_then(BinaryOp::from_token) {
+            if op.precedence() <= min_precedence {
+                break;
+            }
+
+            self.tokens.next();
+            let rhs = self.expression(op.precedence())?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Node, ParseExprError> {
+        let op = match self.tokens.peek() {
+            Some(Token::Operator("-")) => UnaryOp::Negate,
+            Some(Token::Operator("!")) => UnaryOp::Not,
+            Some(Token::Operator("~")) => UnaryOp::Complement,
+            _ => return self.primary(),
+        };
+
+        self.tokens.next();
+        Ok(Node::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Node, ParseExprError> {
+        match self.tokens.next() {
+            Some(Token::Number(n)) => Ok(Node::Number(n)),
+            Some(Token::Identifier(name)) => Ok(Node::Variable(name.parse()?)),
+            Some(Token::OpenParen) => {
+                let node = self.expression(0)?;
+                self.expect(Token::CloseParen)?;
+                Ok(node)
+            }
+            Some(Token::OpenBracket) => {
+                let node = self.expression(0)?;
+                self.expect(Token::CloseBracket)?;
+                Ok(Node::Memory(Box::new(node)))
+            }
+            Some(token) => Err(ParseExprError::UnexpectedToken(token.to_string())),
+            None => Err(ParseExprError::UnexpectedEnd),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseExprError> {
+        match self.tokens.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(ParseExprError::UnexpectedToken(token.to_string())),
+            None => Err(ParseExprError::UnexpectedEnd),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::cpu::{Cpu, Flags};
+
+    use super::{assign, Expr, ParseExprError};
+
+    fn evaluate(cpu: &Cpu, bus: &Bus, s: &str) -> i64 {
+        s.parse::<Expr>().unwrap().evaluate(cpu, bus)
+    }
+
+    #[test]
+    fn arithmetic() {
+        let (cpu, bus) = (Cpu::new(), Bus::default());
+
+        assert_eq!(evaluate(&cpu, &bus, "1 + 2 * 3"), 7);
+        assert_eq!(evaluate(&cpu, &bus, "(1 + 2) * 3"), 9);
+        assert_eq!(evaluate(&cpu, &bus, "0x10 - $08 - 0b10"), 6);
+        assert_eq!(evaluate(&cpu, &bus, "1 << 4 | 1"), 17);
+        assert_eq!(evaluate(&cpu, &bus, "-1 + ~0xF0 & 0xFF"), 0x0E);
+        assert_eq!(evaluate(&cpu, &bus, "1 == 1 && 2 > 3 || !0"), 1);
+        assert_eq!(evaluate(&cpu, &bus, "3 >= 3 && 2 <= 1"), 0);
+    }
+
+    #[test]
+    fn variables() {
+        let mut cpu = Cpu::new();
+        let mut bus = Bus::default();
+
+        cpu.reg.a = 0x3c;
+        cpu.reg.h = 0xC0;
+        cpu.reg.l = 0x10;
+        cpu.reg.f = Flags::CARRY;
+        bus.write_byte_no_tick(0xC010, 5);
+        bus.ppu.control.display_enabled = true;
+        bus.ppu.line = 144;
+
+        assert_eq!(evaluate(&cpu, &bus, "a == 0x3c && [hl] > 4"), 1);
+        assert_eq!(evaluate(&cpu, &bus, "[hl + 1]"), 0);
+        assert_eq!(evaluate(&cpu, &bus, "HL"), 0xC010);
+        assert_eq!(evaluate(&cpu, &bus, "cf + zf"), 1);
+        assert_eq!(evaluate(&cpu, &bus, "ly == 144"), 1);
+    }
+
+    #[test]
+    fn assignment() {
+        let mut cpu = Cpu::new();
+        let mut bus = Bus::default();
+
+        assign("a", 0x13c, &mut cpu, &mut bus).unwrap();
+        assign("hl", 0xC010, &mut cpu, &mut bus).unwrap();
+        assign("af", 0x12FF, &mut cpu, &mut bus).unwrap();
+        assign("cf", 0, &mut cpu, &mut bus).unwrap();
+        assign("scx", 0x20, &mut cpu, &mut bus).unwrap();
+
+        assert_eq!(cpu.reg.hl(), 0xC010);
+        assert_eq!(cpu.reg.a, 0x12);
+        assert_eq!(cpu.reg.f, Flags::ZERO | Flags::SUBTRACT | Flags::HALF_CARRY);
+        assert_eq!(bus.read_byte_no_tick(0xFF43), 0x20);
+
+        assert_eq!(
+            assign("q", 0, &mut cpu, &mut bus),
+            Err(ParseExprError::UnknownVariable(String::from("q")))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!("a ==".parse::<Expr>(), Err(ParseExprError::UnexpectedEnd));
+        assert_eq!(
+            "foo".parse::<Expr>(),
+            Err(ParseExprError::UnknownVariable(String::from("foo")))
+        );
+        assert_eq!("[hl".parse::<Expr>(), Err(ParseExprError::UnexpectedEnd));
+        assert_eq!(
+            "a b".parse::<Expr>(),
+            Err(ParseExprError::UnexpectedToken(String::from("b")))
+        );
+        assert_eq!(
+            "0xZZ".parse::<Expr>(),
+            Err(ParseExprError::InvalidNumber(String::from("0xZZ")))
+        );
+        assert_eq!(
+            "a # 1".parse::<Expr>(),
+            Err(ParseExprError::UnexpectedCharacter('#'))
+        );
+    }
+
+    #[test]
+    fn display() {
+        let expr = " a == 0x3c ".parse::<Expr>().unwrap();
+        assert_eq!(expr.to_string(), "a == 0x3c");
+    }
+}
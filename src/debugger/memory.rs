@@ -0,0 +1,205 @@
+//! Ranges of memory and hex dumps.
+
+use std::fmt::{self, Display};
+
+use super::BankedAddress;
+
+/// The number of bytes shown on each line of a [`HexDump`].
+const BYTES_PER_LINE: usize = 16;
+
+/// A range of memory, which may be read from a bank that is not mapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryRange {
+    /// The first address of the range, and the bank to read it from, if any.
+    pub start: BankedAddress,
+
+    /// The last address of the range, inclusive.
+    pub end: u16,
+}
+
+impl MemoryRange {
+    /// The entire address space.
+    pub const ALL: MemoryRange = MemoryRange {
+        start: BankedAddress {
+            bank: None,
+            address: 0x0000,
+        },
+        end: 0xFFFF,
+    };
+
+    pub fn new(start: BankedAddress, end: u16) -> MemoryRange {
+        MemoryRange { start, end }
+    }
+
+    /// Creates a range from a location to the end of its bank, or to the end of the address space
+    /// if the location has no bank.
+    pub fn starting_at(start: BankedAddress) -> MemoryRange {
+        let end = match (start.bank, start.address) {
+            (Some(_), 0x0000..=0x3FFF) => 0x3FFF,
+            (Some(_), 0x4000..=0x7FFF) => 0x7FFF,
+            (Some(_), 0xA000..=0xBFFF) => 0xBFFF,
+            _ => 0xFFFF,
+        };
+
+        MemoryRange { start, end }
+    }
+
+    /// Looks up a region of memory by name: `rom`, `vram`, `sram`, `wram`, `oam`, `io` or `hram`.
+    ///
+    /// `rom` and `sram` may be followed by a bank, such as `rom:3` or `sram:0x01`, to read that bank
+    /// even if it is not mapped. Otherwise, they refer to the banks that are currently mapped.
+    pub fn region(name: &str) -> Option<MemoryRange> {
+        let (name, bank) = match name.split_once(':') {
+            Some((name, bank)) => {
+                let bank = match bank.strip_prefix("0x") {
+                    Some(hex) => u16::from_str_radix(hex, 16),
+                    None => bank.parse(),
+                };
+
+                (name, Some(bank.ok()?))
+            }
+            None => (name, None),
+        };
+
+        let (start, end) = match (name, bank) {
+            ("rom", None) => (0x0000, 0x7FFF),
+            ("rom", Some(0)) => (0x0000, 0x3FFF),
+            ("rom", Some(_)) => (0x4000, 0x7FFF),
+            ("vram", None) => (0x8000, 0x9FFF),
+            ("sram", _) => (0xA000, 0xBFFF),
+            ("wram", None) => (0xC000, 0xDFFF),
+            ("oam", None) => (0xFE00, 0xFE9F),
+            ("io", None) => (0xFF00, 0xFF7F),
+            ("hram", None) => (0xFF80, 0xFFFE),
+            _ => return None,
+        };
+
+        Some(MemoryRange {
+            start: BankedAddress {
+                bank,
+                address: start,
+            },
+            end,
+        })
+    }
+
+    /// The number of bytes in the range.
+    pub fn size(&self) -> usize {
+        usize::from(self.end.saturating_sub(self.start.address)) + 1
+    }
+
+    /// The locations in the range, in order.
+    pub fn locations(&self) -> impl Iterator<Item = BankedAddress> {
+        let bank = self.start.bank;
+        (self.start.address..=self.end).map(move |address| BankedAddress { bank, address })
+    }
+}
+
+impl Display for MemoryRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{:#06x}", self.start, self.end)
+    }
+}
+
+/// Bytes of memory, displayed as lines of hexadecimal bytes followed by the printable ASCII
+/// characters that they encode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexDump {
+    /// The location of the first byte.
+    pub start: BankedAddress,
+
+    pub bytes: Vec<u8>,
+}
+
+impl Display for HexDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (line, chunk) in self.bytes.chunks(BYTES_PER_LINE).enumerate() {
+            if line > 0 {
+                writeln!(f)?;
+            }
+
+            let location = BankedAddress {
+                address: self
+                    .start
+                    .address
+                    .wrapping_add((line * BYTES_PER_LINE) as u16),
+                ..self.start
+            };
+            write!(f, "{:<13}", location.to_string())?;
+
+            for i in 0..BYTES_PER_LINE {
+                if i == BYTES_PER_LINE / 2 {
+                    write!(f, " ")?;
+                }
+
+                match chunk.get(i) {
+                    Some(byte) => write!(f, " {:02x}", byte)?,
+                    None => write!(f, "   ")?,
+                }
+            }
+
+            let text = chunk
+                .iter()
+                .map(|&byte| match byte {
+                    0x20..=0x7E => char::from(byte),
+                    _ => '.',
+                })
+                .collect::<String>();
+            write!(f, "  |{}|", text)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debugger::BankedAddress;
+
+    use super::{HexDump, MemoryRange};
+
+    #[test]
+    fn region() {
+        assert_eq!(
+            MemoryRange::region("rom:3"),
+            Some(MemoryRange::new(
+                BankedAddress::with_bank(3, 0x4000),
+                0x7FFF
+            ))
+        );
+        assert_eq!(
+            MemoryRange::region("sram:0x01"),
+            Some(MemoryRange::new(
+                BankedAddress::with_bank(1, 0xA000),
+                0xBFFF
+            ))
+        );
+        assert_eq!(
+            MemoryRange::region("hram"),
+            Some(MemoryRange::new(BankedAddress::new(0xFF80), 0xFFFE))
+        );
+        assert_eq!(MemoryRange::region("oam").unwrap().size(), 160);
+        assert_eq!(MemoryRange::region("wram:1"), None);
+        assert_eq!(MemoryRange::region("rom:x"), None);
+
+        assert_eq!(
+            MemoryRange::starting_at(BankedAddress::with_bank(2, 0xA123)),
+            MemoryRange::new(BankedAddress::with_bank(2, 0xA123), 0xBFFF)
+        );
+    }
+
+    #[test]
+    fn hex_dump() {
+        let dump = HexDump {
+            start: BankedAddress::with_bank(1, 0x4000),
+            bytes: b"Hello, world!\x00\x01\xffGB".to_vec(),
+        };
+
+        assert_eq!(
+            dump.to_string(),
+            "\
+0x01:0x4000   48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 00 01 ff  |Hello, world!...|
+0x01:0x4010   47 42                                             |GB|"
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! Debugging functionality.
+
+mod command;
+mod disasm;
+mod expr;
+mod memory;
+mod profiler;
+mod search;
+mod symbols;
+mod trace;
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use derivative::Derivative;
+use thiserror::Error;
+
+use crate::bus::{Bus, WatchHit};
+use crate::cpu::{Cpu, Instruction};
+use crate::gdb::GdbStub;
+use crate::memory::Mmu;
+
+pub use self::command::{
+    BacktraceFrame, ChannelStatus, DebugCommand, DebugFrontend, DebugResponse,
+};
+pub use self::disasm::{disassemble, Disassembly};
+pub use self::expr::{assign, Expr, ParseExprError};
+pub use self::memory::{HexDump, MemoryRange};
+pub use self::profiler::{Counts, Profiler};
+pub use self::search::{Candidate, MemorySearch, SearchFilter};
+pub use self::symbols::SymbolTable;
+pub use self::trace::{
+    format_state, Divergence, FieldDifference, TraceChecker, TraceComparison, TraceOptions, Tracer,
+};
+
+/// An error encountered while parsing a [`BankedAddress`].
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseAddressError {
+    #[error("address must start with '0x'")]
+    MissingPrefix,
+
+    #[error("could not parse number '{0}'")]
+    InvalidNumber(String),
+}
+
+/// An address, optionally qualified by the memory bank that must be mapped for it to match.
+///
+/// Parsed and displayed as `bank:address`, such as `0x03:0x4123`, or just the address if the bank
+/// is unspecified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BankedAddress {
+    pub bank: Option<u16>,
+    pub address: u16,
+}
+
+impl BankedAddress {
+    /// Creates an address that matches in any bank.
+    pub fn new(address: u16) -> BankedAddress {
+        BankedAddress {
+            bank: None,
+            address,
+        }
+    }
+
+    /// Creates an address that only matches when the given bank is mapped.
+    pub fn with_bank(bank: u16, address: u16) -> BankedAddress {
+        BankedAddress {
+            bank: Some(bank),
+            address,
+        }
+    }
+
+    /// Qualifies an address with the bank that is currently mapped there, if it is banked.
+    pub fn resolve(address: u16, mmu: &Mmu) -> BankedAddress {
+        BankedAddress {
+            bank: mmu.bank(address),
+            address,
+        }
+    }
+}
+
+impl Display for BankedAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.bank {
+            Some(bank) => write!(f, "{:#04x}:{:#06x}", bank, self.address),
+            None => write!(f, "{:#06x}", self.address),
+        }
+    }
+}
+
+impl FromStr for BankedAddress {
+    type Err = ParseAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseAddressError::InvalidNumber(s.to_owned());
+
+        let (bank, address) = match s.split_once(':') {
+            Some((bank, address)) => {
+                let bank = match bank.strip_prefix("0x") {
+                    Some(hex) => u16::from_str_radix(hex, 16),
+                    None => bank.parse(),
+                };
+
+                (Some(bank.map_err(|_| invalid())?), address)
+            }
+            None => (None, s),
+        };
+
+        let address = address
+            .strip_prefix("0x")
+            .ok_or(ParseAddressError::MissingPrefix)?;
+        let address = u16::from_str_radix(address, 16).map_err(|_| invalid())?;
+
+        Ok(BankedAddress { bank, address })
+    }
+}
+
+/// Pauses execution when the program counter reaches an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// The address of the instruction to break on.
+    pub address: u16,
+
+    /// Only break if this bank is mapped at the address.
+    pub bank: Option<u16>,
+
+    /// Only break if this expression is true.
+    pub condition: Option<Expr>,
+
+    /// The number of upcoming hits to skip before breaking. Decremented by each skipped hit.
+    pub ignore_count: u32,
+
+    /// The number of times the breakpoint was reached while its condition was true.
+    pub hit_count: u32,
+
+    /// Commands to execute when the breakpoint pauses execution.
+    pub commands: Vec<DebugCommand>,
+}
+
+impl Breakpoint {
+    /// Creates an unconditional breakpoint.
+    pub fn new(address: u16) -> Breakpoint {
+        Breakpoint {
+            address,
+            bank: None,
+            condition: None,
+            ignore_count: 0,
+            hit_count: 0,
+            commands: vec![],
+        }
+    }
+
+    /// Creates a breakpoint that only breaks when the condition is true.
+    pub fn with_condition(address: u16, condition: Expr) -> Breakpoint {
+        Breakpoint {
+            condition: Some(condition),
+            ..Breakpoint::new(address)
+        }
+    }
+
+    /// Creates a breakpoint at an address that may be qualified by a bank.
+    pub fn at(location: BankedAddress) -> Breakpoint {
+        Breakpoint {
+            bank: location.bank,
+            ..Breakpoint::new(location.address)
+        }
+    }
+
+    /// The address of the breakpoint, including its bank.
+    pub fn location(&self) -> BankedAddress {
+        BankedAddress {
+            bank: self.bank,
+            address: self.address,
+        }
+    }
+
+    /// Records that the breakpoint was reached, and returns whether execution should pause.
+    pub fn hit(&mut self, cpu: &Cpu, bus: &Bus) -> bool {
+        if let Some(condition) = &self.condition {
+            if !condition.is_true(cpu, bus) {
+                return false;
+            }
+        }
+
+        self.hit_count += 1;
+
+        if self.ignore_count > 0 {
+            self.ignore_count -= 1;
+            return false;
+        }
+
+        true
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.location())?;
+
+        if let Some(condition) = &self.condition {
+            write!(f, " if {}", condition)?;
+        }
+
+        write!(f, " (hits: {}", self.hit_count)?;
+
+        if self.ignore_count > 0 {
+            write!(f, ", ignoring next {}", self.ignore_count)?;
+        }
+
+        if !self.commands.is_empty() {
+            write!(f, ", {} commands", self.commands.len())?;
+        }
+
+        write!(f, ")")
+    }
+}
+
+/// A point that execution continues until, before pausing again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RunUntil {
+    /// Until the program counter reaches an address.
+    Address(BankedAddress),
+
+    /// Until a call returns to `address`. The stack pointer must be at least `sp`, so that
+    /// recursive calls that return to the same address are skipped.
+    Return { address: u16, sp: u16 },
+
+    /// Until the current function returns, which pops the stack above `sp`.
+    FrameExit { sp: u16 },
+}
+
+impl RunUntil {
+    /// Whether execution has reached the point, after executing `instruction`.
+    pub fn is_reached(&self, instruction: &Instruction, cpu: &Cpu, bus: &Bus) -> bool {
+        let reg = &cpu.reg;
+
+        match *self {
+            RunUntil::Address(location) => {
+                reg.pc == location.address
+                    && (location.bank.is_none() || location.bank == bus.mmu.bank(reg.pc))
+            }
+            RunUntil::Return { address, sp } => reg.pc == address && reg.sp >= sp,
+            RunUntil::FrameExit { sp } => instruction.is_return() && reg.sp > sp,
+        }
+    }
+}
+
+pub(crate) use self::command::execute;
+
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub(crate) struct Debugger {
+    /// The interface that commands are read from while execution is paused, if any.
+    #[derivative(Debug = "ignore")]
+    pub frontend: Option<Box<dyn DebugFrontend>>,
+
+    pub breakpoints: HashMap<BankedAddress, Breakpoint>,
+    pub paused: bool,
+
+    /// Commands that are executed before any from the frontend, such as those of a breakpoint that
+    /// was hit or of a script.
+    pub queued: VecDeque<DebugCommand>,
+
+    /// Where execution should pause, in addition to the breakpoints.
+    pub run_until: Option<RunUntil>,
+
+    /// The most recent watchpoint hit, along with the address of the instruction that caused it.
+    pub watch_hit: Option<(u16, Instruction, WatchHit)>,
+
+    /// The GDB connection that controls execution in place of the prompt, if any.
+    pub gdb: Option<GdbStub>,
+
+    /// Whether a frontend has asked for the emulator to stop.
+    pub quit: bool,
+
+    /// The rest of the memory that was being dumped, which is shown by the next dump without a
+    /// range.
+    pub dump_next: Option<MemoryRange>,
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: Default::default(),
+            paused: true,
+            queued: VecDeque::new(),
+            run_until: None,
+            watch_hit: None,
+            gdb: None,
+            quit: false,
+            dump_next: None,
+            frontend: None,
+        }
+    }
+
+    /// Checks the breakpoints at the current program counter, in any bank and in the bank that is
+    /// currently mapped. Returns whether execution should pause, and queues the commands of the
+    /// breakpoints that pause it ahead of any others.
+    pub fn check_breakpoints(&mut self, cpu: &Cpu, bus: &Bus) -> bool {
+        let pc = cpu.reg.pc;
+        let locations = [
+            Some(BankedAddress::new(pc)),
+            bus.mmu
+                .bank(pc)
+                .map(|bank| BankedAddress::with_bank(bank, pc)),
+        ];
+
+        let mut pause = false;
+
+        for location in locations.iter().flatten() {
+            if let Some(breakpoint) = self.breakpoints.get_mut(location) {
+                if breakpoint.hit(cpu, bus) {
+                    pause = true;
+                    for command in breakpoint.commands.iter().rev() {
+                        self.queued.push_front(command.clone());
+                    }
+                }
+            }
+        }
+
+        pause
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Bus;
+    use crate::cpu::Cpu;
+
+    use super::{BankedAddress, Breakpoint, Debugger, ParseAddressError};
+
+    #[test]
+    fn conditional_breakpoint() {
+        let mut cpu = Cpu::new();
+        let bus = Bus::default();
+
+        let mut breakpoint = Breakpoint::with_condition(0x150, "a == 3".parse().unwrap());
+        breakpoint.ignore_count = 1;
+
+        cpu.reg.a = 2;
+        assert!(!breakpoint.hit(&cpu, &bus));
+
+        cpu.reg.a = 3;
+        assert_eq!(
+            breakpoint.to_string(),
+            "0x0150 if a == 3 (hits: 0, ignoring next 1)"
+        );
+        assert!(!breakpoint.hit(&cpu, &bus));
+        assert!(breakpoint.hit(&cpu, &bus));
+        assert_eq!(breakpoint.hit_count, 2);
+
+        assert_eq!(breakpoint.to_string(), "0x0150 if a == 3 (hits: 2)");
+    }
+
+    #[test]
+    fn parse_banked_address() {
+        assert_eq!("0x4123".parse(), Ok(BankedAddress::new(0x4123)));
+        assert_eq!("3:0x4123".parse(), Ok(BankedAddress::with_bank(3, 0x4123)));
+        assert_eq!(
+            "0x1f:0x4123".parse(),
+            Ok(BankedAddress::with_bank(0x1F, 0x4123))
+        );
+        assert_eq!(
+            "4123".parse::<BankedAddress>(),
+            Err(ParseAddressError::MissingPrefix)
+        );
+        assert!("x:0x4123".parse::<BankedAddress>().is_err());
+
+        assert_eq!(
+            BankedAddress::with_bank(3, 0x4123).to_string(),
+            "0x03:0x4123"
+        );
+    }
+
+    #[test]
+    fn banked_breakpoint() {
+        let cpu = Cpu::new();
+        let bus = Bus::default();
+
+        let mut debugger = Debugger::new();
+        let location = BankedAddress::with_bank(2, cpu.reg.pc);
+        debugger
+            .breakpoints
+            .insert(location, Breakpoint::at(location));
+        assert!(!debugger.check_breakpoints(&cpu, &bus));
+
+        let location = BankedAddress::with_bank(0, cpu.reg.pc);
+        debugger
+            .breakpoints
+            .insert(location, Breakpoint::at(location));
+        assert!(debugger.check_breakpoints(&cpu, &bus));
+    }
+}
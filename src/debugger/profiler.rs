@@ -0,0 +1,275 @@
+//! Execution profiling.
+//!
+//! The profiler counts the instructions executed and the T-cycles consumed at each address, and
+//! attributes the cycles to the call stack they were consumed in. Cycles spent halted are
+//! attributed to the address that execution resumes at.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use crate::cpu::{CallStack, TCycles};
+
+use super::{BankedAddress, SymbolTable};
+
+/// Execution counts for an address or bank.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counts {
+    /// The number of instructions executed.
+    pub instructions: u64,
+
+    /// The number of T-cycles consumed.
+    pub cycles: u64,
+}
+
+impl Counts {
+    fn add(&mut self, executed: bool, cycles: TCycles) {
+        self.instructions += u64::from(executed);
+        self.cycles += u64::from(cycles.0);
+    }
+}
+
+/// Counts where execution time is spent.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    by_location: HashMap<BankedAddress, Counts>,
+
+    /// The cycles consumed in each call stack. Stacks are the addresses of the functions that were
+    /// entered, outermost first.
+    by_stack: HashMap<Vec<BankedAddress>, u64>,
+
+    /// The stack of the instruction that is being executed, reused to avoid allocating.
+    stack: Vec<BankedAddress>,
+
+    /// The location of the instruction that is being executed, and whether it is executed or the
+    /// CPU is halted.
+    current: Option<(BankedAddress, bool)>,
+}
+
+impl Profiler {
+    /// Creates an empty profile.
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Starts recording the instruction at `location`, which is about to be executed with
+    /// `call_stack` as the stack. `executed` is `false` if the CPU is halted instead.
+    pub fn begin(&mut self, location: BankedAddress, call_stack: &CallStack, executed: bool) {
+        self.current = Some((location, executed));
+
+        self.stack.clear();
+        self.stack
+            .extend(call_stack.iter().rev().map(|frame| BankedAddress {
+                bank: frame.target_bank,
+                address: frame.target,
+            }));
+    }
+
+    /// Records the cycles consumed since the last call to [`Profiler::begin`].
+    pub fn end(&mut self, cycles: TCycles) {
+        let (location, executed) = match self.current.take() {
+            Some(current) => current,
+            None => return,
+        };
+
+        self.by_location
+            .entry(location)
+            .or_default()
+            .add(executed, cycles);
+
+        let cycles = u64::from(cycles.0);
+        match self.by_stack.get_mut(self.stack.as_slice()) {
+            Some(total) => *total += cycles,
+            None => {
+                self.by_stack.insert(self.stack.clone(), cycles);
+            }
+        }
+    }
+
+    /// Forgets everything that has been recorded.
+    pub fn reset(&mut self) {
+        self.by_location.clear();
+        self.by_stack.clear();
+    }
+
+    /// The total counts of all addresses.
+    pub fn total(&self) -> Counts {
+        self.by_location
+            .values()
+            .fold(Counts::default(), |total, counts| Counts {
+                instructions: total.instructions + counts.instructions,
+                cycles: total.cycles + counts.cycles,
+            })
+    }
+
+    /// The counts of each address, ordered by the most cycles consumed.
+    pub fn hot_spots(&self) -> Vec<(BankedAddress, Counts)> {
+        let mut hot_spots = self
+            .by_location
+            .iter()
+            .map(|(&location, &counts)| (location, counts))
+            .collect::<Vec<_>>();
+        hot_spots.sort_by(|(a, a_counts), (b, b_counts)| {
+            b_counts.cycles.cmp(&a_counts.cycles).then(a.cmp(b))
+        });
+        hot_spots
+    }
+
+    /// The counts of each bank, ordered by bank. Addresses that are not banked are counted under
+    /// `None`.
+    pub fn banks(&self) -> BTreeMap<Option<u16>, Counts> {
+        let mut banks = BTreeMap::<_, Counts>::new();
+
+        for (location, counts) in &self.by_location {
+            let bank = banks.entry(location.bank).or_default();
+            bank.instructions += counts.instructions;
+            bank.cycles += counts.cycles;
+        }
+
+        banks
+    }
+
+    /// Writes a report of the `limit` hottest addresses and the time spent in each bank.
+    pub fn write_report(
+        &self,
+        mut out: impl Write,
+        symbols: &SymbolTable,
+        limit: usize,
+    ) -> io::Result<()> {
+        let total = self.total();
+        let percent = |cycles: u64| cycles as f64 * 100.0 / total.cycles.max(1) as f64;
+
+        writeln!(
+            out,
+            "{:>12} {:>7} {:>12}  location",
+            "cycles", "", "instructions"
+        )?;
+        for (location, counts) in self.hot_spots().into_iter().take(limit) {
+            writeln!(
+                out,
+                "{:>12} {:>6.2}% {:>12}  {}",
+                counts.cycles,
+                percent(counts.cycles),
+                counts.instructions,
+                symbols.describe(location)
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{:>12} {:>7} {:>12}  bank",
+            "cycles", "", "instructions"
+        )?;
+        for (bank, counts) in self.banks() {
+            let bank = match bank {
+                Some(bank) => format!("{:#04x}", bank),
+                None => String::from("unbanked"),
+            };
+
+            writeln!(
+                out,
+                "{:>12} {:>6.2}% {:>12}  {}",
+                counts.cycles,
+                percent(counts.cycles),
+                counts.instructions,
+                bank
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the cycles consumed in each call stack in the folded format used by flame graph
+    /// tools, such as `Main;PlayerUpdate;DrawSprite 1234`, with one stack per line.
+    ///
+    /// Functions are named by their symbol, if there is one. Cycles spent outside of any function
+    /// are attributed to `[top]`.
+    pub fn write_folded(&self, mut out: impl Write, symbols: &SymbolTable) -> io::Result<()> {
+        let mut stacks = self
+            .by_stack
+            .iter()
+            .map(|(stack, &cycles)| {
+                let names = std::iter::once(String::from("[top]"))
+                    .chain(stack.iter().map(|&function| {
+                        symbols
+                            .symbolize(function)
+                            .unwrap_or_else(|| function.to_string())
+                    }))
+                    .collect::<Vec<_>>();
+
+                (names.join(";"), cycles)
+            })
+            .collect::<Vec<_>>();
+        stacks.sort();
+
+        for (stack, cycles) in stacks {
+            writeln!(out, "{} {}", stack, cycles)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cpu::{CallStack, Frame, FrameKind, TCycles};
+    use crate::debugger::{BankedAddress, SymbolTable};
+
+    use super::{Counts, Profiler};
+
+    #[test]
+    fn profile() {
+        let mut profiler = Profiler::new();
+        let mut call_stack = CallStack::default();
+
+        profiler.begin(BankedAddress::new(0x0150), &call_stack, true);
+        profiler.end(TCycles(24));
+
+        call_stack.push(Frame {
+            kind: FrameKind::Call,
+            target: 0x4000,
+            target_bank: Some(2),
+            return_address: 0x0153,
+            return_bank: Some(0),
+            sp: 0xDFFC,
+        });
+
+        let location = BankedAddress::with_bank(2, 0x4000);
+        for (executed, cycles) in [(true, 8), (true, 8), (false, 4)] {
+            profiler.begin(location, &call_stack, executed);
+            profiler.end(TCycles(cycles));
+        }
+
+        assert_eq!(
+            profiler.hot_spots(),
+            [
+                (
+                    BankedAddress::new(0x0150),
+                    Counts {
+                        instructions: 1,
+                        cycles: 24
+                    }
+                ),
+                (
+                    location,
+                    Counts {
+                        instructions: 2,
+                        cycles: 20
+                    }
+                ),
+            ]
+        );
+        assert_eq!(profiler.banks()[&Some(2)].cycles, 20);
+
+        let mut folded = vec![];
+        let symbols = SymbolTable::parse("02:4000 PlayerUpdate");
+        profiler.write_folded(&mut folded, &symbols).unwrap();
+        assert_eq!(
+            String::from_utf8(folded).unwrap(),
+            "[top] 24\n[top];PlayerUpdate 20\n"
+        );
+
+        profiler.reset();
+        assert_eq!(profiler.total(), Counts::default());
+    }
+}
@@ -0,0 +1,141 @@
+//! Searching RAM for the variables behind values in a game, such as lives, health or timers.
+//!
+//! A search starts with every byte of RAM as a candidate. Each filter compares the value of each
+//! candidate to its value when it was last filtered, and keeps the candidates that match. Playing
+//! the game between filters narrows the candidates down to the variable that is being searched
+//! for.
+
+use super::BankedAddress;
+
+/// A comparison between the value of a candidate when it was last filtered and its current value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchFilter {
+    /// The value is the same.
+    Unchanged,
+
+    /// The value is different.
+    Changed,
+
+    /// The value is greater, or greater by exactly the given amount, wrapping around.
+    Increased(Option<u8>),
+
+    /// The value is less, or less by exactly the given amount, wrapping around.
+    Decreased(Option<u8>),
+
+    /// The value is equal to a number, regardless of its previous value.
+    Equals(u8),
+}
+
+impl SearchFilter {
+    /// Whether a candidate that changed from `previous` to `current` matches the filter.
+    pub fn matches(&self, previous: u8, current: u8) -> bool {
+        match *self {
+            SearchFilter::Unchanged => current == previous,
+            SearchFilter::Changed => current != previous,
+            SearchFilter::Increased(None) => current > previous,
+            SearchFilter::Increased(Some(amount)) => current == previous.wrapping_add(amount),
+            SearchFilter::Decreased(None) => current < previous,
+            SearchFilter::Decreased(Some(amount)) => current == previous.wrapping_sub(amount),
+            SearchFilter::Equals(value) => current == value,
+        }
+    }
+}
+
+/// A location that may hold the variable being searched for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub location: BankedAddress,
+
+    /// The value of the location when it was last filtered.
+    pub value: u8,
+}
+
+/// A search for the locations whose values change in a particular way.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySearch {
+    candidates: Vec<Candidate>,
+}
+
+impl MemorySearch {
+    /// Starts a search with a snapshot of the locations to search and their current values.
+    pub fn new(snapshot: impl IntoIterator<Item = (BankedAddress, u8)>) -> MemorySearch {
+        let candidates = snapshot
+            .into_iter()
+            .map(|(location, value)| Candidate { location, value })
+            .collect();
+
+        MemorySearch { candidates }
+    }
+
+    /// Keeps the candidates that match the filter, and records their current values, which are
+    /// read with `read`. Returns the number of candidates that are left.
+    pub fn filter(
+        &mut self,
+        filter: SearchFilter,
+        mut read: impl FnMut(BankedAddress) -> u8,
+    ) -> usize {
+        self.candidates.retain_mut(|candidate| {
+            let value = read(candidate.location);
+            let matches = filter.matches(candidate.value, value);
+            candidate.value = value;
+            matches
+        });
+
+        self.candidates.len()
+    }
+
+    /// The remaining candidates, ordered by location.
+    pub fn candidates(&self) -> &[Candidate] {
+        &self.candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::debugger::BankedAddress;
+
+    use super::{MemorySearch, SearchFilter};
+
+    #[test]
+    fn filter() {
+        let mut memory = [3, 3, 3, 5];
+        let snapshot = memory
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| (BankedAddress::new(0xC000 + i as u16), value));
+        let mut search = MemorySearch::new(snapshot);
+
+        let read = |memory: [u8; 4]| {
+            move |location: BankedAddress| memory[location.address as usize - 0xC000]
+        };
+
+        // A life is lost.
+        memory = [2, 3, 4, 4];
+        assert_eq!(
+            search.filter(SearchFilter::Decreased(None), read(memory)),
+            2
+        );
+
+        // Nothing happens.
+        assert_eq!(search.filter(SearchFilter::Unchanged, read(memory)), 2);
+
+        // Another life is lost.
+        memory = [1, 3, 4, 2];
+        assert_eq!(
+            search.filter(SearchFilter::Decreased(Some(1)), read(memory)),
+            1
+        );
+        assert_eq!(search.candidates()[0].location, BankedAddress::new(0xC000));
+        assert_eq!(search.candidates()[0].value, 1);
+
+        assert_eq!(search.filter(SearchFilter::Equals(2), read(memory)), 0);
+    }
+
+    #[test]
+    fn matches() {
+        assert!(SearchFilter::Increased(Some(2)).matches(0xFF, 0x01));
+        assert!(!SearchFilter::Increased(None).matches(0xFF, 0x01));
+        assert!(SearchFilter::Changed.matches(1, 2));
+        assert!(!SearchFilter::Unchanged.matches(1, 2));
+    }
+}
@@ -0,0 +1,206 @@
+//! Symbol files produced by RGBDS.
+//!
+//! A symbol file lists the address of every label in a ROM, one per line, in the form
+//! `bank:address label`. Both numbers are hexadecimal. Comments begin with a semicolon.
+//!
+//! ```text
+//! ; File generated by rgblink
+//! 00:0150 Start
+//! 01:4000 PlayerUpdate
+//! 01:4012 PlayerUpdate.loop
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+
+use log::*;
+
+use super::BankedAddress;
+
+/// Labels loaded from a symbol file.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    by_name: HashMap<String, BankedAddress>,
+    by_location: BTreeMap<BankedAddress, String>,
+}
+
+impl SymbolTable {
+    /// Parses the contents of an RGBDS symbol file. Lines that cannot be parsed are skipped.
+    pub fn parse(contents: &str) -> SymbolTable {
+        let mut symbols = SymbolTable::default();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match parse_line(line) {
+                Some((location, name)) => symbols.insert(location, name),
+                None => warn!("skipping invalid symbol on line {}: {}", number + 1, line),
+            }
+        }
+
+        symbols
+    }
+
+    /// Adds a label at a location. Labels outside of banked memory match in any bank.
+    pub fn insert(&mut self, location: BankedAddress, name: &str) {
+        let location = normalize(location);
+
+        self.by_name.insert(name.to_owned(), location);
+        self.by_location
+            .entry(location)
+            .or_insert_with(|| name.to_owned());
+    }
+
+    /// The number of labels.
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    /// Whether there are no labels.
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// Returns the location of a label.
+    pub fn lookup(&self, name: &str) -> Option<BankedAddress> {
+        self.by_name.get(name).copied()
+    }
+
+    /// Returns the label at exactly this location, if any.
+    pub fn label(&self, location: BankedAddress) -> Option<&str> {
+        self.by_location
+            .get(&normalize(location))
+            .map(String::as_str)
+    }
+
+    /// Describes a location relative to the closest preceding label in the same bank and region of
+    /// memory, such as `PlayerUpdate+0x12`.
+    pub fn symbolize(&self, location: BankedAddress) -> Option<String> {
+        let location = normalize(location);
+
+        let (label_location, name) = self.by_location.range(..=location).next_back()?;
+        if label_location.bank != location.bank
+            || region(label_location.address) != region(location.address)
+        {
+            return None;
+        }
+
+        match location.address - label_location.address {
+            0 => Some(name.clone()),
+            offset => Some(format!("{}+{:#x}", name, offset)),
+        }
+    }
+
+    /// Formats a location, followed by its symbolic name if there is one.
+    pub fn describe(&self, location: BankedAddress) -> String {
+        match self.symbolize(location) {
+            Some(name) => format!("{} <{}>", location, name),
+            None => location.to_string(),
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Option<(BankedAddress, &str)> {
+    let (location, name) = line.split_once(char::is_whitespace)?;
+    let (bank, address) = location.split_once(':')?;
+
+    let bank = u16::from_str_radix(bank, 16).ok()?;
+    let address = u16::from_str_radix(address, 16).ok()?;
+
+    Some((BankedAddress::with_bank(bank, address), name.trim()))
+}
+
+/// The name of the region of memory that contains an address.
+fn region(address: u16) -> &'static str {
+    match address {
+        0x0000..=0x3FFF => "ROM0",
+        0x4000..=0x7FFF => "ROMX",
+        0x8000..=0x9FFF => "VRAM",
+        0xA000..=0xBFFF => "SRAM",
+        0xC000..=0xDFFF => "WRAM",
+        0xE000..=0xFDFF => "ECHO",
+        0xFE00..=0xFE9F => "OAM",
+        0xFEA0..=0xFEFF => "UNUSED",
+        0xFF00..=0xFF7F => "IO",
+        0xFF80..=0xFFFE => "HRAM",
+        0xFFFF => "IE",
+    }
+}
+
+/// Removes the bank from locations in memory that is not banked on the DMG.
+fn normalize(location: BankedAddress) -> BankedAddress {
+    match location.address {
+        0x0000..=0x7FFF | 0xA000..=0xBFFF => location,
+        _ => BankedAddress::new(location.address),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use crate::debugger::BankedAddress;
+
+    use super::SymbolTable;
+
+    #[test]
+    fn parse() {
+        let symbols = SymbolTable::parse(indoc!(
+            "
+            ; File generated by rgblink
+            00:0150 Start
+            01:4000 PlayerUpdate
+            01:4012 PlayerUpdate.loop ; comment
+            02:4000 EnemyUpdate
+            00:c000 wPlayerX
+            invalid
+            "
+        ));
+
+        assert_eq!(symbols.len(), 5);
+        assert_eq!(
+            symbols.lookup("PlayerUpdate.loop"),
+            Some(BankedAddress::with_bank(1, 0x4012))
+        );
+        assert_eq!(symbols.lookup("wPlayerX"), Some(BankedAddress::new(0xC000)));
+        assert_eq!(symbols.lookup("Missing"), None);
+    }
+
+    #[test]
+    fn symbolize() {
+        let symbols =
+            SymbolTable::parse("01:4000 PlayerUpdate\n02:4000 EnemyUpdate\n00:c000 wPlayerX");
+
+        assert_eq!(
+            symbols.label(BankedAddress::with_bank(2, 0x4000)),
+            Some("EnemyUpdate")
+        );
+        assert_eq!(
+            symbols
+                .symbolize(BankedAddress::with_bank(1, 0x4012))
+                .as_deref(),
+            Some("PlayerUpdate+0x12")
+        );
+        assert_eq!(symbols.symbolize(BankedAddress::with_bank(3, 0x4012)), None);
+        assert_eq!(
+            symbols.describe(BankedAddress::new(0xC001)),
+            "0xc001 <wPlayerX+0x1>"
+        );
+    }
+
+    #[test]
+    fn symbolize_region() {
+        let symbols = SymbolTable::parse("00:0150 Start\n00:c000 wPlayerX");
+
+        // The OAM DMA routine is usually copied to HRAM, which has no labels.
+        assert_eq!(symbols.symbolize(BankedAddress::new(0xFF80)), None);
+        assert_eq!(symbols.symbolize(BankedAddress::new(0xE000)), None);
+        assert_eq!(symbols.symbolize(BankedAddress::new(0x8000)), None);
+        assert_eq!(
+            symbols.symbolize(BankedAddress::new(0xDFFF)).as_deref(),
+            Some("wPlayerX+0x1fff")
+        );
+    }
+}
@@ -0,0 +1,423 @@
+//! Execution traces.
+//!
+//! A trace contains one line for each executed instruction, describing the state of the CPU
+//! before the instruction is executed. Lines are in the format used by [Gameboy Doctor], which
+//! many other emulators can produce as well:
+//!
+//! ```text
+//! A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+//! ```
+//!
+//! The number of T-cycles executed so far and the state of the PPU may be appended, such as
+//! `CY:23440 LY:00 MODE:2`. Few emulators produce these fields, so they are optional.
+//!
+//! A [`TraceChecker`] compares execution against a reference trace from another emulator, and
+//! finds the first instruction at which they differ.
+//!
+//! [Gameboy Doctor]: https://github.com/robert/gameboy-doctor
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display, Write as _};
+use std::io::{self, BufRead, Write};
+
+use derivative::Derivative;
+
+use crate::bus::Bus;
+use crate::cpu::{Cpu, TCycles};
+
+/// Optional fields that are appended to each line of a trace.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TraceOptions {
+    /// Append the number of T-cycles executed before the instruction.
+    pub cycles: bool,
+
+    /// Append the current scanline and PPU mode.
+    pub ppu: bool,
+}
+
+/// Writes an execution trace.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct Tracer {
+    #[derivative(Debug = "ignore")]
+    writer: Box<dyn Write>,
+
+    options: TraceOptions,
+
+    /// The number of T-cycles executed since the trace started.
+    cycles: u64,
+}
+
+impl Tracer {
+    /// Creates a tracer that writes lines to `writer`.
+    pub fn new(writer: impl Write + 'static, options: TraceOptions) -> Tracer {
+        Tracer {
+            writer: Box::new(writer),
+            options,
+            cycles: 0,
+        }
+    }
+
+    /// Writes the line for the instruction that is about to be executed. `elapsed` is the number
+    /// of cycles that have been executed since the last call to [`Tracer::advance`], such as by
+    /// dispatching an interrupt.
+    pub fn trace(&mut self, cpu: &Cpu, bus: &Bus, elapsed: TCycles) -> io::Result<()> {
+        let line = format_state(cpu, bus, self.cycles + u64::from(elapsed.0), self.options);
+        writeln!(self.writer, "{}", line)
+    }
+
+    /// Records that cycles were executed.
+    pub fn advance(&mut self, cycles: TCycles) {
+        self.cycles += u64::from(cycles.0);
+    }
+}
+
+/// Compares execution against a reference trace, one instruction at a time.
+///
+/// Only the fields that are present in the reference are compared, so the reference may include
+/// or omit any of the optional fields. Fields are compared case-insensitively.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub struct TraceChecker {
+    #[derivative(Debug = "ignore")]
+    reference: Box<dyn BufRead>,
+
+    /// The number of the last reference line that was read.
+    line_number: usize,
+
+    /// The most recent matching lines, oldest first.
+    history: VecDeque<String>,
+
+    /// The maximum number of lines kept in the history.
+    history_len: usize,
+
+    /// The number of T-cycles executed since the comparison started.
+    cycles: u64,
+
+    /// The outcome of the comparison, once it has finished.
+    result: Option<io::Result<TraceComparison>>,
+}
+
+impl TraceChecker {
+    /// Creates a checker that compares against the lines of `reference`. The last `history_len`
+    /// matching lines are reported when the trace diverges.
+    pub fn new(reference: impl BufRead + 'static, history_len: usize) -> TraceChecker {
+        TraceChecker {
+            reference: Box::new(reference),
+            line_number: 0,
+            history: VecDeque::with_capacity(history_len),
+            history_len,
+            cycles: 0,
+            result: None,
+        }
+    }
+
+    /// Compares the state before the instruction that is about to be executed with the next line
+    /// of the reference. `elapsed` is the number of cycles that have been executed since the last
+    /// call to [`TraceChecker::advance`].
+    ///
+    /// Does nothing once the comparison has finished.
+    pub fn check(&mut self, cpu: &Cpu, bus: &Bus, elapsed: TCycles) {
+        if self.result.is_some() {
+            return;
+        }
+
+        let expected = match self.next_reference_line() {
+            Ok(Some(expected)) => expected,
+            Ok(None) => {
+                self.result = Some(Ok(TraceComparison::Matched {
+                    lines: self.line_number,
+                }));
+                return;
+            }
+            Err(e) => {
+                self.result = Some(Err(e));
+                return;
+            }
+        };
+
+        let options = TraceOptions {
+            cycles: true,
+            ppu: true,
+        };
+        let actual = format_state(cpu, bus, self.cycles + u64::from(elapsed.0), options);
+
+        let actual_fields = parse_fields(&actual).collect::<HashMap<_, _>>();
+        let differences = parse_fields(&expected)
+            .filter_map(|(name, expected)| {
+                let actual = actual_fields.get(&name).copied().unwrap_or_default();
+                if actual.eq_ignore_ascii_case(expected) {
+                    None
+                } else {
+                    Some(FieldDifference {
+                        name,
+                        expected: expected.to_owned(),
+                        actual: actual.to_owned(),
+                    })
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if differences.is_empty() {
+            if self.history.len() == self.history_len {
+                self.history.pop_front();
+            }
+            if self.history_len > 0 {
+                self.history.push_back(actual);
+            }
+        } else {
+            self.result = Some(Ok(TraceComparison::Diverged(Divergence {
+                line_number: self.line_number,
+                history: self.history.drain(..).collect(),
+                expected,
+                actual,
+                differences,
+            })));
+        }
+    }
+
+    /// Records that cycles were executed.
+    pub fn advance(&mut self, cycles: TCycles) {
+        self.cycles += u64::from(cycles.0);
+    }
+
+    /// The number of reference lines that have been compared so far.
+    pub fn lines_checked(&self) -> usize {
+        self.line_number
+    }
+
+    /// Returns the outcome of the comparison, if it has finished.
+    pub fn take_result(&mut self) -> Option<io::Result<TraceComparison>> {
+        self.result.take()
+    }
+
+    /// Reads the next non-empty line of the reference, if there is one.
+    fn next_reference_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            if self.reference.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+
+            self.line_number += 1;
+
+            if !line.trim().is_empty() {
+                return Ok(Some(line.trim().to_owned()));
+            }
+        }
+    }
+}
+
+/// The outcome of comparing execution against a reference trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceComparison {
+    /// Every line of the reference matched.
+    Matched { lines: usize },
+
+    /// Execution diverged from the reference.
+    Diverged(Divergence),
+}
+
+/// The first instruction at which execution differed from a reference trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The number of the reference line that did not match, starting at 1.
+    pub line_number: usize,
+
+    /// The lines that preceded the divergence, oldest first.
+    pub history: Vec<String>,
+
+    /// The line in the reference.
+    pub expected: String,
+
+    /// The line produced by the emulator.
+    pub actual: String,
+
+    /// The fields that differed.
+    pub differences: Vec<FieldDifference>,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "trace diverged at line {}", self.line_number)?;
+
+        for line in &self.history {
+            writeln!(f, "            {}", line)?;
+        }
+
+        writeln!(f, "expected:   {}", self.expected)?;
+        writeln!(f, "actual:     {}", self.actual)?;
+
+        for difference in &self.differences {
+            writeln!(f, "{}", difference)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A field of a trace line that differed from the reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDifference {
+    /// The name of the field, such as `PC`.
+    pub name: String,
+
+    /// The value in the reference.
+    pub expected: String,
+
+    /// The value produced by the emulator. Empty if the emulator does not produce the field.
+    pub actual: String,
+}
+
+impl Display for FieldDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, was {}",
+            self.name, self.expected, self.actual
+        )
+    }
+}
+
+/// Formats the state of the CPU before an instruction is executed as a line of a trace, without a
+/// trailing newline. `cycles` is the number of T-cycles executed before the instruction.
+pub fn format_state(cpu: &Cpu, bus: &Bus, cycles: u64, options: TraceOptions) -> String {
+    let reg = &cpu.reg;
+    let pc_mem = |offset| bus.read_byte_no_tick(reg.pc.wrapping_add(offset));
+
+    let mut line = format!(
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} \
+         PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+        reg.a,
+        reg.f.bits(),
+        reg.b,
+        reg.c,
+        reg.d,
+        reg.e,
+        reg.h,
+        reg.l,
+        reg.sp,
+        reg.pc,
+        pc_mem(0),
+        pc_mem(1),
+        pc_mem(2),
+        pc_mem(3),
+    );
+
+    if options.cycles {
+        write!(line, " CY:{}", cycles).unwrap();
+    }
+
+    if options.ppu {
+        write!(line, " LY:{:02X} MODE:{}", bus.ppu.line(), bus.ppu.mode()).unwrap();
+    }
+
+    line
+}
+
+/// Splits a line of a trace into `(name, value)` pairs. Names are converted to uppercase.
+fn parse_fields(line: &str) -> impl Iterator<Item = (String, &str)> {
+    line.split_whitespace().map(|field| {
+        let (name, value) = field.split_once(':').unwrap_or((field, ""));
+        (name.to_ascii_uppercase(), value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use indoc::indoc;
+
+    use crate::bus::Bus;
+    use crate::cpu::{Cpu, Flags, TCycles};
+    use crate::test_util::SharedBuffer;
+
+    use super::{FieldDifference, TraceChecker, TraceComparison, TraceOptions, Tracer};
+
+    #[test]
+    fn trace() {
+        let mut cpu = Cpu::new();
+        let mut bus = Bus::default();
+
+        cpu.reg.a = 0x01;
+        cpu.reg.f = Flags::ZERO | Flags::HALF_CARRY | Flags::CARRY;
+        cpu.reg.c = 0x13;
+        cpu.reg.e = 0xD8;
+        cpu.reg.h = 0x01;
+        cpu.reg.l = 0x4D;
+        cpu.reg.sp = 0xFFFE;
+        cpu.reg.pc = 0xC000;
+        for (offset, byte) in [0x00, 0xC3, 0x13, 0x02].iter().enumerate() {
+            bus.write_byte_no_tick(0xC000 + offset as u16, *byte);
+        }
+
+        let buffer = SharedBuffer::default();
+        let mut tracer = Tracer::new(buffer.clone(), TraceOptions::default());
+        tracer.trace(&cpu, &bus, TCycles(0)).unwrap();
+
+        let options = TraceOptions {
+            cycles: true,
+            ppu: true,
+        };
+        let mut detailed = Tracer::new(buffer.clone(), options);
+        detailed.advance(TCycles(16));
+        detailed.trace(&cpu, &bus, TCycles(20)).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buffer.contents()).unwrap(),
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C000 PCMEM:00,C3,13,02\n\
+             A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C000 PCMEM:00,C3,13,02 \
+             CY:36 LY:00 MODE:0\n"
+        );
+    }
+
+    #[test]
+    fn check() {
+        let mut cpu = Cpu::new();
+        let bus = Bus::default();
+
+        let reference = indoc!(
+            "
+            A:00 F:00 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 PC:0000 PCMEM:00,00,00,00
+
+            a:00 f:00 b:00 c:00 d:00 e:00 h:00 l:00 sp:0000 pc:0001 pcmem:00,00,00,00 CY:4
+            A:00 F:00 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 PC:0002 PCMEM:00,00,00,00 CY:8
+            A:00 F:00 B:00 C:00 D:00 E:00 H:00 L:00 SP:0000 PC:0004 PCMEM:00,00,00,00 CY:12
+            "
+        );
+        let mut checker = TraceChecker::new(Cursor::new(reference), 2);
+
+        for pc in 0..4 {
+            cpu.reg.pc = pc;
+            checker.check(&cpu, &bus, TCycles(0));
+            checker.advance(TCycles(4));
+        }
+
+        let divergence = match checker.take_result().unwrap().unwrap() {
+            TraceComparison::Diverged(divergence) => divergence,
+            comparison => panic!("unexpected comparison {:?}", comparison),
+        };
+        assert_eq!(divergence.line_number, 5);
+        assert_eq!(divergence.history.len(), 2);
+        assert!(divergence.history[1].contains("PC:0002"));
+        assert_eq!(
+            divergence.differences,
+            [FieldDifference {
+                name: String::from("PC"),
+                expected: String::from("0004"),
+                actual: String::from("0003"),
+            }]
+        );
+
+        let mut checker = TraceChecker::new(Cursor::new("A:00"), 0);
+        checker.check(&cpu, &bus, TCycles(0));
+        checker.check(&cpu, &bus, TCycles(0));
+        assert_eq!(
+            checker.take_result().unwrap().unwrap(),
+            TraceComparison::Matched { lines: 1 }
+        );
+    }
+}
@@ -0,0 +1,393 @@
+//! Support for GBS (Game Boy Sound System) music rips.
+//!
+//! A GBS file contains the sound driver and music data extracted from a game, along with the
+//! addresses of a routine that starts a track and a routine that must be called at a steady rate
+//! to play it. The emulator plays GBS files by converting them into a cartridge ROM with a small
+//! driver program that calls these routines.
+//!
+//! See the [GBS specification] for more details.
+//!
+//! [GBS specification]: https://www.vgmpf.com/Wiki/index.php?title=GBS
+
+use std::num::Wrapping;
+
+use byteorder::{ByteOrder, LittleEndian};
+use thiserror::Error;
+
+/// The size of the GBS header. The payload follows immediately after.
+const HEADER_SIZE: usize = 0x70;
+
+/// The lowest address that the payload may be loaded at. The memory below it contains the driver.
+const MIN_LOAD_ADDRESS: u16 = 0x0400;
+
+/// The address of the interrupt handler that calls the PLAY routine.
+const PLAY_HANDLER: u16 = 0x0080;
+
+/// The address of the driver's entry point, just after the cartridge header.
+const ENTRY_POINT: u16 = 0x0150;
+
+#[derive(Debug, Error)]
+pub enum GbsError {
+    #[error("the file is too short to contain a GBS header")]
+    InvalidSize,
+
+    #[error("the file does not start with the GBS signature")]
+    InvalidSignature,
+
+    #[error("GBS version {0} is unsupported")]
+    UnsupportedVersion(u8),
+
+    #[error("the load address {0:#06x} overlaps the driver")]
+    InvalidLoadAddress(u16),
+
+    #[error("track {track} does not exist, the file contains tracks 1-{count}")]
+    InvalidTrack { track: u8, count: u8 },
+}
+
+/// The event that the PLAY routine is called on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayRate {
+    /// PLAY is called on every VBlank interrupt, about 59.7 times per second.
+    VBlank,
+
+    /// PLAY is called on every timer interrupt, with the given timer modulo (TMA) and timer
+    /// control (TAC) values.
+    Timer { modulo: u8, control: u8 },
+}
+
+/// A parsed GBS file.
+#[derive(Debug, Clone)]
+pub struct Gbs {
+    /// The number of tracks in the file.
+    pub track_count: u8,
+
+    /// The track that should be played by default, starting at 1.
+    pub first_track: u8,
+
+    /// The address that the payload is loaded at.
+    pub load_address: u16,
+
+    /// The address of the routine that starts a track. It is called with the track index,
+    /// starting at 0, in register A.
+    pub init_address: u16,
+
+    /// The address of the routine that plays the track.
+    pub play_address: u16,
+
+    /// The initial value of the stack pointer.
+    pub stack_pointer: u16,
+
+    /// How often the PLAY routine is called.
+    pub play_rate: PlayRate,
+
+    /// The title of the game.
+    pub title: String,
+
+    /// The composer of the music.
+    pub author: String,
+
+    /// The copyright holder of the music.
+    pub copyright: String,
+
+    /// The driver and music data, loaded at `load_address`.
+    payload: Vec<u8>,
+}
+
+impl Gbs {
+    /// Parses the contents of a GBS file.
+    pub fn parse(bytes: &[u8]) -> Result<Gbs, GbsError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(GbsError::InvalidSize);
+        }
+
+        if &bytes[0x00..0x03] != b"GBS" {
+            return Err(GbsError::InvalidSignature);
+        }
+
+        let version = bytes[0x03];
+        if version != 1 {
+            return Err(GbsError::UnsupportedVersion(version));
+        }
+
+        let load_address = LittleEndian::read_u16(&bytes[0x06..]);
+        if load_address < MIN_LOAD_ADDRESS {
+            return Err(GbsError::InvalidLoadAddress(load_address));
+        }
+
+        let modulo = bytes[0x0E];
+        let control = bytes[0x0F];
+
+        // Bit 2 of TAC selects the timer, and the rest of the register is only valid if it's set.
+        let play_rate = if control & 0x04 != 0 {
+            PlayRate::Timer { modulo, control }
+        } else {
+            PlayRate::VBlank
+        };
+
+        Ok(Gbs {
+            track_count: bytes[0x04],
+            first_track: bytes[0x05],
+            load_address,
+            init_address: LittleEndian::read_u16(&bytes[0x08..]),
+            play_address: LittleEndian::read_u16(&bytes[0x0A..]),
+            stack_pointer: LittleEndian::read_u16(&bytes[0x0C..]),
+            play_rate,
+            title: parse_string(&bytes[0x10..0x30]),
+            author: parse_string(&bytes[0x30..0x50]),
+            copyright: parse_string(&bytes[0x50..0x70]),
+            payload: bytes[HEADER_SIZE..].to_vec(),
+        })
+    }
+
+    /// Builds a cartridge ROM that plays a track, starting at 1.
+    ///
+    /// The payload is loaded at its load address in an MBC1 cartridge, so that the sound driver
+    /// may switch banks as it would in the original game. The memory below the load address
+    /// contains the driver program, which starts the track and then calls PLAY from the VBlank or
+    /// timer interrupt handler. The restart vectors jump to the same offsets from the load
+    /// address.
+    pub fn to_rom(&self, track: u8) -> Result<Vec<u8>, GbsError> {
+        if track == 0 || track > self.track_count {
+            return Err(GbsError::InvalidTrack {
+                track,
+                count: self.track_count,
+            });
+        }
+
+        let mut rom = vec![0; usize::from(self.load_address)];
+        rom.extend_from_slice(&self.payload);
+
+        let size = rom.len().max(0x8000).next_power_of_two();
+        rom.resize(size, 0);
+
+        // RST vectors
+        for vector in (0x00..0x40).step_by(8) {
+            write_instruction(&mut rom, vector, &jp(self.load_address + vector));
+        }
+
+        // Interrupt vectors
+        let (vblank, timer, interrupt_enable, modulo, control) = match self.play_rate {
+            PlayRate::VBlank => (jp(PLAY_HANDLER), vec![RETI], 0x01, 0x00, 0x00),
+            PlayRate::Timer { modulo, control } => {
+                (vec![RETI], jp(PLAY_HANDLER), 0x04, modulo, control)
+            }
+        };
+        write_instruction(&mut rom, 0x40, &vblank);
+        write_instruction(&mut rom, 0x48, &[RETI]);
+        write_instruction(&mut rom, 0x50, &timer);
+        write_instruction(&mut rom, 0x58, &[RETI]);
+        write_instruction(&mut rom, 0x60, &[RETI]);
+
+        #[rustfmt::skip]
+        let play_handler = [
+            0xF5,                                   // PUSH AF
+            0xC5,                                   // PUSH BC
+            0xD5,                                   // PUSH DE
+            0xE5,                                   // PUSH HL
+            0xCD, lo(self.play_address), hi(self.play_address), // CALL play
+            0xE1,                                   // POP HL
+            0xD1,                                   // POP DE
+            0xC1,                                   // POP BC
+            0xF1,                                   // POP AF
+            RETI,
+        ];
+        write_instruction(&mut rom, PLAY_HANDLER, &play_handler);
+
+        write_instruction(
+            &mut rom,
+            0x100,
+            &[0x00, 0xC3, lo(ENTRY_POINT), hi(ENTRY_POINT)],
+        );
+        self.write_header(&mut rom);
+
+        #[rustfmt::skip]
+        let entry = [
+            0xF3,                                   // DI
+            0x31, lo(self.stack_pointer), hi(self.stack_pointer), // LD SP, stack_pointer
+            0x3E, 0x0A, 0xEA, 0x00, 0x00,           // Enable cartridge RAM
+            0xAF, 0xE0, 0x26,                       // Power cycle the APU
+            0x3E, 0x80, 0xE0, 0x26,
+            0x3E, 0x77, 0xE0, 0x24,                 // Maximum master volume
+            0x3E, 0xFF, 0xE0, 0x25,                 // Output every channel to both terminals
+            0x3E, modulo, 0xE0, 0x06,               // LDH (TMA), modulo
+            0xE0, 0x05,                             // LDH (TIMA), modulo
+            0x3E, control, 0xE0, 0x07,              // LDH (TAC), control
+            0x3E, track - 1,                        // LD A, track
+            0xCD, lo(self.init_address), hi(self.init_address), // CALL init
+            0x3E, interrupt_enable, 0xE0, 0xFF,     // LDH (IE), interrupt_enable
+            0xAF, 0xE0, 0x0F,                       // Clear IF
+            0xFB,                                   // EI
+            0x76,                                   // HALT
+            0x00,                                   // NOP
+            0x18, 0xFC,                             // JR -4
+        ];
+        write_instruction(&mut rom, ENTRY_POINT, &entry);
+
+        Ok(rom)
+    }
+
+    /// Writes a cartridge header describing an MBC1 cartridge with RAM.
+    fn write_header(&self, rom: &mut [u8]) {
+        for (dst, src) in rom[0x134..0x144].iter_mut().zip(self.title.bytes()) {
+            *dst = if src.is_ascii() { src } else { b'?' };
+        }
+
+        rom[0x147] = 0x03; // MBC1+RAM+BATTERY
+        rom[0x148] = (rom.len() / 0x8000).trailing_zeros() as u8;
+        rom[0x149] = 0x03; // 32KB
+
+        let mut checksum = Wrapping(0u8);
+        for byte in &rom[0x134..0x14D] {
+            checksum = checksum - Wrapping(*byte) - Wrapping(1);
+        }
+        rom[0x14D] = checksum.0;
+    }
+}
+
+/// The `RETI` opcode.
+const RETI: u8 = 0xD9;
+
+/// Assembles a `JP nn` instruction.
+fn jp(address: u16) -> Vec<u8> {
+    vec![0xC3, lo(address), hi(address)]
+}
+
+fn lo(word: u16) -> u8 {
+    word as u8
+}
+
+fn hi(word: u16) -> u8 {
+    (word >> 8) as u8
+}
+
+fn write_instruction(rom: &mut [u8], address: u16, instruction: &[u8]) {
+    let address = usize::from(address);
+    rom[address..address + instruction.len()].copy_from_slice(instruction);
+}
+
+/// Parses a null-padded header string.
+fn parse_string(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&c| c as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::Emulator;
+
+    use super::{Gbs, GbsError, PlayRate};
+
+    /// Builds a GBS file whose INIT routine stores the track index in 0xC000, and whose PLAY
+    /// routine increments 0xC001.
+    fn test_gbs(modulo: u8, control: u8) -> Vec<u8> {
+        let mut gbs = vec![0; 0x70];
+
+        gbs[0x00..0x04].copy_from_slice(b"GBS\x01");
+        gbs[0x04] = 3;
+        gbs[0x05] = 2;
+        gbs[0x06..0x08].copy_from_slice(&[0x00, 0x04]); // Load
+        gbs[0x08..0x0A].copy_from_slice(&[0x00, 0x04]); // Init
+        gbs[0x0A..0x0C].copy_from_slice(&[0x04, 0x04]); // Play
+        gbs[0x0C..0x0E].copy_from_slice(&[0xFE, 0xFF]); // Stack pointer
+        gbs[0x0E] = modulo;
+        gbs[0x0F] = control;
+        gbs[0x10..0x14].copy_from_slice(b"Test");
+        gbs[0x30..0x36].copy_from_slice(b"Author");
+
+        #[rustfmt::skip]
+        gbs.extend_from_slice(&[
+            // INIT
+            0xEA, 0x00, 0xC0, // LD (0xC000), A
+            0xC9,             // RET
+
+            // PLAY
+            0x21, 0x01, 0xC0, // LD HL, 0xC001
+            0x34,             // INC (HL)
+            0xC9,             // RET
+        ]);
+
+        gbs
+    }
+
+    fn play(gbs: &Gbs, track: u8) -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.load_gbs(gbs, track).unwrap();
+        emulator.reset();
+        emulator.update(Duration::from_secs(1)).unwrap();
+        emulator
+    }
+
+    #[test]
+    fn parse() {
+        let gbs = Gbs::parse(&test_gbs(0, 0)).unwrap();
+
+        assert_eq!(gbs.track_count, 3);
+        assert_eq!(gbs.first_track, 2);
+        assert_eq!(gbs.load_address, 0x400);
+        assert_eq!(gbs.play_address, 0x404);
+        assert_eq!(gbs.play_rate, PlayRate::VBlank);
+        assert_eq!(gbs.title, "Test");
+        assert_eq!(gbs.author, "Author");
+        assert_eq!(gbs.copyright, "");
+
+        assert!(matches!(Gbs::parse(b"GBX\x01"), Err(GbsError::InvalidSize)));
+
+        let mut invalid = test_gbs(0, 0);
+        invalid[0] = b'X';
+        assert!(matches!(
+            Gbs::parse(&invalid),
+            Err(GbsError::InvalidSignature)
+        ));
+
+        let mut invalid = test_gbs(0, 0);
+        invalid[0x07] = 0x00;
+        assert!(matches!(
+            Gbs::parse(&invalid),
+            Err(GbsError::InvalidLoadAddress(0x0000))
+        ));
+    }
+
+    #[test]
+    fn invalid_track() {
+        let gbs = Gbs::parse(&test_gbs(0, 0)).unwrap();
+
+        assert!(gbs.to_rom(0).is_err());
+        assert!(gbs.to_rom(4).is_err());
+    }
+
+    #[test]
+    fn vblank_play() {
+        let gbs = Gbs::parse(&test_gbs(0, 0)).unwrap();
+        let emulator = play(&gbs, 3);
+
+        assert_eq!(emulator.bus.read_byte_no_tick(0xC000), 2);
+
+        let plays = emulator.bus.read_byte_no_tick(0xC001);
+        assert!((58..=61).contains(&plays), "{}", plays);
+    }
+
+    #[test]
+    fn timer_play() {
+        // The timer increments at 4096 Hz, and overflows every 64 increments.
+        let gbs = Gbs::parse(&test_gbs(0xC0, 0x04)).unwrap();
+        assert_eq!(
+            gbs.play_rate,
+            PlayRate::Timer {
+                modulo: 0xC0,
+                control: 0x04
+            }
+        );
+
+        let emulator = play(&gbs, 1);
+
+        assert_eq!(emulator.bus.read_byte_no_tick(0xC000), 0);
+
+        let plays = emulator.bus.read_byte_no_tick(0xC001);
+        assert!((63..=65).contains(&plays), "{}", plays);
+    }
+}
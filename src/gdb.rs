@@ -0,0 +1,668 @@
+//! A stub for the GDB remote serial protocol.
+//!
+//! Allows GDB, or any other frontend that speaks its [protocol], to debug the emulator over TCP.
+//! While GDB is attached, it takes the place of the debugger prompt: execution is paused and
+//! resumed by GDB alone.
+//!
+//! The CPU is described to GDB as having six 16-bit registers: `af`, `bc`, `de`, `hl`, `sp` and
+//! `pc`. Software and hardware breakpoints are treated the same, and read and write watchpoints
+//! are supported.
+//!
+//! [protocol]: https://sourceware.org/gdb/current/onlinedocs/gdb/Remote-Protocol.html
+
+use std::fmt::Write as _;
+use std::io::{self, ErrorKind, Read, Write};
+use std::mem;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use anyhow::{anyhow, Context, Result};
+use log::*;
+
+use crate::bus::{WatchKind, Watchpoint};
+use crate::debugger::{BankedAddress, Breakpoint, DebugCommand};
+use crate::Emulator;
+
+/// The registers reported to GDB, in order.
+const REGISTERS: [&str; 6] = ["af", "bc", "de", "hl", "sp", "pc"];
+
+/// The description of the registers that is sent to GDB.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.feo-boy.sm83">
+    <reg name="af" bitsize="16" type="int"/>
+    <reg name="bc" bitsize="16" type="int"/>
+    <reg name="de" bitsize="16" type="int"/>
+    <reg name="hl" bitsize="16" type="data_ptr"/>
+    <reg name="sp" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// The byte that GDB sends to interrupt execution.
+const INTERRUPT: u8 = 0x03;
+
+/// The signal reported when execution stops at a breakpoint or watchpoint, or after a step.
+const SIGTRAP: u8 = 5;
+
+/// The signal reported when execution stops because GDB interrupted it.
+const SIGINT: u8 = 2;
+
+/// A connection to GDB.
+#[derive(Debug)]
+pub struct GdbStub {
+    stream: TcpStream,
+
+    /// Bytes received from GDB that have not been handled yet.
+    received: Vec<u8>,
+
+    /// Whether packets must be acknowledged. GDB may turn acknowledgements off.
+    ack: bool,
+
+    /// Whether GDB resumed execution and is waiting for it to stop.
+    running: bool,
+
+    /// Whether GDB asked to interrupt execution.
+    interrupted: bool,
+}
+
+/// The state of GDB after the stub handled the packets that were ready.
+#[derive(Debug, PartialEq, Eq)]
+enum Status {
+    /// GDB has not sent a complete packet yet.
+    Waiting,
+
+    /// GDB resumed execution.
+    Resumed,
+
+    /// GDB detached, or killed the emulator.
+    Detached,
+}
+
+impl GdbStub {
+    /// Listens on an address, and waits for GDB to connect.
+    pub fn listen(address: impl ToSocketAddrs) -> Result<GdbStub> {
+        let listener = TcpListener::bind(address).context("could not listen for GDB")?;
+        info!("waiting for GDB to connect on {}", listener.local_addr()?);
+
+        let (stream, peer) = listener
+            .accept()
+            .context("could not accept GDB connection")?;
+        info!("GDB connected from {}", peer);
+
+        GdbStub::new(stream)
+    }
+
+    fn new(stream: TcpStream) -> Result<GdbStub> {
+        stream.set_nodelay(true)?;
+
+        Ok(GdbStub {
+            stream,
+            received: vec![],
+            ack: true,
+            running: false,
+            interrupted: false,
+        })
+    }
+
+    /// Reports why execution stopped, if GDB is waiting for it, then handles the packets that
+    /// have been received, without blocking, until GDB resumes execution or detaches.
+    fn serve(&mut self, emulator: &mut Emulator) -> io::Result<Status> {
+        if mem::take(&mut self.running) {
+            let signal = if mem::take(&mut self.interrupted) {
+                SIGINT
+            } else {
+                SIGTRAP
+            };
+
+            let reply = stop_reply(emulator, signal);
+            self.send(&reply)?;
+        }
+
+        self.receive()?;
+
+        while let Some(packet) = self.next_packet()? {
+            if packet == "QStartNoAckMode" {
+                self.send("OK")?;
+                self.ack = false;
+                continue;
+            }
+
+            match handle_packet(emulator, &packet) {
+                Response::Reply(reply) => self.send(&reply)?,
+                Response::Resume => {
+                    self.running = true;
+                    return Ok(Status::Resumed);
+                }
+                Response::Detach => {
+                    self.send("OK")?;
+                    emulator.resume();
+                    return Ok(Status::Detached);
+                }
+                Response::Kill => {
+                    // The debugger is enabled while GDB is attached, so quitting cannot fail.
+                    let _ = emulator.execute(DebugCommand::Quit);
+                    return Ok(Status::Detached);
+                }
+            }
+        }
+
+        Ok(Status::Waiting)
+    }
+
+    /// Checks whether GDB asked to interrupt execution while it was running, without blocking.
+    fn poll_interrupt(&mut self) -> io::Result<bool> {
+        if !self.running || self.interrupted {
+            return Ok(self.interrupted);
+        }
+
+        self.receive()?;
+
+        if let Some(position) = self.received.iter().position(|&byte| byte == INTERRUPT) {
+            self.received.drain(..=position);
+            self.interrupted = true;
+        }
+
+        Ok(self.interrupted)
+    }
+
+    /// Reads the bytes that GDB has sent, without waiting for more.
+    fn receive(&mut self) -> io::Result<()> {
+        self.stream.set_nonblocking(true)?;
+
+        let mut buffer = [0; 1024];
+        let result = loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => break Err(ErrorKind::UnexpectedEof.into()),
+                Ok(n) => self.received.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => break Err(e),
+            }
+        };
+
+        self.stream.set_nonblocking(false)?;
+        result
+    }
+
+    /// Takes the next complete packet from the received bytes, and returns its data. Returns
+    /// `None` if no complete packet has been received.
+    fn next_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Skip acknowledgements, and interrupts that arrive after execution already stopped.
+            // Packets are sent over TCP, so they never need to be resent.
+            let start = match self.received.iter().position(|&byte| byte == b'$') {
+                Some(start) => start,
+                None => {
+                    self.received.clear();
+                    return Ok(None);
+                }
+            };
+            self.received.drain(..start);
+
+            let end = match self.received.iter().position(|&byte| byte == b'#') {
+                Some(end) if end + 2 < self.received.len() => end,
+                _ => return Ok(None),
+            };
+
+            let packet = self.received.drain(..end + 3).collect::<Vec<_>>();
+            let data = &packet[1..end];
+            let valid = std::str::from_utf8(&packet[end + 1..])
+                .ok()
+                .and_then(|expected| u8::from_str_radix(expected, 16).ok())
+                == Some(checksum(data));
+
+            if self.ack {
+                let ack: &[u8] = if valid { b"+" } else { b"-" };
+                self.stream.write_all(ack)?;
+            }
+
+            if valid {
+                let packet = String::from_utf8_lossy(data).into_owned();
+                trace!("gdb <- {}", packet);
+                return Ok(Some(packet));
+            }
+
+            warn!("discarding GDB packet with invalid checksum");
+        }
+    }
+
+    /// Sends a packet containing `data`.
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        trace!("gdb -> {}", data);
+
+        let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())
+    }
+}
+
+/// Hands control of the paused emulator to GDB, and handles the packets that GDB has sent.
+/// Returns `false` if GDB is waiting for input.
+///
+/// If GDB detaches or disconnects, control returns to the debugger prompt.
+pub(crate) fn serve(emulator: &mut Emulator) -> bool {
+    let mut stub = match emulator.debug.as_mut().and_then(|d| d.gdb.take()) {
+        Some(stub) => stub,
+        None => return false,
+    };
+
+    match stub.serve(emulator) {
+        Ok(Status::Waiting) => {
+            emulator.debug.as_mut().unwrap().gdb = Some(stub);
+            return false;
+        }
+        Ok(Status::Resumed) => emulator.debug.as_mut().unwrap().gdb = Some(stub),
+        Ok(Status::Detached) => info!("GDB detached"),
+        Err(e) => warn!("GDB disconnected: {}", e),
+    }
+
+    true
+}
+
+/// Pauses the emulator if GDB asked to interrupt execution.
+pub(crate) fn poll(emulator: &mut Emulator) {
+    let debugger = match &mut emulator.debug {
+        Some(debugger) => debugger,
+        None => return,
+    };
+
+    if let Some(stub) = &mut debugger.gdb {
+        match stub.poll_interrupt() {
+            Ok(true) => {
+                debugger.paused = true;
+                debugger.run_until = None;
+            }
+            Ok(false) => (),
+            Err(e) => {
+                warn!("GDB disconnected: {}", e);
+                debugger.gdb = None;
+            }
+        }
+    }
+}
+
+/// The action taken after handling a packet.
+#[derive(Debug, PartialEq, Eq)]
+enum Response {
+    /// Reply with a packet.
+    Reply(String),
+
+    /// Resume execution, and reply once it stops.
+    Resume,
+
+    /// Stop debugging and resume execution.
+    Detach,
+
+    /// Stop debugging and quit.
+    Kill,
+}
+
+/// Handles a packet from GDB. Packets that fail to parse receive an error reply, and packets that
+/// are not supported receive an empty reply.
+fn handle_packet(emulator: &mut Emulator, packet: &str) -> Response {
+    match handle_command(emulator, packet) {
+        Ok(response) => response,
+        Err(e) => {
+            debug!("could not handle GDB packet '{}': {}", packet, e);
+            Response::Reply(String::from("E01"))
+        }
+    }
+}
+
+fn handle_command(emulator: &mut Emulator, packet: &str) -> Result<Response> {
+    let args = packet.get(1..).unwrap_or_default();
+
+    let reply = match packet.chars().next() {
+        Some('?') => stop_reply(emulator, SIGTRAP),
+        Some('g') => REGISTERS
+            .iter()
+            .map(|name| encode_word(read_register(emulator, name)))
+            .collect(),
+        Some('G') => {
+            let words = decode_bytes(args)?;
+            if words.len() != REGISTERS.len() * 2 {
+                return Err(anyhow!("expected {} registers", REGISTERS.len()));
+            }
+
+            for (name, word) in REGISTERS.iter().zip(words.chunks(2)) {
+                let value = u16::from_le_bytes([word[0], word[1]]);
+                emulator.set_register(name, value.into())?;
+            }
+
+            String::from("OK")
+        }
+        Some('p') => encode_word(read_register(emulator, register(args)?)),
+        Some('P') => {
+            let (index, value) = args
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expected a register and a value"))?;
+            let value = match decode_bytes(value)?[..] {
+                [low, high] => u16::from_le_bytes([low, high]),
+                _ => return Err(anyhow!("expected a 16-bit value")),
+            };
+
+            emulator.set_register(register(index)?, value.into())?;
+            String::from("OK")
+        }
+        Some('m') => {
+            let (address, length) = parse_range(args)?;
+
+            let mut reply = String::new();
+            for offset in 0..length {
+                let byte = emulator.bus.read_byte_no_tick(address.wrapping_add(offset));
+                write!(reply, "{:02x}", byte).unwrap();
+            }
+            reply
+        }
+        Some('M') => {
+            let (range, data) = args
+                .split_once(':')
+                .ok_or_else(|| anyhow!("expected a range and data"))?;
+            let (address, length) = parse_range(range)?;
+            let bytes = decode_bytes(data)?;
+            if bytes.len() != usize::from(length) {
+                return Err(anyhow!("expected {} bytes", length));
+            }
+
+            for (offset, byte) in (0..).zip(bytes) {
+                emulator
+                    .bus
+                    .write_byte_no_tick(address.wrapping_add(offset), byte);
+            }
+
+            String::from("OK")
+        }
+        Some('c') => {
+            if !args.is_empty() {
+                emulator.cpu.reg.pc = parse_hex(args)?;
+            }
+
+            emulator.resume();
+            return Ok(Response::Resume);
+        }
+        Some('s') => {
+            if !args.is_empty() {
+                emulator.cpu.reg.pc = parse_hex(args)?;
+            }
+
+            emulator.step();
+            stop_reply(emulator, SIGTRAP)
+        }
+        Some(command @ ('Z' | 'z')) => set_breakpoint(emulator, command == 'Z', args)?,
+        Some('D') => return Ok(Response::Detach),
+        Some('k') => return Ok(Response::Kill),
+        Some('H') => String::from("OK"),
+        Some('q') => query(packet)?,
+        _ => String::new(),
+    };
+
+    Ok(Response::Reply(reply))
+}
+
+/// Handles a general query. Only queries that are needed to connect are supported.
+fn query(packet: &str) -> Result<String> {
+    if packet.starts_with("qSupported") {
+        return Ok(String::from(
+            "PacketSize=4000;qXfer:features:read+;QStartNoAckMode+",
+        ));
+    }
+
+    if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+        let (offset, length) = range
+            .split_once(',')
+            .ok_or_else(|| anyhow!("expected an offset and a length"))?;
+        let offset = usize::from(parse_hex(offset)?).min(TARGET_XML.len());
+        let end = offset
+            .saturating_add(parse_hex(length)?.into())
+            .min(TARGET_XML.len());
+
+        let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+        return Ok(format!("{}{}", marker, &TARGET_XML[offset..end]));
+    }
+
+    let reply = match packet {
+        "qAttached" => "1",
+        "qC" => "QC1",
+        "qfThreadInfo" => "m1",
+        "qsThreadInfo" => "l",
+        _ => "",
+    };
+
+    Ok(String::from(reply))
+}
+
+/// Inserts or removes a breakpoint or watchpoint, from the arguments of a `Z` or `z` packet.
+fn set_breakpoint(emulator: &mut Emulator, insert: bool, args: &str) -> Result<String> {
+    let mut fields = args.splitn(3, ',');
+    let kind = fields.next().unwrap_or_default();
+    let address = parse_hex(fields.next().unwrap_or_default())?;
+    let length = parse_hex(fields.next().unwrap_or_default())?;
+
+    match kind {
+        "0" | "1" if insert => emulator.add_breakpoint(Breakpoint::new(address)),
+        "0" | "1" => {
+            emulator.remove_breakpoint(BankedAddress::new(address));
+        }
+        "2" | "3" => {
+            let kind = if kind == "2" {
+                WatchKind::Write
+            } else {
+                WatchKind::Read
+            };
+            let end = address.saturating_add(length.max(1) - 1);
+            let watchpoint = Watchpoint::new(address..=end, kind);
+
+            if insert {
+                emulator.add_watchpoint(watchpoint);
+            } else if let Some(index) = emulator
+                .watchpoints()
+                .iter()
+                .position(|existing| *existing == watchpoint)
+            {
+                emulator.remove_watchpoint(index);
+            }
+        }
+
+        // Access watchpoints are not supported.
+        _ => return Ok(String::new()),
+    }
+
+    Ok(String::from("OK"))
+}
+
+/// The reply that describes why execution stopped.
+fn stop_reply(emulator: &mut Emulator, signal: u8) -> String {
+    let hit = emulator.debug.as_mut().and_then(|d| d.watch_hit.take());
+
+    match hit {
+        Some((_, _, hit)) => {
+            let kind = match hit.watchpoint.kind {
+                WatchKind::Read => "rwatch",
+                WatchKind::Write | WatchKind::Change => "watch",
+            };
+
+            format!("T{:02x}{}:{:x};", SIGTRAP, kind, hit.address)
+        }
+        None => format!("S{:02x}", signal),
+    }
+}
+
+fn read_register(emulator: &Emulator, name: &str) -> u16 {
+    let reg = &emulator.cpu.reg;
+
+    match name {
+        "af" => reg.af(),
+        "bc" => reg.bc(),
+        "de" => reg.de(),
+        "hl" => reg.hl(),
+        "sp" => reg.sp,
+        "pc" => reg.pc,
+        _ => unreachable!("unknown register {}", name),
+    }
+}
+
+/// Returns the name of a register from its index in hexadecimal.
+fn register(index: &str) -> Result<&'static str> {
+    let index = usize::from_str_radix(index, 16).context("could not parse register")?;
+
+    REGISTERS
+        .get(index)
+        .copied()
+        .ok_or_else(|| anyhow!("unknown register {}", index))
+}
+
+/// Parses the `address,length` arguments of a memory packet.
+fn parse_range(range: &str) -> Result<(u16, u16)> {
+    let (address, length) = range
+        .split_once(',')
+        .ok_or_else(|| anyhow!("expected an address and a length"))?;
+
+    Ok((parse_hex(address)?, parse_hex(length)?))
+}
+
+fn parse_hex(number: &str) -> Result<u16> {
+    u16::from_str_radix(number, 16).with_context(|| format!("could not parse '{}'", number))
+}
+
+/// Encodes a word as hexadecimal, in target byte order.
+fn encode_word(word: u16) -> String {
+    let [low, high] = word.to_le_bytes();
+    format!("{:02x}{:02x}", low, high)
+}
+
+fn decode_bytes(hex: &str) -> Result<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| anyhow!("expected pairs of hexadecimal digits"))
+        })
+        .collect()
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    use crate::bus::{WatchKind, Watchpoint};
+    use crate::Emulator;
+
+    use super::{handle_packet, GdbStub, Response};
+
+    fn reply(emulator: &mut Emulator, packet: &str) -> String {
+        match handle_packet(emulator, packet) {
+            Response::Reply(reply) => reply,
+            response => panic!("unexpected response {:?}", response),
+        }
+    }
+
+    #[test]
+    fn registers() {
+        let mut emulator = Emulator::builder().with_debug().build();
+        emulator.cpu.reg.pc = 0x0150;
+        emulator.cpu.reg.sp = 0xFFFE;
+
+        let registers = reply(&mut emulator, "g");
+        assert_eq!(&registers[16..], "feff5001");
+
+        assert_eq!(reply(&mut emulator, "G3001120034005600feff0002"), "OK");
+        assert_eq!(emulator.cpu.reg.af(), 0x0130);
+        assert_eq!(emulator.cpu.reg.hl(), 0x0056);
+        assert_eq!(emulator.cpu.reg.pc, 0x0200);
+
+        assert_eq!(reply(&mut emulator, "P3=10c0"), "OK");
+        assert_eq!(reply(&mut emulator, "p3"), "10c0");
+        assert_eq!(reply(&mut emulator, "p9"), "E01");
+    }
+
+    #[test]
+    fn memory() {
+        let mut emulator = Emulator::builder().with_debug().build();
+
+        assert_eq!(reply(&mut emulator, "Mc000,3:3e3cc9"), "OK");
+        assert_eq!(emulator.bus.read_byte_no_tick(0xC002), 0xC9);
+        assert_eq!(reply(&mut emulator, "mc001,2"), "3cc9");
+        assert_eq!(reply(&mut emulator, "Mc000,2:3e"), "E01");
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut emulator = Emulator::builder().with_debug().build();
+        emulator.cpu.reg.pc = 0xC000;
+        for (offset, byte) in [0x00, 0xEA, 0x00, 0xC1].iter().enumerate() {
+            emulator
+                .bus
+                .write_byte_no_tick(0xC000 + offset as u16, *byte);
+        }
+
+        assert_eq!(reply(&mut emulator, "Z0,c001,1"), "OK");
+        assert_eq!(emulator.breakpoints()[0].address, 0xC001);
+        assert_eq!(reply(&mut emulator, "s"), "S05");
+        assert_eq!(reply(&mut emulator, "z0,c001,1"), "OK");
+        assert!(emulator.breakpoints().is_empty());
+
+        assert_eq!(reply(&mut emulator, "Z2,c100,2"), "OK");
+        assert_eq!(
+            emulator.watchpoints(),
+            [Watchpoint::new(0xC100..=0xC101, WatchKind::Write)]
+        );
+        assert_eq!(reply(&mut emulator, "s"), "T05watch:c100;");
+        assert_eq!(reply(&mut emulator, "z2,c100,2"), "OK");
+        assert!(emulator.watchpoints().is_empty());
+
+        assert_eq!(reply(&mut emulator, "Z4,c100,1"), "");
+        assert_eq!(handle_packet(&mut emulator, "c"), Response::Resume);
+        assert!(!emulator.is_paused());
+    }
+
+    #[test]
+    fn target_description() {
+        let mut emulator = Emulator::builder().with_debug().build();
+
+        let first = reply(&mut emulator, "qXfer:features:read:target.xml:0,b");
+        assert_eq!(first, "m<?xml versi");
+
+        let rest = reply(&mut emulator, "qXfer:features:read:target.xml:b,1000");
+        assert!(rest.starts_with("lon=\"1.0\"?>"));
+        assert!(rest.ends_with("</target>\n"));
+    }
+
+    fn next_packet(stub: &mut GdbStub) -> String {
+        loop {
+            stub.receive().unwrap();
+            if let Some(packet) = stub.next_packet().unwrap() {
+                return packet;
+            }
+        }
+    }
+
+    #[test]
+    fn packets() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut gdb = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut stub = GdbStub::new(listener.accept().unwrap().0).unwrap();
+
+        gdb.write_all(b"+$m0,1#fa$bad#00\x03$g").unwrap();
+        assert_eq!(next_packet(&mut stub), "m0,1");
+        assert_eq!(stub.next_packet().unwrap(), None);
+
+        gdb.write_all(b"#67").unwrap();
+        assert_eq!(next_packet(&mut stub), "g");
+
+        stub.send("OK").unwrap();
+        drop(stub);
+
+        let mut received = String::new();
+        gdb.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "+-+$OK#9a");
+    }
+}
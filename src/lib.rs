@@ -7,22 +7,27 @@ pub mod audio;
 pub mod bus;
 pub mod bytes;
 pub mod cpu;
+pub mod debugger;
+pub mod gbs;
+pub mod gdb;
 pub mod graphics;
 pub mod input;
 pub mod memory;
 pub mod tui;
 
-use std::collections::HashSet;
+#[cfg(test)]
+mod test_util;
+
 use std::fmt::Debug;
-use std::io::Write;
-use std::process;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::thread;
 use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use log::*;
 use pixels::{Pixels, SurfaceTexture};
-use rustyline::error::ReadlineError;
-use rustyline::Editor;
 use winit::dpi::LogicalSize;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::EventLoop;
@@ -30,9 +35,16 @@ use winit::keyboard::KeyCode;
 use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
 
-use crate::audio::SoundController;
-use crate::bus::Bus;
-use crate::cpu::{Cpu, Instruction, MCycles, TCycles};
+use crate::audio::{AudioSink, AudioStats, SoundController, WriteSeek};
+use crate::bus::{Bus, CodeDataLog, Watchpoint};
+use crate::cpu::{Cpu, Instruction, MCycles, State, TCycles};
+use crate::debugger::{
+    BankedAddress, Breakpoint, DebugCommand, DebugFrontend, DebugResponse, Debugger, Disassembly,
+    MemoryRange, MemorySearch, Profiler, RunUntil, SearchFilter, SymbolTable, TraceChecker,
+    TraceComparison, TraceOptions, Tracer,
+};
+use crate::gbs::Gbs;
+use crate::gdb::GdbStub;
 use crate::graphics::Ppu;
 use crate::memory::Mmu;
 
@@ -46,6 +58,27 @@ pub use crate::input::Button;
 /// See also [`crate::cpu::Frequency`].
 const CYCLE_DURATION: Duration = Duration::from_nanos(238);
 
+/// The most time that may be emulated in a single update when pacing emulation to the audio
+/// device. Prevents the window from becoming unresponsive if the audio device stalls.
+const MAX_AUDIO_UPDATE: Duration = Duration::from_millis(100);
+
+/// The time between updates when running without a window, which is about the length of a frame.
+const HEADLESS_UPDATE_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Determines how much time is emulated by each call to [`Emulator::update`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Emulate the amount of time that has passed on the host's clock.
+    #[default]
+    Clock,
+
+    /// Emulate just enough to keep the audio device's queue filled to the target latency.
+    ///
+    /// This avoids audio crackles caused by the host clock and the audio device drifting apart.
+    /// Falls back to [`SyncMode::Clock`] if audio is not being played.
+    Audio,
+}
+
 /// The emulator itself. Contains all components required to emulate the Game Boy.
 #[derive(Debug)]
 pub struct Emulator {
@@ -56,6 +89,26 @@ pub struct Emulator {
     pub bus: Bus,
 
     debug: Option<Debugger>,
+
+    /// Labels for addresses in the loaded ROM.
+    symbols: SymbolTable,
+
+    /// Writes a line for every executed instruction. `None` if execution is not being traced.
+    trace: Option<Tracer>,
+
+    /// Compares execution against a reference trace. `None` if no comparison is running.
+    trace_check: Option<TraceChecker>,
+
+    /// Counts where execution time is spent. `None` if execution is not being profiled.
+    profiler: Option<Profiler>,
+
+    /// The search of RAM in progress, if any.
+    search: Option<MemorySearch>,
+
+    /// The file that the code/data log is saved to. `None` if ROM accesses are not being logged.
+    cdl_path: Option<PathBuf>,
+
+    sync_mode: SyncMode,
 }
 
 impl Emulator {
@@ -141,6 +194,19 @@ impl Emulator {
         Ok(())
     }
 
+    /// Load a track of a GBS music file into the emulator. Tracks start at 1.
+    ///
+    /// The emulator must be reset to start playing the track. Another track may be selected by
+    /// loading it and resetting again.
+    pub fn load_gbs(&mut self, gbs: &Gbs, track: u8) -> Result<()> {
+        let rom = gbs.to_rom(track)?;
+        self.bus.mmu.load_rom(&rom)?;
+
+        info!("loaded GBS track {} of {}", track, gbs.track_count);
+
+        Ok(())
+    }
+
     /// Open a graphical window and start execution of the emulator.
     pub fn run(mut self) -> Result<()> {
         let event_loop = EventLoop::new()?;
@@ -168,6 +234,12 @@ impl Emulator {
         let mut last_update = Instant::now();
 
         event_loop.run(move |event, elwt| {
+            if let Event::LoopExiting = event {
+                if let Err(e) = self.save_code_data_log() {
+                    error!("{:#}", e);
+                }
+            }
+
             if let Event::WindowEvent { event: WindowEvent::RedrawRequested, .. } = event {
                 self.render(pixels.frame_mut());
 
@@ -200,6 +272,10 @@ impl Emulator {
                     error!("unable to update emulator state: {}", e);
                     elwt.exit();
                 }
+
+                if self.quit_requested() {
+                    elwt.exit();
+                }
                 last_update = current_time;
                 window.request_redraw();
             }
@@ -208,6 +284,26 @@ impl Emulator {
         Ok(())
     }
 
+    /// Start execution of the emulator without a window, until a debugger frontend quits.
+    ///
+    /// Nothing is displayed, but the debugger, audio and serial output work as usual. Useful on
+    /// machines without a display, such as over SSH.
+    pub fn run_headless(mut self) -> Result<()> {
+        self.reset();
+
+        let mut last_update = Instant::now();
+
+        while !self.quit_requested() {
+            thread::sleep(HEADLESS_UPDATE_INTERVAL);
+
+            let current_time = Instant::now();
+            self.update(current_time - last_update)?;
+            last_update = current_time;
+        }
+
+        self.save_code_data_log()
+    }
+
     fn handle_keys(&mut self, input: &WinitInputHelper) {
         macro_rules! button_mapping {
             ( $( $winit_key:expr => $feo_boy_key:expr),+ $(,)? ) => {{
@@ -248,6 +344,31 @@ impl Emulator {
         self.cpu.handle_interrupts(&mut self.bus);
         cycles += self.bus.timer.diff();
 
+        if let (Some(tracer), State::Running) = (&mut self.trace, &self.cpu.state) {
+            if let Err(e) = tracer.trace(&self.cpu, &self.bus, TCycles::from(cycles)) {
+                error!("unable to write trace, stopping: {}", e);
+                self.trace = None;
+            }
+        }
+
+        if let (Some(checker), State::Running) = (&mut self.trace_check, &self.cpu.state) {
+            checker.check(&self.cpu, &self.bus, TCycles::from(cycles));
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            let location = BankedAddress::resolve(self.cpu.reg.pc, &self.bus.mmu);
+            let executed = matches!(self.cpu.state, State::Running);
+            profiler.begin(location, &self.cpu.call_stack, executed);
+        }
+
+        // Remember the instruction for the debugger, in case it triggers a watchpoint or ends a
+        // step.
+        let instruction = if self.debug.is_some() {
+            Some(self.current_instruction())
+        } else {
+            None
+        };
+
         // FIXME: Hack: the cycle timing debug assert at the end of Cpu::execute is dependent on
         // this state, but it shouldn't be.
         self.bus.timer.reset_diff();
@@ -255,13 +376,39 @@ impl Emulator {
         self.cpu.step(&mut self.bus);
         cycles += self.bus.timer.diff();
 
-        self.bus.audio.step(cycles.into());
-
         if let Some(ref mut debugger) = self.debug {
-            let pc = self.cpu.reg.pc;
-            if debugger.breakpoints.contains(&pc) {
+            if debugger.check_breakpoints(&self.cpu, &self.bus) {
+                debugger.paused = true;
+            }
+
+            let (address, instruction) = instruction.unwrap();
+
+            if let Some(run_until) = debugger.run_until {
+                if run_until.is_reached(&instruction, &self.cpu, &self.bus) {
+                    debugger.paused = true;
+                }
+            }
+
+            if let Some(hit) = self.bus.watchpoints.take_hit() {
                 debugger.paused = true;
+                debugger.watch_hit = Some((address, instruction, hit));
             }
+
+            if debugger.paused {
+                debugger.run_until = None;
+            }
+        }
+
+        if let Some(tracer) = &mut self.trace {
+            tracer.advance(TCycles::from(cycles));
+        }
+
+        if let Some(checker) = &mut self.trace_check {
+            checker.advance(TCycles::from(cycles));
+        }
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.end(TCycles::from(cycles));
         }
 
         TCycles::from(cycles)
@@ -277,28 +424,42 @@ impl Emulator {
 
     /// Step the emulation state for the given time in seconds.
     ///
-    /// If the debugger is enabled, debug commands will be read from stdin.
+    /// If the emulator is synchronized to audio, the time is ignored, and the emulator instead
+    /// runs until enough audio has been generated. See [`SyncMode`].
+    ///
+    /// If the debugger is enabled and execution is paused, the commands that are ready are read
+    /// from the debugger frontend, or from GDB if it is attached. Returns early if the frontend is
+    /// waiting for input. The frontend is also polled once while execution is running.
     pub fn update(&mut self, dt: Duration) -> Result<()> {
-        let cycles_to_execute = TCycles((dt.as_nanos() / CYCLE_DURATION.as_nanos()) as u32);
+        gdb::poll(self);
+
+        if self.debug.is_some() && !self.is_paused() {
+            // Give the frontend a chance to refresh its display, or to pause execution.
+            self.poll_frontend();
+        }
+
+        let cycles_to_execute = match (self.sync_mode, self.bus.audio.cycles_until_buffered()) {
+            (SyncMode::Audio, Some(cycles)) => {
+                let max_cycles = MAX_AUDIO_UPDATE.as_nanos() / CYCLE_DURATION.as_nanos();
+                TCycles(cycles.0.min(max_cycles as u32))
+            }
+            _ => TCycles((dt.as_nanos() / CYCLE_DURATION.as_nanos()) as u32),
+        };
 
         let mut cycles_executed = TCycles(0);
 
         while cycles_executed < cycles_to_execute {
             if self.is_paused() {
-                let readline = {
-                    let editor = &mut self.debug.as_mut().unwrap().editor;
-                    let prompt = format!("feo debug [{}] >> ", tui::COMMANDS);
-                    editor.readline(&prompt)
-                };
-
-                match readline {
-                    Ok(line) => {
-                        self.debug.as_mut().unwrap().editor.add_history_entry(&line);
-                        // FIXME: Don't propagate this error.
-                        tui::parse_command(self, line.trim())?
+                if self.debug.as_ref().unwrap().gdb.is_some() {
+                    if !gdb::serve(self) || self.quit_requested() {
+                        break;
                     }
-                    Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => process::exit(0),
-                    Err(err) => panic!("{}", err),
+
+                    continue;
+                }
+
+                if !self.poll_frontend() || self.quit_requested() {
+                    break;
                 }
             } else {
                 cycles_executed += self.step();
@@ -308,6 +469,92 @@ impl Emulator {
         Ok(())
     }
 
+    /// Run the emulator without a window, comparing the state before each instruction against a
+    /// reference trace, until the reference ends or execution diverges from it.
+    ///
+    /// The reference is in the format written by [`debugger::Tracer`]. When execution diverges,
+    /// the last `history` matching lines are included in the result.
+    pub fn compare_trace(
+        &mut self,
+        reference: impl BufRead + 'static,
+        history: usize,
+    ) -> Result<TraceComparison> {
+        self.trace_check = Some(TraceChecker::new(reference, history));
+
+        let mut lines_checked = 0;
+        let mut idle_cycles = TCycles(0);
+
+        loop {
+            idle_cycles += self.step();
+
+            let checker = self.trace_check.as_mut().unwrap();
+            if let Some(result) = checker.take_result() {
+                self.trace_check = None;
+                return Ok(result?);
+            }
+
+            if checker.lines_checked() > lines_checked {
+                lines_checked = checker.lines_checked();
+                idle_cycles = TCycles(0);
+            } else if idle_cycles > TCycles(cpu::FREQUENCY) {
+                self.trace_check = None;
+                bail!(
+                    "execution stopped after line {} of the reference trace",
+                    lines_checked
+                );
+            }
+        }
+    }
+
+    /// Executes the next queued command, or the next command from the debugger frontend if one is
+    /// ready, and presents the response. Returns `false` if the frontend is waiting for input.
+    fn poll_frontend(&mut self) -> bool {
+        let debugger = self.debug.as_mut().unwrap();
+        let mut frontend = match debugger.frontend.take() {
+            Some(frontend) => frontend,
+            None => return false,
+        };
+
+        if let Some((address, instruction, hit)) = debugger.watch_hit.take() {
+            let address = BankedAddress::resolve(address, &self.bus.mmu);
+            frontend.respond(Ok(DebugResponse::WatchHit {
+                hit,
+                location: self.symbols.describe(address),
+                instruction,
+            }));
+        }
+
+        let queued = debugger.queued.pop_front();
+        let command = queued.or_else(|| frontend.poll_command(self));
+        let polled = command.is_some();
+
+        if let Some(command) = command {
+            let response = self.execute(command);
+            frontend.respond(response);
+        }
+
+        self.debug.as_mut().unwrap().frontend = Some(frontend);
+        polled
+    }
+
+    /// Execute a debugger command. Commands are ignored if the debugger is not enabled.
+    pub fn execute(&mut self, command: DebugCommand) -> Result<DebugResponse> {
+        debugger::execute(self, command)
+    }
+
+    /// Whether a debugger frontend has asked for the emulator to stop.
+    pub fn quit_requested(&self) -> bool {
+        self.debug.as_ref().is_some_and(|d| d.quit)
+    }
+
+    /// Pause execution, cancelling any step that is in progress.
+    pub fn pause(&mut self) {
+        if let Some(ref mut debugger) = self.debug {
+            debugger.paused = true;
+            debugger.run_until = None;
+        }
+    }
+
     /// Resume execution after pausing.
     pub fn resume(&mut self) {
         if let Some(ref mut debugger) = self.debug {
@@ -315,23 +562,135 @@ impl Emulator {
         }
     }
 
+    /// Execute the current instruction. If it is a call, resume execution until the call returns.
+    pub fn step_over(&mut self) {
+        let (address, instruction) = self.current_instruction();
+
+        if instruction.is_call() {
+            self.run_until(RunUntil::Return {
+                address: address.wrapping_add(instruction.size()),
+                sp: self.cpu.reg.sp,
+            });
+        } else {
+            self.step();
+        }
+    }
+
+    /// Resume execution until the current function returns to its caller.
+    pub fn step_out(&mut self) {
+        self.run_until(RunUntil::FrameExit {
+            sp: self.cpu.reg.sp,
+        });
+    }
+
+    /// Resume execution until the program counter reaches a location.
+    pub fn run_to(&mut self, location: BankedAddress) {
+        self.run_until(RunUntil::Address(location));
+    }
+
+    fn run_until(&mut self, run_until: RunUntil) {
+        if let Some(ref mut debugger) = self.debug {
+            debugger.run_until = Some(run_until);
+            debugger.paused = false;
+        }
+    }
+
     /// Whether the emulator is paused.
     pub fn is_paused(&self) -> bool {
         self.debug.as_ref().map_or(false, |d| d.paused)
     }
 
-    /// Insert a breakpoint at a given memory address.
-    pub fn add_breakpoint(&mut self, breakpoint: u16) {
+    /// Insert a breakpoint, replacing any existing breakpoint at the same address and bank.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
         if let Some(ref mut debugger) = self.debug {
-            debugger.breakpoints.insert(breakpoint);
+            debugger
+                .breakpoints
+                .insert(breakpoint.location(), breakpoint);
         }
     }
 
-    /// Return a list of active breakpoints.
-    pub fn breakpoints(&self) -> Vec<u16> {
+    /// Remove the breakpoint at the given location, if there is one.
+    pub fn remove_breakpoint(&mut self, location: BankedAddress) -> Option<Breakpoint> {
         self.debug
+            .as_mut()
+            .and_then(|d| d.breakpoints.remove(&location))
+    }
+
+    /// Set the number of upcoming hits of the breakpoint at the given location that are skipped
+    /// before pausing, replacing any that are left. Returns `false` if there is no breakpoint
+    /// there.
+    pub fn set_ignore_count(&mut self, location: BankedAddress, count: u32) -> bool {
+        let breakpoint = self
+            .debug
+            .as_mut()
+            .and_then(|d| d.breakpoints.get_mut(&location));
+
+        match breakpoint {
+            Some(breakpoint) => {
+                breakpoint.ignore_count = count;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace the commands that are executed when the breakpoint at the given location pauses
+    /// execution. Returns `false` if there is no breakpoint there.
+    pub fn set_breakpoint_commands(
+        &mut self,
+        location: BankedAddress,
+        commands: Vec<DebugCommand>,
+    ) -> bool {
+        let breakpoint = self
+            .debug
+            .as_mut()
+            .and_then(|d| d.breakpoints.get_mut(&location));
+
+        match breakpoint {
+            Some(breakpoint) => {
+                breakpoint.commands = commands;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Return a list of active breakpoints, ordered by location.
+    pub fn breakpoints(&self) -> Vec<Breakpoint> {
+        let mut breakpoints = self
+            .debug
             .as_ref()
-            .map_or(vec![], |d| d.breakpoints.iter().cloned().collect())
+            .map_or(vec![], |d| d.breakpoints.values().cloned().collect());
+        breakpoints.sort_by_key(|breakpoint| (breakpoint.address, breakpoint.bank));
+        breakpoints
+    }
+
+    /// Insert a watchpoint. Execution pauses after any instruction that triggers it.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.bus.watchpoints.add(watchpoint);
+    }
+
+    /// Remove a watchpoint by its index in the list returned by [`Emulator::watchpoints`].
+    pub fn remove_watchpoint(&mut self, index: usize) -> Option<Watchpoint> {
+        self.bus.watchpoints.remove(index)
+    }
+
+    /// Return a list of active watchpoints.
+    pub fn watchpoints(&self) -> Vec<Watchpoint> {
+        self.bus.watchpoints.iter().cloned().collect()
+    }
+
+    /// Removes and returns the stereo audio samples generated since the last call.
+    ///
+    /// Audio is only captured if the emulator was built with
+    /// [`EmulatorBuilder::with_audio_capture`]. Otherwise, the returned vector is empty.
+    pub fn drain_audio(&mut self) -> Vec<[f32; 2]> {
+        self.bus.audio.drain_captured()
+    }
+
+    /// Returns statistics about the audio stream, if audio is being played.
+    pub fn audio_stats(&self) -> Option<AudioStats> {
+        self.bus.audio.stats()
     }
 
     /// Returns the current value of the program counter and the instruction at that memory
@@ -340,6 +699,269 @@ impl Emulator {
         (self.cpu.reg.pc, self.cpu.current_instruction(&self.bus))
     }
 
+    /// Set a CPU register, register pair, flag or I/O register by name. See
+    /// [`debugger::assign`].
+    pub fn set_register(&mut self, name: &str, value: i64) -> Result<()> {
+        debugger::assign(name, value, &mut self.cpu, &mut self.bus)?;
+        Ok(())
+    }
+
+    /// Read a byte of memory without ticking any components.
+    ///
+    /// If the location has a bank that is not mapped, the byte is read from that bank of the
+    /// cartridge ROM or RAM.
+    pub fn peek(&self, location: BankedAddress) -> Result<u8> {
+        let address = location.address;
+
+        match location.bank {
+            Some(bank) if location.bank != self.bus.mmu.bank(address) => self
+                .bus
+                .mmu
+                .read_bank(bank, address)
+                .ok_or_else(|| anyhow!("{} is not in the cartridge", location)),
+            _ => Ok(self.bus.read_byte_no_tick(address)),
+        }
+    }
+
+    /// Read a range of memory. See [`Emulator::peek`].
+    pub fn read_memory(&self, range: MemoryRange) -> Result<Vec<u8>> {
+        if range.start.address > range.end {
+            bail!("range start must not be after its end");
+        }
+
+        range
+            .locations()
+            .map(|location| self.peek(location))
+            .collect()
+    }
+
+    /// Write bytes to memory, starting at a location. See [`Emulator::poke`].
+    pub fn write_memory(&mut self, start: BankedAddress, bytes: &[u8]) -> Result<()> {
+        if usize::from(start.address) + bytes.len() > 0x10000 {
+            bail!("{} bytes do not fit in memory from {}", bytes.len(), start);
+        }
+
+        for (address, &byte) in (start.address..=0xFFFF).zip(bytes) {
+            self.poke(BankedAddress { address, ..start }, byte)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a byte to memory without ticking any components.
+    ///
+    /// Bytes written to ROM are stored in a patch overlay instead of controlling the memory bank
+    /// controller. If the location has a bank, the byte is written to that ROM or cartridge RAM
+    /// bank even if it is not mapped.
+    pub fn poke(&mut self, location: BankedAddress, byte: u8) -> Result<()> {
+        let address = location.address;
+        let mapped_bank = self.bus.mmu.bank(address);
+
+        match address {
+            0x0000..=0x7FFF => {
+                let bank = location
+                    .bank
+                    .or(mapped_bank)
+                    .ok_or_else(|| anyhow!("cannot write to the BIOS at {:#06x}", address))?;
+                if (bank == 0) != (address < 0x4000) {
+                    bail!(
+                        "ROM bank {:#04x} cannot be mapped at {:#06x}",
+                        bank,
+                        address
+                    );
+                }
+
+                self.bus.mmu.patch_rom(bank, address, byte);
+            }
+            0xA000..=0xBFFF if location.bank.is_some() && location.bank != mapped_bank => {
+                let bank = location.bank.unwrap();
+                if !self.bus.mmu.write_ram_bank(bank, address, byte) {
+                    bail!("the cartridge does not have RAM bank {:#04x}", bank);
+                }
+            }
+            _ => {
+                if location.bank.is_some() && location.bank != mapped_bank {
+                    bail!("{} is not mapped", location);
+                }
+
+                self.bus.write_byte_no_tick(address, byte);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a word to memory in little-endian order. See [`Emulator::poke`].
+    pub fn poke_word(&mut self, location: BankedAddress, word: u16) -> Result<()> {
+        let [low, high] = word.to_le_bytes();
+        let next = BankedAddress {
+            address: location.address.wrapping_add(1),
+            ..location
+        };
+
+        self.poke(location, low)?;
+        self.poke(next, high)
+    }
+
+    /// Write a byte to every address from `start` to `end`, inclusive. See [`Emulator::poke`].
+    pub fn fill(&mut self, start: BankedAddress, end: u16, byte: u8) -> Result<()> {
+        for address in start.address..=end {
+            self.poke(BankedAddress { address, ..start }, byte)?;
+        }
+
+        Ok(())
+    }
+
+    /// Load labels from the contents of an RGBDS symbol file, replacing any that were loaded
+    /// previously.
+    pub fn load_symbols(&mut self, symbols: &str) {
+        self.symbols = SymbolTable::parse(symbols);
+        info!("loaded {} symbols", self.symbols.len());
+    }
+
+    /// The labels for addresses in the loaded ROM.
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// The execution profile, if the emulator was built with [`EmulatorBuilder::with_profiler`].
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Forgets the execution profile recorded so far, if execution is being profiled.
+    pub fn reset_profiler(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.reset();
+        }
+    }
+
+    /// Starts a search of RAM for a variable, with every byte of work RAM, high RAM and each bank
+    /// of cartridge RAM as a candidate. Replaces any search in progress. Returns the number of
+    /// candidates. See [`MemorySearch`].
+    pub fn start_search(&mut self) -> usize {
+        let cartridge_ram = (0..self.bus.mmu.ram_banks())
+            .map(|bank| MemoryRange::new(BankedAddress::with_bank(bank, 0xA000), 0xBFFF));
+        let ranges = [
+            MemoryRange::new(BankedAddress::new(0xC000), 0xDFFF),
+            MemoryRange::new(BankedAddress::new(0xFF80), 0xFFFE),
+        ]
+        .into_iter()
+        .chain(cartridge_ram);
+
+        let snapshot = ranges
+            .flat_map(|range| range.locations())
+            .filter_map(|location| Some((location, self.peek(location).ok()?)))
+            .collect::<Vec<_>>();
+
+        let search = MemorySearch::new(snapshot);
+        let candidates = search.candidates().len();
+        self.search = Some(search);
+        candidates
+    }
+
+    /// Keeps the candidates of the search in progress whose values match a filter. Returns the
+    /// number of candidates that are left.
+    pub fn filter_search(&mut self, filter: SearchFilter) -> Result<usize> {
+        let mut search = self
+            .search
+            .take()
+            .ok_or_else(|| anyhow!("no search is in progress"))?;
+
+        let candidates = search.filter(filter, |location| self.peek(location).unwrap_or(0xFF));
+        self.search = Some(search);
+        Ok(candidates)
+    }
+
+    /// The search of RAM in progress, if any.
+    pub fn search(&self) -> Option<&MemorySearch> {
+        self.search.as_ref()
+    }
+
+    /// Starts logging how each byte of the loaded ROM is accessed by the CPU.
+    ///
+    /// If a log already exists at `path`, accesses are added to it. The log is saved to `path` by
+    /// [`Emulator::save_code_data_log`], which is also called when the emulator exits.
+    pub fn log_code_data(&mut self, path: impl Into<PathBuf>) -> Result<()> {
+        let path = path.into();
+        let rom_len = self.bus.mmu.cartridge_rom().len();
+
+        let cdl = if path.exists() {
+            let bytes = fs::read(&path).context("could not read code/data log")?;
+            CodeDataLog::from_bytes(&bytes, rom_len).context("could not load code/data log")?
+        } else {
+            CodeDataLog::new(rom_len)
+        };
+
+        self.bus.cdl = Some(cdl);
+        self.cdl_path = Some(path);
+
+        Ok(())
+    }
+
+    /// The code/data log, if it was started with [`Emulator::log_code_data`].
+    pub fn code_data_log(&self) -> Option<&CodeDataLog> {
+        self.bus.cdl.as_ref()
+    }
+
+    /// Saves the code/data log to the file that it was started with, if ROM accesses are being
+    /// logged.
+    pub fn save_code_data_log(&self) -> Result<()> {
+        if let (Some(cdl), Some(path)) = (&self.bus.cdl, &self.cdl_path) {
+            fs::write(path, cdl.as_bytes()).context("could not save code/data log")?;
+            info!("saved code/data log to '{}'", path.display());
+        }
+
+        Ok(())
+    }
+
+    /// Decodes `count` instructions from memory, starting at `address`.
+    pub fn disassemble(&self, address: u16, count: usize) -> Vec<Disassembly> {
+        debugger::disassemble(
+            address,
+            count,
+            |address| self.bus.read_byte_no_tick(address),
+            |address| self.bus.mmu.bank(address),
+            &self.symbols,
+        )
+    }
+
+    /// Decodes `count` instructions from a bank of the cartridge ROM, regardless of the banks that
+    /// are currently mapped.
+    ///
+    /// `address` is the address that the bank would be mapped to, so bank 0 starts at 0x0000 and
+    /// other banks start at 0x4000.
+    pub fn disassemble_rom(
+        &self,
+        bank: u16,
+        address: u16,
+        count: usize,
+    ) -> Result<Vec<Disassembly>> {
+        const BANK_SIZE: usize = 0x4000;
+
+        let rom = self.bus.mmu.cartridge_rom();
+        let bank_start = usize::from(bank) * BANK_SIZE;
+
+        if bank_start >= rom.len() {
+            bail!("the cartridge does not have a ROM bank {:#04x}", bank);
+        }
+
+        Ok(debugger::disassemble(
+            address,
+            count,
+            |address| {
+                let offset = bank_start + usize::from(address) % BANK_SIZE;
+                rom.get(offset).copied().unwrap_or(0xFF)
+            },
+            |address| match address {
+                0x0000..=0x3FFF => Some(0),
+                0x4000..=0x7FFF => Some(bank),
+                _ => self.bus.mmu.bank(address),
+            },
+            &self.symbols,
+        ))
+    }
+
     fn bios_loaded(&self) -> bool {
         self.bus.mmu.has_bios()
     }
@@ -356,6 +978,14 @@ pub struct EmulatorBuilder {
     debug: bool,
     serial_out: Option<Box<dyn Write>>,
     playback: bool,
+    sync_mode: SyncMode,
+    audio_sinks: Vec<Box<dyn AudioSink>>,
+    audio_capture: Option<u32>,
+    vgm_log: Option<Box<dyn WriteSeek>>,
+    trace: Option<Tracer>,
+    profile: bool,
+    gdb: Option<GdbStub>,
+    debug_frontend: Option<Box<dyn DebugFrontend>>,
 }
 
 impl EmulatorBuilder {
@@ -365,6 +995,14 @@ impl EmulatorBuilder {
             serial_out: None,
             debug: false,
             playback: false,
+            sync_mode: SyncMode::default(),
+            audio_sinks: vec![],
+            audio_capture: None,
+            vgm_log: None,
+            trace: None,
+            profile: false,
+            gdb: None,
+            debug_frontend: None,
         }
     }
 
@@ -374,12 +1012,18 @@ impl EmulatorBuilder {
         self
     }
 
-    /// Enable the debugger.
+    /// Enable the debugger, controlled from the terminal. See [`tui`].
     pub fn with_debug(mut self) -> Self {
         self.debug = true;
         self
     }
 
+    /// Enable the debugger, controlled by a frontend instead of the prompt.
+    pub fn with_debug_frontend(mut self, frontend: impl DebugFrontend + 'static) -> Self {
+        self.debug_frontend = Some(Box::new(frontend));
+        self
+    }
+
     /// Enable audio playback.
     ///
     /// If the system does not support audio or the audio controller cannot be initialized, the
@@ -389,9 +1033,56 @@ impl EmulatorBuilder {
         self
     }
 
+    /// Pace emulation to the audio device instead of the host clock.
+    ///
+    /// Only has an effect if audio playback is enabled.
+    pub fn with_audio_sync(mut self) -> Self {
+        self.sync_mode = SyncMode::Audio;
+        self
+    }
+
+    /// Send generated audio to a sink, in addition to any playback.
+    pub fn with_audio_sink(mut self, sink: impl AudioSink + 'static) -> Self {
+        self.audio_sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Capture generated audio at the given sample rate, to be retrieved with
+    /// [`Emulator::drain_audio`].
+    pub fn with_audio_capture(mut self, sample_rate: u32) -> Self {
+        self.audio_capture = Some(sample_rate);
+        self
+    }
+
+    /// Log every write to the sound registers to a VGM file.
+    pub fn with_vgm_log(mut self, writer: impl WriteSeek + 'static) -> Self {
+        self.vgm_log = Some(Box::new(writer));
+        self
+    }
+
+    /// Write a line describing the state of the CPU before every executed instruction. See
+    /// [`debugger::Tracer`].
+    pub fn with_trace(mut self, writer: impl Write + 'static, options: TraceOptions) -> Self {
+        self.trace = Some(Tracer::new(writer, options));
+        self
+    }
+
+    /// Count the instructions executed and the cycles consumed at each address. See
+    /// [`Emulator::profiler`].
+    pub fn with_profiler(mut self) -> Self {
+        self.profile = true;
+        self
+    }
+
+    /// Enable the debugger, controlled by a connected GDB instead of the prompt.
+    pub fn with_gdb(mut self, stub: GdbStub) -> Self {
+        self.gdb = Some(stub);
+        self
+    }
+
     /// Construct the emulator from the builder options.
     pub fn build(self) -> Emulator {
-        let audio = if self.playback {
+        let mut audio = if self.playback {
             SoundController::new_with_playback()
                 .map_err(|err| {
                     error!("unable to initialize audio playback: {}", err);
@@ -402,6 +1093,20 @@ impl EmulatorBuilder {
             SoundController::default()
         };
 
+        for sink in self.audio_sinks {
+            audio.add_sink(sink);
+        }
+
+        if let Some(sample_rate) = self.audio_capture {
+            audio.capture(sample_rate);
+        }
+
+        if let Some(writer) = self.vgm_log {
+            if let Err(err) = audio.log_vgm(writer) {
+                error!("unable to start VGM log: {}", err);
+            }
+        }
+
         Emulator {
             cpu: Cpu::new(),
             bus: Bus {
@@ -411,11 +1116,31 @@ impl EmulatorBuilder {
                 serial_out: self.serial_out,
                 ..Default::default()
             },
-            debug: if self.debug {
-                Some(Debugger::new())
+            debug: if self.debug || self.gdb.is_some() || self.debug_frontend.is_some() {
+                let terminal = || -> Box<dyn DebugFrontend> {
+                    if io::stdin().is_terminal() && io::stdout().is_terminal() {
+                        Box::new(tui::Dashboard::new())
+                    } else {
+                        Box::new(tui::Repl::new())
+                    }
+                };
+                let frontend = self.debug_frontend.or_else(|| self.debug.then(terminal));
+
+                Some(Debugger {
+                    frontend,
+                    gdb: self.gdb,
+                    ..Debugger::new()
+                })
             } else {
                 None
             },
+            symbols: SymbolTable::default(),
+            trace: self.trace,
+            trace_check: None,
+            profiler: self.profile.then(Profiler::new),
+            search: None,
+            cdl_path: None,
+            sync_mode: self.sync_mode,
         }
     }
 }
@@ -426,32 +1151,10 @@ impl Default for EmulatorBuilder {
     }
 }
 
-#[derive(Debug)]
-struct Debugger {
-    editor: Editor<()>,
-    breakpoints: HashSet<u16>,
-    paused: bool,
-}
-
-impl Debugger {
-    fn new() -> Debugger {
-        Debugger {
-            breakpoints: Default::default(),
-            paused: true,
-            editor: Editor::<()>::new(),
-        }
-    }
-}
-
-impl Default for Debugger {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::cpu::State;
+    use super::debugger::{BankedAddress, Breakpoint, MemoryRange, SearchFilter};
     use super::Emulator;
 
     #[test]
@@ -545,4 +1248,195 @@ mod tests {
         assert_eq!(emulator.cpu.reg.a, 2);
         assert_eq!(emulator.bus.read_byte(0xD000), 2);
     }
+
+    #[test]
+    fn step_over_and_out() {
+        let mut emulator = Emulator::builder().with_debug().build();
+        emulator.cpu.reg.pc = 0xC000;
+        emulator.cpu.reg.sp = 0xDFFF;
+
+        let test_program: &[(u16, &[u8])] = &[
+            (0xC000, &[0xCD, 0x10, 0xC0]), // CALL $C010
+            (0xC003, &[0x00]),             // NOP
+            (0xC010, &[0xC5]),             // PUSH BC
+            (0xC011, &[0xCD, 0x20, 0xC0]), // CALL $C020
+            (0xC014, &[0xC1]),             // POP BC
+            (0xC015, &[0xC9]),             // RET
+            (0xC020, &[0x3C]),             // INC A
+            (0xC021, &[0xC9]),             // RET
+        ];
+
+        for (address, bytes) in test_program {
+            for (offset, byte) in bytes.iter().enumerate() {
+                emulator
+                    .bus
+                    .write_byte_no_tick(address + offset as u16, *byte);
+            }
+        }
+
+        let run = |emulator: &mut Emulator| {
+            for _ in 0..100 {
+                if emulator.is_paused() {
+                    return;
+                }
+                emulator.step();
+            }
+            panic!("emulator did not pause");
+        };
+
+        emulator.step_over();
+        run(&mut emulator);
+        assert_eq!(emulator.cpu.reg.pc, 0xC003);
+        assert_eq!(emulator.cpu.reg.a, 1);
+
+        emulator.cpu.reg.pc = 0xC000;
+        emulator.step();
+        emulator.step();
+        emulator.step();
+        assert_eq!(emulator.cpu.reg.pc, 0xC020);
+
+        emulator.step_out();
+        run(&mut emulator);
+        assert_eq!(emulator.cpu.reg.pc, 0xC014);
+
+        emulator.step_out();
+        run(&mut emulator);
+        assert_eq!(emulator.cpu.reg.pc, 0xC003);
+
+        emulator.cpu.reg.pc = 0xC000;
+        emulator.run_to(BankedAddress::new(0xC021));
+        run(&mut emulator);
+        assert_eq!(emulator.cpu.reg.pc, 0xC021);
+
+        // Stepping over other instructions executes a single instruction.
+        emulator.step_over();
+        assert_eq!(emulator.cpu.reg.pc, 0xC014);
+    }
+
+    #[test]
+    fn ignore_count() {
+        let mut emulator = Emulator::builder().with_debug().build();
+        emulator.cpu.reg.pc = 0xC000;
+        emulator.bus.write_byte_no_tick(0xC000, 0x18); // JR -2
+        emulator.bus.write_byte_no_tick(0xC001, 0xFE);
+
+        let location = BankedAddress::new(0xC000);
+        emulator.add_breakpoint(Breakpoint::at(location));
+
+        /// Resumes and loops once, returning whether the breakpoint paused execution.
+        fn hit(emulator: &mut Emulator) -> bool {
+            emulator.resume();
+            emulator.step();
+            emulator.is_paused()
+        }
+
+        for _ in 0..5 {
+            assert!(hit(&mut emulator));
+        }
+
+        // Only the hits after the ignore count is set are skipped.
+        assert!(emulator.set_ignore_count(location, 3));
+        let hits = (0..4).map(|_| hit(&mut emulator)).collect::<Vec<_>>();
+        assert_eq!(hits, [false, false, false, true]);
+    }
+
+    #[test]
+    fn poke() {
+        let mut emulator = Emulator::new();
+        emulator.bus.mmu.unmap_bios();
+
+        emulator
+            .poke_word(BankedAddress::new(0xC000), 0xBEEF)
+            .unwrap();
+        assert_eq!(emulator.bus.read_byte_no_tick(0xC000), 0xEF);
+        assert_eq!(emulator.bus.read_byte_no_tick(0xC001), 0xBE);
+
+        emulator.poke(BankedAddress::new(0x4000), 0x12).unwrap();
+        emulator
+            .poke(BankedAddress::with_bank(2, 0x4000), 0x34)
+            .unwrap();
+        assert_eq!(emulator.bus.read_byte_no_tick(0x4000), 0x12);
+        assert!(emulator
+            .poke(BankedAddress::with_bank(0, 0x4000), 0x56)
+            .is_err());
+
+        emulator
+            .fill(BankedAddress::new(0xFF80), 0xFF8F, 0xAA)
+            .unwrap();
+        assert!((0xFF80..=0xFF8F).all(|address| emulator.bus.read_byte_no_tick(address) == 0xAA));
+        assert_eq!(emulator.bus.read_byte_no_tick(0xFF90), 0x00);
+
+        emulator.set_register("pc", 0x150).unwrap();
+        assert_eq!(emulator.cpu.reg.pc, 0x150);
+    }
+
+    #[test]
+    fn poke_bios() {
+        let mut emulator = Emulator::new();
+        emulator.load_bios(&[0; 0x100]).unwrap();
+
+        assert!(emulator.poke(BankedAddress::new(0x0050), 0x12).is_err());
+        emulator
+            .poke(BankedAddress::with_bank(0, 0x0050), 0x12)
+            .unwrap();
+        assert_eq!(emulator.bus.read_byte_no_tick(0x0050), 0x00);
+    }
+
+    #[test]
+    fn banked_memory() {
+        let mut rom = vec![0; 0x10000];
+        rom[0x147] = 0x03; // MBC1+RAM+BATTERY
+        rom[0x148] = 0x01; // 4 banks
+        rom[0x14D] = 0xE3; // Header checksum
+        rom[0xC000] = 0x42; // Bank 3
+
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&rom).unwrap();
+        emulator.bus.mmu.unmap_bios();
+
+        assert_eq!(
+            emulator.peek(BankedAddress::with_bank(3, 0x4000)).unwrap(),
+            0x42
+        );
+        assert_eq!(emulator.peek(BankedAddress::new(0x4000)).unwrap(), 0x00);
+        assert!(emulator.peek(BankedAddress::with_bank(4, 0x4000)).is_err());
+        assert!(emulator.peek(BankedAddress::with_bank(1, 0xC000)).is_err());
+
+        let ram_bank = BankedAddress::with_bank(2, 0xA000);
+        emulator.write_memory(ram_bank, &[1, 2, 3]).unwrap();
+        assert_eq!(
+            emulator
+                .read_memory(MemoryRange::new(ram_bank, 0xA003))
+                .unwrap(),
+            [1, 2, 3, 0]
+        );
+        assert_eq!(emulator.peek(BankedAddress::new(0xA000)).unwrap(), 0x00);
+
+        assert!(emulator
+            .write_memory(BankedAddress::new(0xFFFF), &[1, 2])
+            .is_err());
+    }
+
+    #[test]
+    fn search() {
+        let mut emulator = Emulator::new();
+        assert!(emulator.filter_search(SearchFilter::Changed).is_err());
+
+        emulator.bus.write_byte_no_tick(0xC123, 3);
+        emulator.bus.write_byte_no_tick(0xFF90, 3);
+        assert_eq!(emulator.start_search(), 0x2000 + 0x7F);
+        assert_eq!(emulator.filter_search(SearchFilter::Equals(3)).unwrap(), 2);
+
+        emulator.bus.write_byte_no_tick(0xC123, 2);
+        assert_eq!(
+            emulator
+                .filter_search(SearchFilter::Decreased(Some(1)))
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            emulator.search().unwrap().candidates()[0].location,
+            BankedAddress::new(0xC123)
+        );
+    }
 }
@@ -1,21 +1,36 @@
-use std::fs;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
 use std::path::PathBuf;
 use std::process;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use log::*;
 use structopt::clap::AppSettings::*;
+use structopt::clap::ArgGroup;
 use structopt::StructOpt;
 
-use feo_boy::Emulator;
+use feo_boy::audio::WavSink;
+use feo_boy::debugger::{DebugCommand, TraceComparison, TraceOptions};
+use feo_boy::gbs::Gbs;
+use feo_boy::gdb::GdbStub;
+use feo_boy::{tui, Emulator};
 
 #[derive(Debug, StructOpt)]
 #[structopt(setting(ColorAuto), setting(ColoredHelp))]
 #[structopt(author, about)]
+#[structopt(group = ArgGroup::with_name("debugger").multiple(true))]
 struct Opt {
     /// A file containing a ROM to load into the emulator.
+    ///
+    /// GBS music files are also accepted.
     rom: PathBuf,
 
+    /// The track of a GBS file to play, starting at 1.
+    ///
+    /// If not supplied, plays the default track of the file.
+    #[structopt(long)]
+    track: Option<u8>,
+
     /// A file containing a binary dump of the Game Boy BIOS.
     ///
     /// If not supplied, the emulator will begin executing the ROM as if the BIOS had succeeded.
@@ -32,15 +47,99 @@ struct Opt {
     #[structopt(long)]
     disable_audio: bool,
 
+    /// Pace emulation to the audio device instead of the system clock.
+    ///
+    /// Prevents audio crackling caused by the system clock and audio device drifting apart.
+    #[structopt(long, conflicts_with = "disable-audio")]
+    audio_sync: bool,
+
+    /// Record the audio output to a WAV file.
+    #[structopt(long, value_name = "FILE")]
+    wav_out: Option<PathBuf>,
+
+    /// Log every write to the sound registers to a VGM file.
+    #[structopt(long, value_name = "FILE")]
+    vgm_out: Option<PathBuf>,
+
+    /// Write a trace of every executed instruction to a file, in the Gameboy Doctor format.
+    #[structopt(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+
+    /// Include the number of executed cycles in the trace.
+    #[structopt(long, requires = "trace")]
+    trace_cycles: bool,
+
+    /// Include the current scanline and PPU mode in the trace.
+    #[structopt(long, requires = "trace")]
+    trace_ppu: bool,
+
+    /// Run without a window, comparing execution against a reference trace in the Gameboy Doctor
+    /// format, and report the first instruction that differs.
+    #[structopt(long, value_name = "FILE")]
+    compare_trace: Option<PathBuf>,
+
+    /// The number of matching instructions to show before the first difference.
+    #[structopt(long, value_name = "COUNT", default_value = "10")]
+    trace_history: usize,
+
+    /// Count the instructions executed and cycles consumed at each address.
+    ///
+    /// The profile can be shown and exported from the debugger with the `profile` command.
+    #[structopt(long)]
+    profile: bool,
+
+    /// Log whether each byte of the ROM is executed or read as data to a CDL file.
+    ///
+    /// The file has a byte of flags for each byte of the ROM: 0x01 if it was executed as an
+    /// opcode, 0x02 if it was read as an operand and 0x04 if it was read as data. An existing log
+    /// is added to, and the log is saved on exit.
+    #[structopt(long, value_name = "FILE")]
+    cdl: Option<PathBuf>,
+
+    /// An RGBDS symbol file containing labels to use in the debugger.
+    ///
+    /// If not supplied, a `.sym` file next to the ROM is loaded if it exists.
+    #[structopt(long, value_name = "FILE")]
+    symbols: Option<PathBuf>,
+
     /// Enable debug mode.
-    #[structopt(short, long)]
+    ///
+    /// The debugger is shown full-screen in the terminal, or as a prompt if the terminal is not
+    /// interactive.
+    #[structopt(short, long, group = "debugger")]
     debug: bool,
+
+    /// A file of debugger commands, one per line, to run before reading any from the terminal.
+    ///
+    /// Enables debug mode.
+    #[structopt(long, value_name = "FILE", group = "debugger")]
+    debug_script: Option<PathBuf>,
+
+    /// Run the debug script without reading commands from the terminal, and quit once execution
+    /// pauses after the script has finished.
+    ///
+    /// Useful to reproduce a debugging session in CI, usually along with `--headless`.
+    #[structopt(long, requires = "debug-script")]
+    batch: bool,
+
+    /// Run without opening a window, such as over SSH. Stop with the debugger's `q` command.
+    ///
+    /// Requires `--debug` or `--debug-script`, so that the emulator can be stopped even after GDB
+    /// detaches.
+    #[structopt(long, requires = "debugger")]
+    headless: bool,
+
+    /// Wait for GDB to connect on a local TCP port, and debug with it instead of the prompt.
+    #[structopt(long, value_name = "PORT")]
+    gdb: Option<u16>,
 }
 
 fn run(opt: Opt) -> Result<()> {
     let mut builder = Emulator::builder();
 
-    if opt.debug {
+    if opt.batch {
+        builder = builder.with_debug_frontend(tui::Batch::new());
+    } else if opt.debug || opt.debug_script.is_some() {
         builder = builder.with_debug();
     }
 
@@ -48,6 +147,41 @@ fn run(opt: Opt) -> Result<()> {
         builder = builder.with_playback();
     }
 
+    if opt.audio_sync {
+        builder = builder.with_audio_sync();
+    }
+
+    if let Some(path) = &opt.wav_out {
+        info!("recording audio to '{}'", path.display());
+        let sink = WavSink::create(path, 44100).context("could not create WAV file")?;
+        builder = builder.with_audio_sink(sink);
+    }
+
+    if let Some(path) = &opt.vgm_out {
+        info!("logging sound register writes to '{}'", path.display());
+        let file = File::create(path).context("could not create VGM file")?;
+        builder = builder.with_vgm_log(BufWriter::new(file));
+    }
+
+    if opt.profile {
+        builder = builder.with_profiler();
+    }
+
+    if let Some(path) = &opt.trace {
+        info!("tracing execution to '{}'", path.display());
+        let file = File::create(path).context("could not create trace file")?;
+        let options = TraceOptions {
+            cycles: opt.trace_cycles,
+            ppu: opt.trace_ppu,
+        };
+        builder = builder.with_trace(BufWriter::new(file), options);
+    }
+
+    if let Some(port) = opt.gdb {
+        let stub = GdbStub::listen(("127.0.0.1", port))?;
+        builder = builder.with_gdb(stub);
+    }
+
     let mut emulator = builder.build();
 
     if let Some(bios) = &opt.bios {
@@ -58,9 +192,73 @@ fn run(opt: Opt) -> Result<()> {
 
     info!("loading ROM from file '{}'", opt.rom.display());
     let rom = fs::read(&opt.rom).context("could not read ROM")?;
-    emulator.load_rom(&rom).context("could not load ROM")?;
 
-    emulator.run()
+    if rom.starts_with(b"GBS") {
+        let gbs = Gbs::parse(&rom).context("could not parse GBS file")?;
+        info!(
+            "playing '{}' by {} ({})",
+            gbs.title, gbs.author, gbs.copyright
+        );
+
+        let track = opt.track.unwrap_or(gbs.first_track);
+        emulator
+            .load_gbs(&gbs, track)
+            .context("could not load GBS track")?;
+    } else {
+        if opt.track.is_some() {
+            bail!("--track can only be used with a GBS file");
+        }
+
+        emulator.load_rom(&rom).context("could not load ROM")?;
+    }
+
+    if let Some(path) = &opt.cdl {
+        info!("logging ROM accesses to '{}'", path.display());
+        emulator.log_code_data(path)?;
+    }
+
+    let symbols = opt
+        .symbols
+        .clone()
+        .or_else(|| Some(opt.rom.with_extension("sym")).filter(|path| path.is_file()));
+
+    if let Some(path) = symbols {
+        info!("loading symbols from file '{}'", path.display());
+        let symbols = fs::read_to_string(&path).context("could not read symbol file")?;
+        emulator.load_symbols(&symbols);
+    }
+
+    if let Some(path) = &opt.debug_script {
+        info!("running debugger commands from '{}'", path.display());
+        let script = fs::read_to_string(path).context("could not read debug script")?;
+        let commands = tui::parse_script(&script, emulator.symbols())
+            .context("could not parse debug script")?;
+        emulator.execute(DebugCommand::Exec(commands))?;
+    }
+
+    if let Some(path) = &opt.compare_trace {
+        info!("comparing execution to trace '{}'", path.display());
+        let reference = File::open(path).context("could not open reference trace")?;
+
+        emulator.reset();
+        match emulator.compare_trace(BufReader::new(reference), opt.trace_history)? {
+            TraceComparison::Matched { lines } => {
+                println!("execution matched all {} lines of the trace", lines);
+            }
+            TraceComparison::Diverged(divergence) => {
+                print!("{}", divergence);
+                process::exit(1);
+            }
+        }
+
+        return Ok(());
+    }
+
+    if opt.headless {
+        emulator.run_headless()
+    } else {
+        emulator.run()
+    }
 }
 
 fn main() {
@@ -7,9 +7,19 @@ const RTC_SIZE: usize = 0x2000 * 5;
 const ROM_BANK_SIZE: usize = 0x4000;
 const RAM_BANK_RTC_REG_SIZE: usize = 0x2000;
 
-pub trait Mbc: Addressable + Debug {}
+pub trait Mbc: Addressable + Debug {
+    /// The ROM bank that is mapped to 0x4000-0x7FFF.
+    fn rom_bank(&self) -> u16;
 
-impl<M: Addressable + Debug> Mbc for M {}
+    /// The RAM bank that is mapped to 0xA000-0xBFFF, if the region is mapped to RAM.
+    fn ram_bank(&self) -> Option<u8>;
+
+    /// The cartridge RAM, with each bank in order.
+    fn ram(&self) -> &[u8];
+
+    /// The cartridge RAM, with each bank in order.
+    fn ram_mut(&mut self) -> &mut [u8];
+}
 
 pub struct Mbc1 {
     rom: Rc<Vec<u8>>,
@@ -118,6 +128,24 @@ impl super::Addressable for Mbc1 {
     }
 }
 
+impl Mbc for Mbc1 {
+    fn rom_bank(&self) -> u16 {
+        u16::from(self.rom_num)
+    }
+
+    fn ram_bank(&self) -> Option<u8> {
+        Some(self.ram_num)
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 enum RamRtcSelect {
     Ram(u8), // 0-3
@@ -258,6 +286,27 @@ impl super::Addressable for Mbc3 {
     }
 }
 
+impl Mbc for Mbc3 {
+    fn rom_bank(&self) -> u16 {
+        u16::from(self.rom_select)
+    }
+
+    fn ram_bank(&self) -> Option<u8> {
+        match self.ram_rtc_select {
+            RamRtcSelect::Ram(bank) => Some(bank),
+            RamRtcSelect::Rtc(_) => None,
+        }
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+}
+
 impl Debug for Mbc3 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let ram: &[u8] = &self.ram;
@@ -278,7 +327,7 @@ impl Debug for Mbc3 {
 mod tests {
     use std::rc::Rc;
 
-    use super::{Addressable, LatchStatus, Mbc3};
+    use super::{Addressable, LatchStatus, Mbc, Mbc1, Mbc3};
 
     #[test]
     fn mbc3_rtc_latch() {
@@ -287,4 +336,20 @@ mod tests {
         mbc.write_byte(0x6000, 0x01);
         assert_eq!(mbc.rtc_latch, LatchStatus::Latched);
     }
+
+    #[test]
+    fn banks() {
+        let mut mbc = Mbc1::new(Rc::new(vec![]));
+        assert_eq!(mbc.rom_bank(), 1);
+        mbc.write_byte(0x2000, 0x05);
+        mbc.write_byte(0x4000, 0x02);
+        assert_eq!((mbc.rom_bank(), mbc.ram_bank()), (5, Some(2)));
+
+        let mut mbc = Mbc3::new(Rc::new(vec![]));
+        mbc.write_byte(0x2000, 0x00);
+        mbc.write_byte(0x4000, 0x03);
+        assert_eq!((mbc.rom_bank(), mbc.ram_bank()), (1, Some(3)));
+        mbc.write_byte(0x4000, 0x08);
+        assert_eq!(mbc.ram_bank(), None);
+    }
 }
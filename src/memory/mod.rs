@@ -4,6 +4,7 @@
 
 mod mbc;
 
+use std::collections::HashMap;
 use std::default::Default;
 use std::fmt::{self, Debug, Formatter};
 use std::num::Wrapping;
@@ -15,6 +16,12 @@ use thiserror::Error;
 
 use self::mbc::{Mbc, Mbc1, Mbc3};
 
+/// The size (in bytes) of a bank of cartridge ROM.
+pub(crate) const ROM_BANK_SIZE: usize = 0x4000;
+
+/// The size (in bytes) of a bank of cartridge RAM.
+const RAM_BANK_SIZE: usize = 0x2000;
+
 /// The size (in bytes) of the DMG BIOS.
 pub const BIOS_SIZE: usize = 0x0100;
 
@@ -133,6 +140,12 @@ pub struct Mmu {
 
     /// Memory bank controller.
     mbc: Option<Box<dyn Mbc>>,
+
+    /// Bytes of the cartridge ROM that have been overwritten, indexed by their offset in the ROM.
+    ///
+    /// Patches are applied when the ROM is read, so writes to ROM can still control the memory bank
+    /// controller.
+    rom_patches: HashMap<usize, u8>,
 }
 
 impl Mmu {
@@ -145,6 +158,7 @@ impl Mmu {
             bios_mapped: true,
             cartridge_rom: Rc::new(vec![]),
             mbc: None,
+            rom_patches: HashMap::new(),
         }
     }
 
@@ -177,6 +191,7 @@ impl Mmu {
         }
 
         self.cartridge_rom = Rc::new(rom.to_vec());
+        self.rom_patches.clear();
 
         let initial_banks = &self.cartridge_rom[..self.mem.rom.len()];
         self.mem.rom.copy_from_slice(initial_banks);
@@ -331,6 +346,110 @@ impl Mmu {
         self.bios_mapped = false;
     }
 
+    /// The entire ROM of the inserted cartridge.
+    pub fn cartridge_rom(&self) -> &[u8] {
+        &self.cartridge_rom
+    }
+
+    /// The ROM bank that is mapped to 0x4000-0x7FFF.
+    pub fn rom_bank(&self) -> u16 {
+        self.mbc.as_ref().map_or(1, |mbc| mbc.rom_bank())
+    }
+
+    /// The cartridge RAM bank that is mapped to 0xA000-0xBFFF, if any.
+    pub fn ram_bank(&self) -> Option<u8> {
+        self.mbc.as_ref().and_then(|mbc| mbc.ram_bank())
+    }
+
+    /// The bank that is currently mapped at an address, if the address is banked. The BIOS is not
+    /// part of any bank.
+    pub fn bank(&self, address: u16) -> Option<u16> {
+        match address {
+            0x0000..=0x00FF if self.bios_mapped && self.has_bios() => None,
+            0x0000..=0x3FFF => Some(0),
+            0x4000..=0x7FFF => Some(self.rom_bank()),
+            0xA000..=0xBFFF => self.ram_bank().map(u16::from),
+            _ => None,
+        }
+    }
+
+    /// The offset into the cartridge ROM of the byte that is read at `address`, if the address is
+    /// mapped to the cartridge ROM.
+    pub fn cartridge_offset(&self, address: u16) -> Option<usize> {
+        if address > 0x7FFF {
+            return None;
+        }
+
+        let offset = rom_offset(self.bank(address)?, address);
+        Some(offset).filter(|&offset| offset < self.cartridge_rom.len())
+    }
+
+    /// Overwrites a byte of a cartridge ROM bank, as it would be read at `address`.
+    pub fn patch_rom(&mut self, bank: u16, address: u16, byte: u8) {
+        self.rom_patches.insert(rom_offset(bank, address), byte);
+    }
+
+    /// Removes all patches to the cartridge ROM. Returns the number of bytes that were restored.
+    pub fn clear_rom_patches(&mut self) -> usize {
+        let patches = self.rom_patches.len();
+        self.rom_patches.clear();
+        patches
+    }
+
+    /// The number of banks of cartridge RAM.
+    pub fn ram_banks(&self) -> u16 {
+        self.mbc
+            .as_ref()
+            .map_or(0, |mbc| (mbc.ram().len() / RAM_BANK_SIZE) as u16)
+    }
+
+    /// Reads a byte from a bank of cartridge ROM or RAM, whether or not the bank is mapped. Returns
+    /// `None` if the bank cannot be mapped at `address`, or the cartridge does not have it.
+    pub fn read_bank(&self, bank: u16, address: u16) -> Option<u8> {
+        match address {
+            0x0000..=0x7FFF => {
+                if (bank == 0) != (address < 0x4000) {
+                    return None;
+                }
+
+                let offset = rom_offset(bank, address);
+                self.rom_patches
+                    .get(&offset)
+                    .or_else(|| self.cartridge_rom.get(offset))
+                    .copied()
+            }
+            0xA000..=0xBFFF => {
+                let offset = ram_offset(bank, address);
+                self.mbc.as_ref()?.ram().get(offset).copied()
+            }
+            _ => None,
+        }
+    }
+
+    /// Writes a byte to a bank of cartridge RAM, whether or not the bank is mapped. Returns `false`
+    /// if the cartridge does not have the bank.
+    pub fn write_ram_bank(&mut self, bank: u16, address: u16, byte: u8) -> bool {
+        let offset = ram_offset(bank, address);
+        let ram = self.mbc.as_mut().map(|mbc| mbc.ram_mut());
+
+        match ram.and_then(|ram| ram.get_mut(offset)) {
+            Some(ram) => {
+                *ram = byte;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn rom_patch(&self, address: u16) -> Option<u8> {
+        if self.rom_patches.is_empty() {
+            return None;
+        }
+
+        let bank = self.bank(address)?;
+        self.rom_patches.get(&rom_offset(bank, address)).copied()
+    }
+
     /// Reads a byte from memory.
     ///
     /// # Panics
@@ -344,9 +463,12 @@ impl Mmu {
             }
 
             // ROM Banks
-            0x0000..=0x7FFF => match self.mbc {
-                Some(ref mbc) => mbc.read_byte(address),
-                None => self.mem.rom[address as usize],
+            0x0000..=0x7FFF => match self.rom_patch(address) {
+                Some(byte) => byte,
+                None => match self.mbc {
+                    Some(ref mbc) => mbc.read_byte(address),
+                    None => self.mem.rom[address as usize],
+                },
             },
 
             // Graphics RAM
@@ -444,9 +566,19 @@ impl Default for Mmu {
     }
 }
 
+/// The offset into the cartridge ROM of an address in a bank.
+fn rom_offset(bank: u16, address: u16) -> usize {
+    usize::from(bank) * ROM_BANK_SIZE + usize::from(address) % ROM_BANK_SIZE
+}
+
+/// The offset into the cartridge RAM of an address in a bank.
+fn ram_offset(bank: u16, address: u16) -> usize {
+    usize::from(bank) * RAM_BANK_SIZE + usize::from(address) % RAM_BANK_SIZE
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Mmu;
+    use super::{Mmu, BIOS_SIZE};
 
     #[test]
     fn rom() {
@@ -463,6 +595,36 @@ mod tests {
         assert_eq!(mmu.read_byte(0x7FFF), 3);
     }
 
+    #[test]
+    fn rom_patches() {
+        let mut mmu = Mmu::default();
+        mmu.unmap_bios();
+
+        mmu.mem.rom[0x4123] = 1;
+        mmu.patch_rom(1, 0x4123, 2);
+        mmu.patch_rom(2, 0x4123, 3);
+        assert_eq!(mmu.read_byte(0x4123), 2);
+
+        mmu.write_byte(0x4123, 4);
+        assert_eq!(mmu.read_byte(0x4123), 2);
+
+        assert_eq!(mmu.clear_rom_patches(), 2);
+        assert_eq!(mmu.read_byte(0x4123), 1);
+    }
+
+    #[test]
+    fn bios_bank() {
+        let mut mmu = Mmu::default();
+        mmu.load_bios(&[0; BIOS_SIZE]).unwrap();
+
+        assert_eq!(mmu.bank(0x0050), None);
+        assert_eq!(mmu.cartridge_offset(0x0050), None);
+        assert_eq!(mmu.bank(0x0100), Some(0));
+
+        mmu.unmap_bios();
+        assert_eq!(mmu.bank(0x0050), Some(0));
+    }
+
     #[test]
     fn wram() {
         let mut mmu = Mmu::default();
@@ -0,0 +1,33 @@
+//! Helpers shared by tests across the crate.
+
+use std::cell::RefCell;
+use std::io::{self, Cursor, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+/// An in-memory writer that can be cloned, so that its output can be inspected while another
+/// value owns it.
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Cursor<Vec<u8>>>>);
+
+impl SharedBuffer {
+    /// The bytes written so far.
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.borrow().get_ref().clone()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for SharedBuffer {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.0.borrow_mut().seek(pos)
+    }
+}
@@ -0,0 +1,37 @@
+//! Running debugger commands without a prompt.
+
+use anyhow::Result;
+
+use crate::debugger::{DebugCommand, DebugFrontend, DebugResponse};
+use crate::Emulator;
+
+/// A frontend that reads no input, for running a script of commands unattended, such as in CI.
+///
+/// Responses are printed to stdout. Once execution pauses with no commands left to execute, the
+/// emulator quits.
+#[derive(Debug, Default)]
+pub struct Batch;
+
+impl Batch {
+    pub fn new() -> Batch {
+        Batch
+    }
+}
+
+impl DebugFrontend for Batch {
+    fn poll_command(&mut self, emulator: &Emulator) -> Option<DebugCommand> {
+        if emulator.is_paused() {
+            Some(DebugCommand::Quit)
+        } else {
+            None
+        }
+    }
+
+    fn respond(&mut self, response: Result<DebugResponse>) {
+        match response {
+            Ok(DebugResponse::Done) => (),
+            Ok(response) => println!("{}", response),
+            Err(e) => println!("error: {:#}", e),
+        }
+    }
+}
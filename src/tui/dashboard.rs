@@ -0,0 +1,648 @@
+//! The full-screen debugger.
+
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::*;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::cpu::{Flags, State};
+use crate::debugger::{BankedAddress, DebugCommand, DebugFrontend, DebugResponse};
+use crate::Emulator;
+
+use super::{parse_command, parse_location, HELP};
+
+/// Keys that control execution, shown by `?` after the commands.
+static KEYS: &str = "\
+m <location>: show memory at a location
+
+F5: resume  F6 or Esc: pause  F7: step  F8: step over  F9: step out
+PageUp/PageDown: scroll memory  Up/Down: command history  Ctrl-C: quit";
+
+/// How often the screen is redrawn while execution is running.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The number of lines of output that are kept.
+const OUTPUT_LINES: usize = 500;
+
+/// The I/O registers that are shown, in pairs.
+const IO_REGISTERS: &[[(&str, u16); 2]] = &[
+    [("LCDC", 0xFF40), ("STAT", 0xFF41)],
+    [("LY", 0xFF44), ("LYC", 0xFF45)],
+    [("SCY", 0xFF42), ("SCX", 0xFF43)],
+    [("WY", 0xFF4A), ("WX", 0xFF4B)],
+    [("BGP", 0xFF47), ("OBP0", 0xFF48)],
+    [("DIV", 0xFF04), ("TIMA", 0xFF05)],
+    [("TMA", 0xFF06), ("TAC", 0xFF07)],
+    [("IE", 0xFFFF), ("IF", 0xFF0F)],
+];
+
+/// A full-screen view of the emulator in the terminal.
+///
+/// Shows the registers, the disassembly around the program counter, a scrollable view of memory,
+/// the stack, the breakpoints and the hardware registers, with a command line at the bottom that
+/// accepts the same commands as the prompt. The view is refreshed as execution runs and steps.
+///
+/// Only terminal escape sequences are used, so the dashboard also works over SSH. It takes over
+/// the terminal when it is first polled, and restores it when it is dropped.
+#[derive(Default)]
+pub struct Dashboard {
+    terminal: Option<DefaultTerminal>,
+    view: View,
+    commands: VecDeque<DebugCommand>,
+    last_draw: Option<Instant>,
+}
+
+impl Dashboard {
+    /// Creates a dashboard. The terminal is not touched until the debugger polls for a command.
+    pub fn new() -> Dashboard {
+        Dashboard::default()
+    }
+
+    fn handle_events(&mut self, emulator: &Emulator) -> io::Result<()> {
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    self.handle_key(key, emulator);
+                }
+                Event::Resize(..) => self.last_draw = None,
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, emulator: &Emulator) {
+        let view = &mut self.view;
+        self.last_draw = None;
+
+        let command = match key.code {
+            KeyCode::Char('c') | KeyCode::Char('d')
+                if key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                Some(DebugCommand::Quit)
+            }
+            KeyCode::F(5) => Some(DebugCommand::Continue),
+            KeyCode::F(6) | KeyCode::Esc => Some(DebugCommand::Pause),
+            KeyCode::F(7) => Some(DebugCommand::Step(1)),
+            KeyCode::F(8) => Some(DebugCommand::StepOver),
+            KeyCode::F(9) => Some(DebugCommand::StepOut),
+            KeyCode::PageUp => {
+                view.memory_address = view.memory_address.saturating_sub(view.memory_page);
+                None
+            }
+            KeyCode::PageDown => {
+                view.memory_address = view.memory_address.saturating_add(view.memory_page);
+                None
+            }
+            KeyCode::Up => {
+                view.recall(-1);
+                None
+            }
+            KeyCode::Down => {
+                view.recall(1);
+                None
+            }
+            KeyCode::Backspace => {
+                view.input.pop();
+                None
+            }
+            KeyCode::Enter => view.submit(emulator),
+            KeyCode::Char(c) => {
+                view.input.push(c);
+                None
+            }
+            _ => None,
+        };
+
+        self.commands.extend(command);
+    }
+}
+
+impl DebugFrontend for Dashboard {
+    fn poll_command(&mut self, emulator: &Emulator) -> Option<DebugCommand> {
+        if self.terminal.is_none() {
+            match ratatui::try_init() {
+                Ok(terminal) => self.terminal = Some(terminal),
+                Err(e) => {
+                    error!("unable to start the terminal UI: {}", e);
+                    return Some(DebugCommand::Quit);
+                }
+            }
+        }
+
+        if let Err(e) = self.handle_events(emulator) {
+            error!("unable to read terminal input: {}", e);
+            return Some(DebugCommand::Quit);
+        }
+
+        let refresh = emulator.is_paused()
+            || self
+                .last_draw
+                .is_none_or(|last_draw| last_draw.elapsed() >= REFRESH_INTERVAL);
+
+        if refresh {
+            let Dashboard { terminal, view, .. } = self;
+            let drawn = terminal
+                .as_mut()
+                .unwrap()
+                .draw(|frame| view.render(frame, emulator));
+
+            if let Err(e) = drawn {
+                error!("unable to draw the terminal UI: {}", e);
+                return Some(DebugCommand::Quit);
+            }
+
+            self.last_draw = Some(Instant::now());
+        }
+
+        self.commands.pop_front()
+    }
+
+    fn respond(&mut self, response: Result<DebugResponse>) {
+        match response {
+            Ok(DebugResponse::Done) => (),
+            Ok(response) => self.view.print(&response.to_string()),
+            Err(e) => self.view.print(&format!("error: {:#}", e)),
+        }
+
+        self.last_draw = None;
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        if self.terminal.is_some() {
+            ratatui::restore();
+        }
+    }
+}
+
+/// The state of the dashboard that is independent of the terminal.
+#[derive(Default)]
+struct View {
+    /// The command that is being typed.
+    input: String,
+
+    /// Previously entered commands, and the index of the one being recalled, if any.
+    history: Vec<String>,
+    history_index: Option<usize>,
+
+    /// Command output, oldest first.
+    output: VecDeque<String>,
+
+    /// The address of the first byte of the memory view.
+    memory_address: u16,
+
+    /// The number of bytes shown by the memory view.
+    memory_page: u16,
+
+    /// The addresses that the disassembly view started and ended at when it was last drawn.
+    listing: Option<(u16, u16)>,
+}
+
+impl View {
+    fn print(&mut self, text: &str) {
+        self.output.extend(text.lines().map(String::from));
+
+        while self.output.len() > OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+
+    /// Replaces the input with an older (`-1`) or newer (`1`) command from the history.
+    fn recall(&mut self, direction: isize) {
+        let index = match (self.history_index, direction) {
+            (None, -1) => self.history.len().checked_sub(1),
+            (None, _) => None,
+            (Some(index), -1) => Some(index.saturating_sub(1)),
+            (Some(index), _) => Some(index + 1).filter(|&index| index < self.history.len()),
+        };
+
+        self.history_index = index;
+        self.input = index.map_or_else(String::new, |index| self.history[index].clone());
+    }
+
+    /// Handles the entered line, returning the command to execute, if any.
+    fn submit(&mut self, emulator: &Emulator) -> Option<DebugCommand> {
+        let line = std::mem::take(&mut self.input);
+        let line = line.trim();
+        self.history_index = None;
+
+        if line.is_empty() {
+            return None;
+        }
+
+        self.print(&format!("> {}", line));
+        self.history.push(line.to_owned());
+
+        if line == "?" {
+            self.print(HELP);
+            self.print(KEYS);
+            return None;
+        }
+
+        if line.split_whitespace().next() == Some("m") {
+            match parse_location(line, emulator.symbols()) {
+                Ok(location) => self.memory_address = location.address,
+                Err(e) => self.print(&format!("error: {:#}", e)),
+            }
+
+            return None;
+        }
+
+        match parse_command(line, emulator.symbols()) {
+            Ok(command) => command,
+            Err(e) => {
+                self.print(&format!("error: {:#}", e));
+                None
+            }
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame<'_>, emulator: &Emulator) {
+        let [top, output, input] = Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(8),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+
+        let [left, center, right] = Layout::horizontal([
+            Constraint::Length(22),
+            Constraint::Min(40),
+            Constraint::Length(36),
+        ])
+        .areas(top);
+
+        let [registers, io_registers] =
+            Layout::vertical([Constraint::Length(12), Constraint::Min(0)]).areas(left);
+        let [disassembly, memory] =
+            Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(center);
+        let [stack, breakpoints] =
+            Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(right);
+
+        render_registers(frame, registers, emulator);
+        render_io_registers(frame, io_registers, emulator);
+        self.render_disassembly(frame, disassembly, emulator);
+        self.render_memory(frame, memory, emulator);
+        render_stack(frame, stack, emulator);
+        render_breakpoints(frame, breakpoints, emulator);
+
+        let height = usize::from(output.height.saturating_sub(2));
+        let lines = self
+            .output
+            .iter()
+            .skip(self.output.len().saturating_sub(height))
+            .map(|line| Line::raw(line.as_str()))
+            .collect::<Vec<_>>();
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered()), output);
+
+        let prompt = format!("> {}", self.input);
+        let cursor_x = input.x + 1 + prompt.chars().count() as u16;
+        frame.render_widget(
+            Paragraph::new(prompt).block(Block::bordered().title(" command (? for help) ")),
+            input,
+        );
+        frame.set_cursor_position((cursor_x.min(input.right().saturating_sub(2)), input.y + 1));
+    }
+
+    fn render_disassembly(&mut self, frame: &mut Frame<'_>, area: Rect, emulator: &Emulator) {
+        let height = usize::from(area.height.saturating_sub(2));
+        let pc = emulator.cpu.reg.pc;
+
+        // Keep the listing in place while the program counter moves through it, so that the
+        // instructions that were just executed stay in view.
+        let mut start = match self.listing {
+            Some((start, end)) if start <= pc && pc < end => start,
+            _ => pc,
+        };
+        let mut lines = emulator.disassemble(start, height);
+
+        let pc_index = lines.iter().position(|line| line.location.address == pc);
+        if start != pc && pc_index.is_none_or(|index| index >= height * 2 / 3) {
+            start = pc;
+            lines = emulator.disassemble(start, height);
+        }
+
+        self.listing = lines.last().map(|last| {
+            let end = last
+                .location
+                .address
+                .saturating_add(last.instruction.size());
+            (start, end)
+        });
+
+        let breakpoints = emulator.breakpoints();
+        let text = lines
+            .iter()
+            .flat_map(|line| {
+                let location = line.location;
+                let is_breakpoint = breakpoints.iter().any(|breakpoint| {
+                    breakpoint.address == location.address
+                        && (breakpoint.bank.is_none() || breakpoint.bank == location.bank)
+                });
+
+                let marker = match (location.address == pc, is_breakpoint) {
+                    (true, _) => ">",
+                    (false, true) => "*",
+                    (false, false) => " ",
+                };
+                let style = if location.address == pc {
+                    Style::new().add_modifier(Modifier::REVERSED)
+                } else if is_breakpoint {
+                    Style::new().fg(Color::Red)
+                } else {
+                    Style::new()
+                };
+
+                let formatted = line.to_string();
+                let mut text = formatted.lines().map(String::from).collect::<Vec<_>>();
+                let instruction = text.pop().unwrap_or_default();
+
+                text.into_iter()
+                    .map(Line::raw)
+                    .chain(Some(
+                        Line::raw(format!("{} {}", marker, instruction)).style(style),
+                    ))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let state = match (&emulator.cpu.state, emulator.is_paused()) {
+            (_, false) => "running",
+            (State::Running, true) => "paused",
+            (State::Halted, true) => "halted",
+            (State::Stopped, true) => "stopped",
+            (State::Locked, true) => "locked",
+        };
+
+        frame.render_widget(
+            Paragraph::new(text).block(Block::bordered().title(format!(" code ({}) ", state))),
+            area,
+        );
+    }
+
+    fn render_memory(&mut self, frame: &mut Frame<'_>, area: Rect, emulator: &Emulator) {
+        let rows = area.height.saturating_sub(2);
+        let width = area.width.saturating_sub(2);
+
+        // Each byte takes four columns: two hex digits, a space and a character.
+        let bytes_per_row: u16 = if width >= 6 + 16 * 4 { 16 } else { 8 };
+        self.memory_page = rows * bytes_per_row;
+
+        let lines = (0..rows)
+            .map_while(|row| {
+                let address = self.memory_address.checked_add(row * bytes_per_row)?;
+                let bytes = (0..bytes_per_row)
+                    .filter_map(|offset| address.checked_add(offset))
+                    .map(|address| emulator.bus.read_byte_no_tick(address))
+                    .collect::<Vec<_>>();
+
+                let hex = bytes
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let chars = bytes
+                    .iter()
+                    .map(|&byte| {
+                        if byte.is_ascii_graphic() {
+                            byte as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect::<String>();
+
+                Some(Line::raw(format!("{:04x}  {}  {}", address, hex, chars)))
+            })
+            .collect::<Vec<_>>();
+
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title(" memory ")),
+            area,
+        );
+    }
+}
+
+fn render_registers(frame: &mut Frame<'_>, area: Rect, emulator: &Emulator) {
+    let reg = &emulator.cpu.reg;
+    let mmu = &emulator.bus.mmu;
+
+    let flag = |flag: Flags, name: char| {
+        if reg.f.contains(flag) {
+            name
+        } else {
+            '-'
+        }
+    };
+    let flags = [
+        flag(Flags::ZERO, 'Z'),
+        flag(Flags::SUBTRACT, 'N'),
+        flag(Flags::HALF_CARRY, 'H'),
+        flag(Flags::CARRY, 'C'),
+    ]
+    .iter()
+    .collect::<String>();
+
+    let ram_bank = mmu
+        .ram_bank()
+        .map_or_else(|| String::from("--"), |bank| format!("{:02x}", bank));
+
+    let lines = vec![
+        Line::raw(format!("AF {:04x}  {}", reg.af(), flags)),
+        Line::raw(format!("BC {:04x}", reg.bc())),
+        Line::raw(format!("DE {:04x}", reg.de())),
+        Line::raw(format!("HL {:04x}", reg.hl())),
+        Line::raw(format!("SP {:04x}", reg.sp)),
+        Line::raw(format!("PC {:04x}", reg.pc)),
+        Line::raw(""),
+        Line::raw(format!(
+            "IME {}",
+            if emulator.bus.interrupts.enabled {
+                "on"
+            } else {
+                "off"
+            }
+        )),
+        Line::raw(format!("ROM {:02x}  RAM {}", mmu.rom_bank(), ram_bank)),
+    ];
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" registers ")),
+        area,
+    );
+}
+
+fn render_io_registers(frame: &mut Frame<'_>, area: Rect, emulator: &Emulator) {
+    let lines = IO_REGISTERS
+        .iter()
+        .map(|pair| {
+            let [(left, left_address), (right, right_address)] = pair;
+            Line::raw(format!(
+                "{:<4} {:02x}  {:<4} {:02x}",
+                left,
+                emulator.bus.read_byte_no_tick(*left_address),
+                right,
+                emulator.bus.read_byte_no_tick(*right_address)
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" hardware ")),
+        area,
+    );
+}
+
+fn render_stack(frame: &mut Frame<'_>, area: Rect, emulator: &Emulator) {
+    let rows = area.height.saturating_sub(2);
+    let sp = emulator.cpu.reg.sp;
+    let symbols = emulator.symbols();
+
+    let lines = (0..rows)
+        .map_while(|row| {
+            let address = sp
+                .checked_add(row * 2)
+                .filter(|&address| address < 0xFFFF)?;
+            let word = u16::from_le_bytes([
+                emulator.bus.read_byte_no_tick(address),
+                emulator.bus.read_byte_no_tick(address + 1),
+            ]);
+
+            // Annotate the return addresses of the functions that have been entered.
+            let frame = emulator
+                .cpu
+                .call_stack
+                .iter()
+                .find(|frame| frame.sp == address);
+            let line = match frame {
+                Some(frame) => {
+                    let target = BankedAddress {
+                        bank: frame.target_bank,
+                        address: frame.target,
+                    };
+                    format!(
+                        "{:04x}  {:04x}  {} {}",
+                        address,
+                        word,
+                        frame.kind,
+                        symbols.describe(target)
+                    )
+                }
+                None => format!("{:04x}  {:04x}", address, word),
+            };
+
+            Some(Line::raw(line))
+        })
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" stack ")),
+        area,
+    );
+}
+
+fn render_breakpoints(frame: &mut Frame<'_>, area: Rect, emulator: &Emulator) {
+    let symbols = emulator.symbols();
+
+    let breakpoints = emulator.breakpoints().into_iter().map(|breakpoint| {
+        match symbols.symbolize(breakpoint.location()) {
+            Some(name) => format!("{} <{}>", breakpoint, name),
+            None => breakpoint.to_string(),
+        }
+    });
+    let watchpoints = emulator
+        .watchpoints()
+        .into_iter()
+        .enumerate()
+        .map(|(index, watchpoint)| format!("w{}: {}", index, watchpoint));
+
+    let lines = breakpoints
+        .chain(watchpoints)
+        .map(Line::raw)
+        .collect::<Vec<_>>();
+
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" breakpoints ")),
+        area,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    use crate::debugger::Breakpoint;
+    use crate::Emulator;
+
+    use super::View;
+
+    #[test]
+    fn render() {
+        let mut emulator = Emulator::builder().with_debug().build();
+        emulator.cpu.reg.pc = 0xC000;
+        emulator.cpu.reg.sp = 0xDFFC;
+        emulator.add_breakpoint(Breakpoint::new(0xC002));
+
+        let program = [0x3E, 0x42, 0x00]; // LD A,$42; NOP
+        for (offset, byte) in program.iter().enumerate() {
+            emulator
+                .bus
+                .write_byte_no_tick(0xC000 + offset as u16, *byte);
+        }
+
+        let mut view = View {
+            memory_address: 0xC000,
+            ..View::default()
+        };
+        view.print("restored 0 patched ROM bytes");
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 40)).unwrap();
+        terminal
+            .draw(|frame| view.render(frame, &emulator))
+            .unwrap();
+
+        let screen = terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(120)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        for expected in [
+            "PC c000",
+            "SP dffc",
+            "code (paused)",
+            "> 0xc000",
+            "c000  3e 42 00",
+            "dffc  0000",
+            "* 0xc002",
+            "0xc002 (hits: 0)",
+            "restored 0 patched ROM bytes",
+        ] {
+            assert!(
+                screen.contains(expected),
+                "'{}' not found in:\n{}",
+                expected,
+                screen
+            );
+        }
+
+        // The center column is too narrow for 16 bytes per row.
+        assert_eq!(view.memory_page, 12 * 8);
+    }
+}
@@ -0,0 +1,729 @@
+//! Terminal UI.
+//!
+//! The debugger is driven from the terminal by either the full-screen [`Dashboard`], or the
+//! line-based [`Repl`] when the terminal cannot display it. Both accept the same commands.
+
+mod batch;
+mod dashboard;
+mod repl;
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::audio::Channel;
+use crate::bus::{WatchKind, Watchpoint};
+use crate::debugger::{
+    BankedAddress, Breakpoint, DebugCommand, Expr, MemoryRange, SearchFilter, SymbolTable,
+};
+
+pub use self::batch::Batch;
+pub use self::dashboard::Dashboard;
+pub use self::repl::Repl;
+
+/// The commands that are available to the debugger.
+pub static COMMANDS: &str = "snfubwlrpdcq?";
+
+/// A description of each command, shown by `?`.
+pub static HELP: &str = "\
+s: step emulator
+n: step over calls
+f: run until the current function returns
+u <location>: run until an address or label is reached
+b <location> [if <condition>]: add breakpoint at an address or label
+ignore <location> <count>: skip the next hits of a breakpoint
+commands <location> [<command>; ...]: run commands when a breakpoint pauses, such as `print a; r`
+w <r|w|c> <address>[-<end>]: watch memory for reads, writes or changes
+unwatch <index>: remove a watchpoint
+l: list breakpoints and watchpoints
+r: resume execution
+p: print current instruction
+bt: print the call stack
+disasm [<location>] [<count>]: disassemble memory, or a ROM bank
+set <register> <value>: set a register, flag or I/O register
+poke <location> <value>: write a byte to memory or patch ROM
+pokew <location> <value>: write a word to memory or patch ROM
+fill <location>-<end> <value>: write a byte to a range of memory
+unpatch: remove all ROM patches
+d [<range>]: dump a page of memory, or the next page
+save <range> <file>: write a range of memory to a file
+load <range> <file>: write a file to memory, or patch ROM
+search start: start searching RAM for a variable, such as the number of lives
+search <filter>: keep the candidates that are changed, unchanged, increased [<n>], decreased [<n>] or
+  equals <value>, compared to the last search
+search [<count>]: list the search candidates and their current values
+c: cpu state
+print <expression>[, ...]: print the values of expressions, such as `print a, [hl]`
+profile [<count>]: show the addresses where the most time is spent
+profile reset: clear the execution profile
+profile report <file>: write the full execution profile to a file
+profile folded <file>: write the profile as folded stacks for flame graphs
+cdl: show how much of each ROM bank has been executed or read
+cdl save: save the code/data log
+mute <channel>: toggle whether a sound channel is muted
+solo <channel>: toggle whether a sound channel is soloed
+channels: show the output and state of each sound channel
+exec <file>: run the commands in a file, one per line
+q: quit
+
+locations are [<bank>:]<address>, such as 0x03:0x4123, or a symbol name
+ranges are <location>[-<end>], or one of rom, vram, sram, wram, oam, io or hram. rom and sram may
+be followed by a bank, such as rom:3";
+
+/// Parse a debugger command from a line of input. Returns `None` if the line is empty.
+pub fn parse_command(command: &str, symbols: &SymbolTable) -> Result<Option<DebugCommand>> {
+    parse_command_in(command, symbols, &[])
+}
+
+/// Parses a command from a line of input, inside the chain of script files that are being
+/// executed.
+fn parse_command_in(
+    command: &str,
+    symbols: &SymbolTable,
+    scripts: &[PathBuf],
+) -> Result<Option<DebugCommand>> {
+    if command.is_empty() {
+        return Ok(None);
+    }
+
+    let name = command.split_whitespace().next().unwrap_or_default();
+
+    let command = match name {
+        "s" => DebugCommand::Step(parse_step(command)?.unwrap_or(1)),
+        "n" => DebugCommand::StepOver,
+        "f" => DebugCommand::StepOut,
+        "u" => DebugCommand::RunTo(parse_location(command, symbols)?),
+        "b" => DebugCommand::AddBreakpoint(parse_breakpoint(command, symbols)?),
+        "ignore" => {
+            let (location, count) = parse_ignore(command, symbols)?;
+            DebugCommand::SetIgnoreCount { location, count }
+        }
+        "commands" => {
+            let (location, commands) = parse_breakpoint_commands(command, symbols, scripts)?;
+            DebugCommand::SetBreakpointCommands { location, commands }
+        }
+        "w" => DebugCommand::AddWatchpoint(parse_watchpoint(command)?),
+        "unwatch" => DebugCommand::RemoveWatchpoint(parse_index(command)?),
+        "l" => DebugCommand::ListBreakpoints,
+        "r" => DebugCommand::Continue,
+        "p" => DebugCommand::CurrentInstruction,
+        "bt" => DebugCommand::Backtrace,
+        "disasm" => {
+            let (location, count) = parse_disasm(command, symbols)?;
+            DebugCommand::Disassemble { location, count }
+        }
+        "set" => {
+            let (name, value) = split_argument(command)?;
+            DebugCommand::SetRegister {
+                name: name.to_owned(),
+                value: parse_expr(value)?,
+            }
+        }
+        "poke" | "pokew" => {
+            let (location, value) = split_argument(command)?;
+            DebugCommand::Poke {
+                location: parse_symbol(location, symbols)?,
+                value: parse_expr(value)?,
+                word: name == "pokew",
+            }
+        }
+        "fill" => {
+            let (range, value) = split_argument(command)?;
+            let (start, end) = range
+                .split_once('-')
+                .ok_or_else(|| anyhow!("`fill` takes a range of addresses"))?;
+
+            DebugCommand::Fill {
+                start: parse_symbol(start, symbols)?,
+                end: parse_address(end)?,
+                value: parse_expr(value)?,
+            }
+        }
+        "unpatch" => DebugCommand::Unpatch,
+        "d" => {
+            let components = command.split_whitespace().collect::<Vec<_>>();
+
+            match components[1..] {
+                [] => DebugCommand::Dump(None),
+                [range] => DebugCommand::Dump(Some(parse_range(range, symbols)?)),
+                _ => bail!("`d` takes an optional range"),
+            }
+        }
+        "save" | "load" => {
+            let components = command.split_whitespace().collect::<Vec<_>>();
+
+            let (range, path) = match components[1..] {
+                [range, path] => (parse_range(range, symbols)?, path.into()),
+                _ => bail!("`{}` takes a range and a file", name),
+            };
+
+            if name == "save" {
+                DebugCommand::SaveMemory { range, path }
+            } else {
+                DebugCommand::LoadMemory { range, path }
+            }
+        }
+        "c" => DebugCommand::CpuState,
+        "print" => DebugCommand::Print(parse_print(command)?),
+        "profile" => {
+            let components = command.split_whitespace().collect::<Vec<_>>();
+
+            match components[1..] {
+                [] => DebugCommand::Profile {
+                    count: DEFAULT_PROFILE_COUNT,
+                },
+                ["reset"] => DebugCommand::ResetProfile,
+                ["report", path] => DebugCommand::WriteProfile(path.into()),
+                ["folded", path] => DebugCommand::WriteFoldedProfile(path.into()),
+                [count] => DebugCommand::Profile {
+                    count: count.parse().context("could not parse count")?,
+                },
+                _ => bail!("unknown `profile` arguments"),
+            }
+        }
+        "cdl" => {
+            let components = command.split_whitespace().collect::<Vec<_>>();
+
+            match components[1..] {
+                [] => DebugCommand::CodeDataReport,
+                ["save"] => DebugCommand::SaveCodeDataLog,
+                _ => bail!("unknown `cdl` arguments"),
+            }
+        }
+        "search" => parse_search(command)?,
+        "mute" => DebugCommand::Mute(parse_channel(command)?),
+        "solo" => DebugCommand::Solo(parse_channel(command)?),
+        "channels" => DebugCommand::Channels,
+        "exec" => {
+            let path = command.trim()[name.len()..].trim();
+            if path.is_empty() {
+                bail!("`exec` takes a file");
+            }
+
+            let script = fs::read_to_string(path)
+                .with_context(|| format!("could not read script '{}'", path))?;
+            let file = fs::canonicalize(path)
+                .with_context(|| format!("could not read script '{}'", path))?;
+            if scripts.contains(&file) {
+                bail!("script '{}' executes itself", path);
+            }
+
+            let scripts = [scripts, &[file]].concat();
+            DebugCommand::Exec(parse_script_in(&script, symbols, &scripts)?)
+        }
+        "q" => DebugCommand::Quit,
+        _ => bail!("unknown command"),
+    };
+
+    Ok(Some(command))
+}
+
+/// Parse a script of debugger commands, one per line. Empty lines and lines starting with `#` are
+/// skipped.
+pub fn parse_script(script: &str, symbols: &SymbolTable) -> Result<Vec<DebugCommand>> {
+    parse_script_in(script, symbols, &[])
+}
+
+fn parse_script_in(
+    script: &str,
+    symbols: &SymbolTable,
+    scripts: &[PathBuf],
+) -> Result<Vec<DebugCommand>> {
+    let mut commands = vec![];
+
+    for (number, line) in script.lines().enumerate() {
+        let line = line.trim();
+
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let command = parse_command_in(line, symbols, scripts)
+            .with_context(|| format!("line {}", number + 1))?;
+        commands.extend(command);
+    }
+
+    Ok(commands)
+}
+
+fn parse_step(command: &str) -> Result<Option<u32>> {
+    let components = command.split(' ').collect::<Vec<_>>();
+
+    match components.len() {
+        1 => return Ok(None),
+        2 => (),
+        _ => bail!("`s` takes a single optional argument"),
+    }
+
+    let step = components[1]
+        .parse()
+        .context("could not parse step count")?;
+
+    Ok(Some(step))
+}
+
+fn parse_breakpoint(command: &str, symbols: &SymbolTable) -> Result<Breakpoint> {
+    let mut components = command.split_whitespace().skip(1);
+
+    let address = components
+        .next()
+        .ok_or_else(|| anyhow!("`b` takes an address and an optional condition"))?;
+    let mut breakpoint = Breakpoint::at(parse_symbol(address, symbols)?);
+
+    match components.next() {
+        None => Ok(breakpoint),
+        Some("if") => {
+            let condition = components.collect::<Vec<_>>().join(" ");
+            breakpoint.condition = Some(condition.parse().context("could not parse condition")?);
+            Ok(breakpoint)
+        }
+        Some(_) => bail!("expected `if` after the breakpoint address"),
+    }
+}
+
+fn parse_breakpoint_commands(
+    command: &str,
+    symbols: &SymbolTable,
+    scripts: &[PathBuf],
+) -> Result<(BankedAddress, Vec<DebugCommand>)> {
+    let mut components = command.trim().splitn(3, char::is_whitespace).skip(1);
+
+    let location = components
+        .next()
+        .ok_or_else(|| anyhow!("`commands` takes a location and a list of commands"))?;
+    let location = parse_symbol(location, symbols)?;
+
+    let commands = components
+        .next()
+        .unwrap_or_default()
+        .split(';')
+        .map(|command| parse_command_in(command.trim(), symbols, scripts))
+        .filter_map(Result::transpose)
+        .collect::<Result<_>>()?;
+
+    Ok((location, commands))
+}
+
+fn parse_print(command: &str) -> Result<Vec<Expr>> {
+    let expressions = command.trim()["print".len()..].trim();
+
+    if expressions.is_empty() {
+        bail!("`print` takes one or more expressions");
+    }
+
+    expressions.split(',').map(parse_expr).collect()
+}
+
+/// Splits a command of the form `<name> <argument> <expression>` into the argument and the
+/// expression.
+fn split_argument(command: &str) -> Result<(&str, &str)> {
+    let mut components = command.trim().splitn(3, char::is_whitespace).skip(1);
+
+    match (components.next(), components.next()) {
+        (Some(argument), Some(expression)) if !expression.trim().is_empty() => {
+            Ok((argument, expression))
+        }
+        _ => bail!("expected an argument and a value"),
+    }
+}
+
+/// Parses an expression, which is evaluated when the command is executed.
+fn parse_expr(expression: &str) -> Result<Expr> {
+    expression.parse().context("could not parse value")
+}
+
+/// Parses a location, which is either the name of a symbol or a possibly banked address.
+fn parse_symbol(location: &str, symbols: &SymbolTable) -> Result<BankedAddress> {
+    match symbols.lookup(location) {
+        Some(location) => Ok(location),
+        None if location.contains(':') || location.starts_with("0x") => Ok(location.parse()?),
+        None => bail!("unknown symbol '{}'", location),
+    }
+}
+
+/// Parses a range of memory, which is either the name of a region, or a location and an optional
+/// end address. Without an end, the range extends to the end of the bank or the address space.
+fn parse_range(range: &str, symbols: &SymbolTable) -> Result<MemoryRange> {
+    if let Some(region) = MemoryRange::region(range) {
+        return Ok(region);
+    }
+
+    match range.split_once('-') {
+        Some((start, end)) => {
+            let range = MemoryRange::new(parse_symbol(start, symbols)?, parse_address(end)?);
+            if range.start.address > range.end {
+                bail!("range start must not be after its end");
+            }
+
+            Ok(range)
+        }
+        None => Ok(MemoryRange::starting_at(parse_symbol(range, symbols)?)),
+    }
+}
+
+fn parse_location(command: &str, symbols: &SymbolTable) -> Result<BankedAddress> {
+    let components = command.split_whitespace().collect::<Vec<_>>();
+
+    if components.len() != 2 {
+        bail!("`{}` takes a single argument", components[0]);
+    }
+
+    parse_symbol(components[1], symbols)
+}
+
+fn parse_ignore(command: &str, symbols: &SymbolTable) -> Result<(BankedAddress, u32)> {
+    let components = command.split_whitespace().collect::<Vec<_>>();
+
+    if components.len() != 3 {
+        bail!("`ignore` takes an address and a count");
+    }
+
+    let location = parse_symbol(components[1], symbols)?;
+    let count = components[2].parse().context("could not parse count")?;
+
+    Ok((location, count))
+}
+
+/// The number of addresses that are shown by `profile` if no count is given.
+const DEFAULT_PROFILE_COUNT: usize = 20;
+
+/// The number of search candidates that are listed by `search` if no count is given.
+const DEFAULT_SEARCH_COUNT: usize = 20;
+
+/// The number of instructions that are disassembled if no count is given.
+const DEFAULT_DISASM_COUNT: usize = 10;
+
+fn parse_disasm(command: &str, symbols: &SymbolTable) -> Result<(Option<BankedAddress>, usize)> {
+    let components = command.split_whitespace().collect::<Vec<_>>();
+
+    if components.len() > 3 {
+        bail!("`disasm` takes an optional address and count");
+    }
+
+    let location = components
+        .get(1)
+        .map(|s| parse_symbol(s, symbols))
+        .transpose()?;
+    let count = components
+        .get(2)
+        .map(|s| s.parse())
+        .transpose()
+        .context("could not parse count")?
+        .unwrap_or(DEFAULT_DISASM_COUNT);
+
+    Ok((location, count))
+}
+
+fn parse_search(command: &str) -> Result<DebugCommand> {
+    let components = command.split_whitespace().collect::<Vec<_>>();
+
+    let filter = match components[1..] {
+        [] => {
+            return Ok(DebugCommand::ListSearch {
+                count: DEFAULT_SEARCH_COUNT,
+            })
+        }
+        ["start"] => return Ok(DebugCommand::StartSearch),
+        ["changed"] => SearchFilter::Changed,
+        ["unchanged"] => SearchFilter::Unchanged,
+        ["increased"] => SearchFilter::Increased(None),
+        ["increased", amount] => SearchFilter::Increased(Some(parse_byte(amount)?)),
+        ["decreased"] => SearchFilter::Decreased(None),
+        ["decreased", amount] => SearchFilter::Decreased(Some(parse_byte(amount)?)),
+        ["equals", value] => SearchFilter::Equals(parse_byte(value)?),
+        [argument] => match argument.parse() {
+            Ok(count) => return Ok(DebugCommand::ListSearch { count }),
+            Err(_) => bail!("unknown `search` filter '{}'", argument),
+        },
+        _ => bail!("unknown `search` arguments"),
+    };
+
+    Ok(DebugCommand::FilterSearch(filter))
+}
+
+/// Parses a byte, in decimal or in hexadecimal with a `0x` prefix.
+fn parse_byte(byte: &str) -> Result<u8> {
+    match byte.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => byte.parse(),
+    }
+    .with_context(|| format!("could not parse byte '{}'", byte))
+}
+
+fn parse_watchpoint(command: &str) -> Result<Watchpoint> {
+    let components = command.split_whitespace().collect::<Vec<_>>();
+
+    if components.len() != 3 {
+        bail!("`w` takes a kind and an address or range");
+    }
+
+    let kind = components[1].parse::<WatchKind>()?;
+
+    let range = match components[2].split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse_address(start)?, parse_address(end)?);
+            if start > end {
+                bail!("range start must not be after its end");
+            }
+            start..=end
+        }
+        None => {
+            let address = parse_address(components[2])?;
+            address..=address
+        }
+    };
+
+    Ok(Watchpoint::new(range, kind))
+}
+
+fn parse_index(command: &str) -> Result<usize> {
+    let components = command.split_whitespace().collect::<Vec<_>>();
+
+    if components.len() != 2 {
+        bail!("`{}` takes a single argument", components[0]);
+    }
+
+    components[1].parse().context("could not parse index")
+}
+
+fn parse_address(address: &str) -> Result<u16> {
+    if !address.starts_with("0x") {
+        bail!("address must start with '0x'");
+    }
+
+    u16::from_str_radix(&address[2..], 16).context("could not parse hexadecimal number")
+}
+
+fn parse_channel(command: &str) -> Result<Channel> {
+    let components = command.split_whitespace().collect::<Vec<_>>();
+
+    if components.len() != 2 {
+        bail!("`{}` takes a single argument", components[0]);
+    }
+
+    Ok(components[1].parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use crate::audio::Channel;
+    use crate::bus::{WatchKind, Watchpoint};
+    use crate::debugger::{
+        BankedAddress, Breakpoint, DebugCommand, MemoryRange, SearchFilter, SymbolTable,
+    };
+
+    #[test]
+    fn parse_breakpoint() {
+        assert_eq!(
+            super::parse_breakpoint("b 0x174", &SymbolTable::default())
+                .unwrap()
+                .address,
+            0x174
+        );
+
+        let breakpoint =
+            super::parse_breakpoint("b 0x174 if a == 0x3c && [hl] > 4", &SymbolTable::default())
+                .unwrap();
+        assert_eq!(breakpoint.address, 0x174);
+        assert_eq!(
+            breakpoint.condition.unwrap().to_string(),
+            "a == 0x3c && [hl] > 4"
+        );
+
+        let breakpoint = super::parse_breakpoint("b 3:0x4123", &SymbolTable::default()).unwrap();
+        assert_eq!((breakpoint.bank, breakpoint.address), (Some(3), 0x4123));
+
+        assert!(super::parse_breakpoint("b 0x174 if", &SymbolTable::default()).is_err());
+        assert!(super::parse_breakpoint("b 0x174 ly == 144", &SymbolTable::default()).is_err());
+
+        let symbols = SymbolTable::parse("01:4000 PlayerUpdate");
+        let breakpoint = super::parse_breakpoint("b PlayerUpdate if a == 1", &symbols).unwrap();
+        assert_eq!((breakpoint.bank, breakpoint.address), (Some(1), 0x4000));
+        assert!(super::parse_breakpoint("b EnemyUpdate", &symbols).is_err());
+    }
+
+    #[test]
+    fn parse_disasm() {
+        assert_eq!(
+            super::parse_disasm("disasm", &SymbolTable::default()).unwrap(),
+            (None, 10)
+        );
+        assert_eq!(
+            super::parse_disasm("disasm 2:0x4000 5", &SymbolTable::default()).unwrap(),
+            (Some(BankedAddress::with_bank(2, 0x4000)), 5)
+        );
+        assert!(super::parse_disasm("disasm 0x4000 x", &SymbolTable::default()).is_err());
+    }
+
+    #[test]
+    fn parse_command() {
+        let symbols = SymbolTable::parse("00:c000 wBuffer");
+
+        assert_eq!(super::parse_command("", &symbols).unwrap(), None);
+        assert_eq!(
+            super::parse_command("s 3", &symbols).unwrap(),
+            Some(DebugCommand::Step(3))
+        );
+        assert_eq!(
+            super::parse_command("pokew wBuffer [hl] + 1", &symbols).unwrap(),
+            Some(DebugCommand::Poke {
+                location: BankedAddress::new(0xC000),
+                value: "[hl] + 1".parse().unwrap(),
+                word: true,
+            })
+        );
+        assert_eq!(
+            super::parse_command("print a, [hl]", &symbols).unwrap(),
+            Some(DebugCommand::Print(vec![
+                "a".parse().unwrap(),
+                "[hl]".parse().unwrap()
+            ]))
+        );
+        assert!(super::parse_command("print", &symbols).is_err());
+        assert!(super::parse_command("zz", &symbols).is_err());
+        assert!(super::parse_command("cdl load", &symbols).is_err());
+    }
+
+    #[test]
+    fn parse_script() {
+        let symbols = SymbolTable::parse("00:c000 wBuffer");
+        let script = "\
+# Log the buffer whenever it is filled.
+b wBuffer
+commands wBuffer print [0xc000], [0xc001]; r
+
+r
+";
+
+        assert_eq!(
+            super::parse_script(script, &symbols).unwrap(),
+            [
+                DebugCommand::AddBreakpoint(Breakpoint::new(0xC000)),
+                DebugCommand::SetBreakpointCommands {
+                    location: BankedAddress::new(0xC000),
+                    commands: vec![
+                        DebugCommand::Print(vec![
+                            "[0xc000]".parse().unwrap(),
+                            "[0xc001]".parse().unwrap()
+                        ]),
+                        DebugCommand::Continue,
+                    ],
+                },
+                DebugCommand::Continue,
+            ]
+        );
+
+        let err = super::parse_script("s\nb zz", &symbols).unwrap_err();
+        assert_eq!(format!("{:#}", err), "line 2: unknown symbol 'zz'");
+    }
+
+    #[test]
+    fn exec_cycle() {
+        let symbols = SymbolTable::default();
+        let dir = env::temp_dir().join(format!("feo-boy-exec-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let first = dir.join("first.txt");
+        let second = dir.join("second.txt");
+        fs::write(&first, format!("s\nexec {}", second.display())).unwrap();
+        fs::write(&second, format!("exec {}", first.display())).unwrap();
+        let looping = dir.join("loop.txt");
+        fs::write(
+            &looping,
+            format!("commands 0xc000 exec {}", looping.display()),
+        )
+        .unwrap();
+
+        let first_err = super::parse_command(&format!("exec {}", first.display()), &symbols);
+        let looping_err = super::parse_command(&format!("exec {}", looping.display()), &symbols);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(format!("{:#}", first_err.unwrap_err())
+            .ends_with(&format!("script '{}' executes itself", first.display())));
+        assert!(format!("{:#}", looping_err.unwrap_err()).ends_with("executes itself"));
+    }
+
+    #[test]
+    fn parse_range() {
+        let symbols = SymbolTable::parse("01:4000 PlayerUpdate");
+
+        assert_eq!(
+            super::parse_range("PlayerUpdate-0x40ff", &symbols).unwrap(),
+            MemoryRange::new(BankedAddress::with_bank(1, 0x4000), 0x40FF)
+        );
+        assert_eq!(
+            super::parse_range("0xc100", &symbols).unwrap(),
+            MemoryRange::new(BankedAddress::new(0xC100), 0xFFFF)
+        );
+        assert_eq!(
+            super::parse_range("sram:2", &symbols).unwrap(),
+            MemoryRange::new(BankedAddress::with_bank(2, 0xA000), 0xBFFF)
+        );
+        assert!(super::parse_range("0xc100-0xc000", &symbols).is_err());
+        assert!(super::parse_range("tiles", &symbols).is_err());
+
+        assert_eq!(
+            super::parse_command("save vram vram.bin", &symbols).unwrap(),
+            Some(DebugCommand::SaveMemory {
+                range: MemoryRange::region("vram").unwrap(),
+                path: "vram.bin".into(),
+            })
+        );
+        assert!(super::parse_command("load 0xc000", &symbols).is_err());
+    }
+
+    #[test]
+    fn parse_search() {
+        assert_eq!(
+            super::parse_search("search").unwrap(),
+            DebugCommand::ListSearch { count: 20 }
+        );
+        assert_eq!(
+            super::parse_search("search decreased 1").unwrap(),
+            DebugCommand::FilterSearch(SearchFilter::Decreased(Some(1)))
+        );
+        assert_eq!(
+            super::parse_search("search equals 0x63").unwrap(),
+            DebugCommand::FilterSearch(SearchFilter::Equals(99))
+        );
+        assert!(super::parse_search("search equals 256").is_err());
+        assert!(super::parse_search("search bigger").is_err());
+    }
+
+    #[test]
+    fn split_argument() {
+        assert_eq!(
+            super::split_argument("set a [hl] + 1").unwrap(),
+            ("a", "[hl] + 1")
+        );
+        assert!(super::split_argument("poke 0xc000").is_err());
+    }
+
+    #[test]
+    fn parse_watchpoint() {
+        assert_eq!(
+            super::parse_watchpoint("w w 0xc000").unwrap(),
+            Watchpoint::new(0xC000..=0xC000, WatchKind::Write)
+        );
+        assert_eq!(
+            super::parse_watchpoint("w c 0xff80-0xfffe").unwrap(),
+            Watchpoint::new(0xFF80..=0xFFFE, WatchKind::Change)
+        );
+        assert!(super::parse_watchpoint("w x 0xc000").is_err());
+        assert!(super::parse_watchpoint("w r 0xc100-0xc000").is_err());
+        assert!(super::parse_watchpoint("w r").is_err());
+    }
+
+    #[test]
+    fn parse_channel() {
+        assert_eq!(super::parse_channel("mute 1").unwrap(), Channel::Square1);
+        assert_eq!(super::parse_channel("solo wave").unwrap(), Channel::Wave);
+        assert!(super::parse_channel("mute 5").is_err());
+        assert!(super::parse_channel("mute").is_err());
+    }
+}
@@ -0,0 +1,222 @@
+//! The line-based debugger prompt.
+
+#[cfg(unix)]
+use std::io;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+use anyhow::Result;
+#[cfg(unix)]
+use nix::sys::termios::{self, SetArg, Termios};
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+
+use crate::debugger::{DebugCommand, DebugFrontend, DebugResponse};
+use crate::Emulator;
+
+use super::{parse_command, COMMANDS, HELP};
+
+/// A message from the emulator to the prompt thread.
+enum Message {
+    /// Print text above the prompt.
+    Print(String),
+
+    /// Read a line of input.
+    Prompt,
+}
+
+/// The debugger prompt, read from stdin.
+///
+/// Lines are read on a separate thread, so that the window stays responsive while the prompt is
+/// waiting for input. Closing the prompt with Ctrl-C or Ctrl-D quits the emulator.
+#[derive(Default)]
+pub struct Repl {
+    /// The channels to the prompt thread, and its handle. The thread is started when output or
+    /// input is first needed.
+    thread: Option<(Sender<Message>, Receiver<String>, JoinHandle<()>)>,
+
+    /// Whether the prompt thread is waiting for input.
+    prompting: bool,
+
+    /// The mode of the terminal before the prompt thread started, if stdin is a terminal.
+    #[cfg(unix)]
+    terminal: Option<TerminalMode>,
+}
+
+impl Repl {
+    /// Creates a prompt. No input is read until the debugger needs a command.
+    pub fn new() -> Repl {
+        Repl::default()
+    }
+
+    fn print(&mut self, text: String) {
+        self.start();
+        let (messages, _, _) = self.thread.as_ref().unwrap();
+        let _ = messages.send(Message::Print(text));
+    }
+
+    /// Starts the prompt thread, if it has not been started yet.
+    fn start(&mut self) {
+        if self.thread.is_none() {
+            #[cfg(unix)]
+            {
+                self.terminal = TerminalMode::save(io::stdin().as_raw_fd());
+            }
+
+            self.thread = Some(spawn_prompt());
+        }
+    }
+}
+
+impl DebugFrontend for Repl {
+    fn poll_command(&mut self, emulator: &Emulator) -> Option<DebugCommand> {
+        if !emulator.is_paused() {
+            return None;
+        }
+
+        self.start();
+        let (messages, lines, _) = self.thread.as_ref().unwrap();
+
+        if !self.prompting {
+            // If the thread has exited, the quit is picked up below.
+            let _ = messages.send(Message::Prompt);
+            self.prompting = true;
+        }
+
+        let line = match lines.try_recv() {
+            Ok(line) => line,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => return Some(DebugCommand::Quit),
+        };
+        self.prompting = false;
+
+        match line.trim() {
+            "?" => {
+                self.print(String::from(HELP));
+                None
+            }
+            line => match parse_command(line, emulator.symbols()) {
+                Ok(command) => command,
+                Err(e) => {
+                    self.print(format!("error: {:#}", e));
+                    None
+                }
+            },
+        }
+    }
+
+    fn respond(&mut self, response: Result<DebugResponse>) {
+        match response {
+            Ok(DebugResponse::Done) => (),
+            Ok(response) => self.print(response.to_string()),
+            Err(e) => self.print(format!("error: {:#}", e)),
+        }
+    }
+}
+
+impl Drop for Repl {
+    fn drop(&mut self) {
+        if let Some((messages, _, handle)) = self.thread.take() {
+            // Wait for any remaining output to be printed, unless the thread is blocked on input.
+            drop(messages);
+            if !self.prompting {
+                let _ = handle.join();
+                return;
+            }
+
+            // The process may exit while the line is being read, so the prompt never gets a
+            // chance to take the terminal out of raw mode.
+            #[cfg(unix)]
+            if let Some(terminal) = &self.terminal {
+                terminal.restore();
+            }
+        }
+    }
+}
+
+/// The mode of a terminal, saved so that it can be restored.
+#[cfg(unix)]
+struct TerminalMode {
+    fd: RawFd,
+    termios: Termios,
+}
+
+#[cfg(unix)]
+impl TerminalMode {
+    /// Saves the mode of a terminal. Returns `None` if the file descriptor is not a terminal.
+    fn save(fd: RawFd) -> Option<TerminalMode> {
+        let termios = termios::tcgetattr(fd).ok()?;
+        Some(TerminalMode { fd, termios })
+    }
+
+    fn restore(&self) {
+        let _ = termios::tcsetattr(self.fd, SetArg::TCSANOW, &self.termios);
+    }
+}
+
+/// Starts the thread that prints output and reads lines of input.
+fn spawn_prompt() -> (Sender<Message>, Receiver<String>, JoinHandle<()>) {
+    let (message_tx, message_rx) = mpsc::channel();
+    let (line_tx, line_rx) = mpsc::channel();
+
+    let handle = thread::spawn(move || {
+        let mut editor = Editor::<()>::new();
+        let prompt = format!("feo debug [{}] >> ", COMMANDS);
+
+        for message in message_rx {
+            match message {
+                Message::Print(text) => println!("{}", text),
+                Message::Prompt => match editor.readline(&prompt) {
+                    Ok(line) => {
+                        editor.add_history_entry(&line);
+                        if line_tx.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+                    Err(err) => {
+                        eprintln!("unable to read debugger command: {}", err);
+                        break;
+                    }
+                },
+            }
+        }
+    });
+
+    (message_tx, line_rx, handle)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use nix::pty;
+    use nix::sys::termios::{self, LocalFlags, SetArg};
+    use nix::unistd;
+
+    use super::{spawn_prompt, Repl, TerminalMode};
+
+    #[test]
+    fn quit_while_prompting() {
+        let pty = pty::openpty(None, None).unwrap();
+        let terminal = TerminalMode::save(pty.slave);
+
+        // Reading a line puts the terminal in raw mode until the line is read.
+        let mut raw = termios::tcgetattr(pty.slave).unwrap();
+        termios::cfmakeraw(&mut raw);
+        termios::tcsetattr(pty.slave, SetArg::TCSANOW, &raw).unwrap();
+
+        let repl = Repl {
+            thread: Some(spawn_prompt()),
+            prompting: true,
+            terminal,
+        };
+        drop(repl);
+
+        let restored = termios::tcgetattr(pty.slave).unwrap();
+        assert!(restored.local_flags.contains(LocalFlags::ECHO));
+
+        unistd::close(pty.slave).unwrap();
+        unistd::close(pty.master).unwrap();
+    }
+}
@@ -36,6 +36,38 @@ fn assert_rom_output(rom: &'static [u8], duration: Duration, output: &str) -> Re
     Ok(())
 }
 
+/// Creates a new emulator, runs it for a simulated duration, and then asserts that the ROM reported
+/// success in cartridge RAM.
+///
+/// Newer test ROMs write a signature to 0xA001-0xA003, their result to 0xA000, and their output
+/// text to 0xA004 onwards.
+fn assert_rom_passed(rom: &'static [u8], duration: Duration) -> Result<()> {
+    let mut emulator = Emulator::builder().build();
+
+    emulator.load_rom(rom)?;
+    emulator.reset();
+    emulator.update(duration)?;
+
+    let bus = &emulator.bus;
+
+    let signature = [0xA001, 0xA002, 0xA003].map(|address| bus.read_byte_no_tick(address));
+    assert_eq!(
+        signature,
+        [0xDE, 0xB0, 0x61],
+        "test did not write its signature"
+    );
+
+    let output = (0xA004..)
+        .map(|address| bus.read_byte_no_tick(address))
+        .take_while(|&byte| byte != 0)
+        .map(char::from)
+        .collect::<String>();
+
+    assert_eq!(bus.read_byte_no_tick(0xA000), 0, "{}", output);
+
+    Ok(())
+}
+
 #[test]
 fn cpu_instrs() -> Result<()> {
     assert_rom_output(
@@ -85,3 +117,43 @@ fn mem_timing() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn dmg_sound_registers() -> Result<()> {
+    assert_rom_passed(
+        include_bytes!("./gb-test-roms/dmg_sound/rom_singles/01-registers.gb"),
+        Duration::from_secs(2),
+    )
+}
+
+#[test]
+fn dmg_sound_len_ctr() -> Result<()> {
+    assert_rom_passed(
+        include_bytes!("./gb-test-roms/dmg_sound/rom_singles/02-len ctr.gb"),
+        Duration::from_secs(5),
+    )
+}
+
+#[test]
+fn dmg_sound_trigger() -> Result<()> {
+    assert_rom_passed(
+        include_bytes!("./gb-test-roms/dmg_sound/rom_singles/03-trigger.gb"),
+        Duration::from_secs(5),
+    )
+}
+
+#[test]
+fn dmg_sound_len_ctr_during_power() -> Result<()> {
+    assert_rom_passed(
+        include_bytes!("./gb-test-roms/dmg_sound/rom_singles/08-len ctr during power.gb"),
+        Duration::from_secs(2),
+    )
+}
+
+#[test]
+fn dmg_sound_regs_after_power() -> Result<()> {
+    assert_rom_passed(
+        include_bytes!("./gb-test-roms/dmg_sound/rom_singles/11-regs after power.gb"),
+        Duration::from_secs(2),
+    )
+}